@@ -7,6 +7,7 @@ fn main() -> Result<(), Box<dyn Error>> {
             "../proto/worker/api/version.proto",
             "../proto/worker/api/v1/limits.proto",
             "../proto/worker/api/v1/download.proto",
+            "../proto/worker/api/v1/media.proto",
         ],
         &["../proto"],
     )?;
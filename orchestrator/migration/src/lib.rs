@@ -2,6 +2,8 @@ pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_create_table;
 mod m20251113_100712_remove_chat_from_downloaded_media;
+mod m20251201_120000_add_gallery_media_types;
+mod m20251215_120000_add_voice_media_types;
 
 pub struct Migrator;
 
@@ -11,6 +13,8 @@ impl MigratorTrait for Migrator {
         vec![
             Box::new(m20220101_000001_create_table::Migration),
             Box::new(m20251113_100712_remove_chat_from_downloaded_media::Migration),
+            Box::new(m20251201_120000_add_gallery_media_types::Migration),
+            Box::new(m20251215_120000_add_voice_media_types::Migration),
         ]
     }
 }
@@ -0,0 +1,101 @@
+use sea_orm_migration::{
+    async_trait::async_trait,
+    prelude::{extension::postgres::Type, *},
+    schema::*,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_type(
+                Type::alter()
+                    .name(MediaType)
+                    .add_value(MediaTypeVariants::Photo)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_type(
+                Type::alter()
+                    .name(MediaType)
+                    .add_value(MediaTypeVariants::Gallery)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        // Every item of a gallery is cached with its own `file_id` under the same media id
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DownloadedMedia::Table)
+                    .add_column(small_integer(DownloadedMedia::Position).default(0))
+                    .to_owned(),
+            )
+            .await?;
+        // The old key was created with the table, so it's a constraint rather than a plain index
+        let db = manager.get_connection();
+        db.execute_unprepared(r#"ALTER TABLE "downloaded_media" DROP CONSTRAINT "idx_downloaded_media_id_domain_type""#)
+            .await?;
+        db.execute_unprepared(
+            r#"ALTER TABLE "downloaded_media" ADD CONSTRAINT "idx_downloaded_media_id_domain_type_position" UNIQUE ("id", "domain", "media_type", "position")"#,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Postgres can't drop enum values, so only the rows that use them are removed
+        manager
+            .exec_stmt(
+                Query::delete()
+                    .from_table(DownloadedMedia::Table)
+                    .and_where(
+                        Expr::col(DownloadedMedia::MediaType)
+                            .cast_as(Alias::new("text"))
+                            .is_in(["photo", "gallery"]),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        let db = manager.get_connection();
+        db.execute_unprepared(r#"ALTER TABLE "downloaded_media" DROP CONSTRAINT "idx_downloaded_media_id_domain_type_position""#)
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DownloadedMedia::Table)
+                    .drop_column(DownloadedMedia::Position)
+                    .to_owned(),
+            )
+            .await?;
+        db.execute_unprepared(
+            r#"ALTER TABLE "downloaded_media" ADD CONSTRAINT "idx_downloaded_media_id_domain_type" UNIQUE ("id", "domain", "media_type")"#,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+struct MediaType;
+
+#[derive(DeriveIden)]
+enum MediaTypeVariants {
+    Photo,
+    Gallery,
+}
+
+#[derive(DeriveIden)]
+enum DownloadedMedia {
+    Table,
+    MediaType,
+    Position,
+}
@@ -0,0 +1,66 @@
+use sea_orm_migration::{
+    async_trait::async_trait,
+    prelude::{extension::postgres::Type, *},
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_type(
+                Type::alter()
+                    .name(MediaType)
+                    .add_value(MediaTypeVariants::Voice)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_type(
+                Type::alter()
+                    .name(MediaType)
+                    .add_value(MediaTypeVariants::VideoNote)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Postgres can't drop enum values, so only the rows that use them are removed
+        manager
+            .exec_stmt(
+                Query::delete()
+                    .from_table(DownloadedMedia::Table)
+                    .and_where(
+                        Expr::col(DownloadedMedia::MediaType)
+                            .cast_as(Alias::new("text"))
+                            .is_in(["voice", "video_note"]),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+struct MediaType;
+
+#[derive(DeriveIden)]
+enum MediaTypeVariants {
+    Voice,
+    VideoNote,
+}
+
+#[derive(DeriveIden)]
+enum DownloadedMedia {
+    Table,
+    MediaType,
+}
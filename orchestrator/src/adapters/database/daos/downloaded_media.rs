@@ -33,12 +33,13 @@ where
             domain,
             display_id,
             media_type,
+            position,
             created_at,
         }: DownloadedMedia,
     ) -> Result<(), ErrorKind<Infallible>> {
         use downloaded_media::{
             ActiveModel,
-            Column::{Domain, Id, MediaType},
+            Column::{Domain, Id, MediaType, Position},
             Entity,
         };
 
@@ -48,11 +49,12 @@ where
             display_id: Set(display_id.into()),
             domain: Set(domain.into()),
             media_type: Set(media_type.into()),
+            position: Set(position),
             created_at: Set(created_at),
         };
 
         Entity::insert(model)
-            .on_conflict(OnConflict::columns([Id, Domain, MediaType]).do_nothing().to_owned())
+            .on_conflict(OnConflict::columns([Id, Domain, MediaType, Position]).do_nothing().to_owned())
             .exec_without_returning(self.conn)
             .await
             .map(|_| ())
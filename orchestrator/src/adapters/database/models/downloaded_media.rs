@@ -11,6 +11,7 @@ pub struct Model {
     pub display_id: Option<String>,
     pub domain: Option<String>,
     pub media_type: MediaType,
+    pub position: i16,
     pub created_at: TimeDateTimeWithTimeZone,
 }
 
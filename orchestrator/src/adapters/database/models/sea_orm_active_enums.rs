@@ -9,4 +9,12 @@ pub enum MediaType {
     Video,
     #[sea_orm(string_value = "audio")]
     Audio,
+    #[sea_orm(string_value = "photo")]
+    Photo,
+    #[sea_orm(string_value = "gallery")]
+    Gallery,
+    #[sea_orm(string_value = "voice")]
+    Voice,
+    #[sea_orm(string_value = "video_note")]
+    VideoNote,
 }
@@ -69,6 +69,9 @@ pub struct Config {
     pub database: Database,
     pub limits: Limits,
     pub tg_bot_api: TgBotApi,
+    /// Spans aren't exported if the section is missing
+    #[serde(default)]
+    pub tracing: Option<telemetry::Config>,
 }
 
 impl Config {
@@ -8,6 +8,8 @@ pub struct DownloadedMedia {
     pub display_id: Option<String>,
     pub domain: Option<String>,
     pub media_type: MediaType,
+    /// Order of the item in a gallery, `0` for other media types
+    pub position: i16,
     pub created_at: OffsetDateTime,
 }
 
@@ -19,6 +21,7 @@ impl From<Model> for DownloadedMedia {
             display_id,
             domain,
             media_type,
+            position,
             created_at,
         }: Model,
     ) -> Self {
@@ -28,6 +31,7 @@ impl From<Model> for DownloadedMedia {
             display_id,
             domain,
             media_type: media_type.into(),
+            position,
             created_at,
         }
     }
@@ -9,7 +9,6 @@ use telers::{
     filters::Command,
 };
 use tracing::{error, info};
-use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt as _, util::SubscriberInitExt as _};
 
 use crate::{
     config::{Config, get_config_path},
@@ -36,10 +35,7 @@ async fn main() -> Result<(), anyhow::Error> {
     let config_path = &*get_config_path();
     let config = Config::from_fs(config_path)?;
 
-    tracing_subscriber::registry()
-        .with(fmt::layer())
-        .with(EnvFilter::builder().parse_lossy(config.logging.dirs.as_ref()))
-        .init();
+    let tracer_provider = telemetry::init("orchestrator", &config.logging.dirs, config.tracing.as_ref())?;
 
     let base_url = format!("{}/bot{{token}}/{{method_name}}", config.tg_bot_api.url);
     let files_url = format!("{}/file{{token}}/{{path}}", config.tg_bot_api.url);
@@ -80,6 +76,9 @@ async fn main() -> Result<(), anyhow::Error> {
     }
 
     container.close().await;
+    if let Some(tracer_provider) = tracer_provider {
+        let _ = tracer_provider.shutdown();
+    }
 
     Ok(())
 }
@@ -3,6 +3,13 @@ use crate::adapters::database::models::sea_orm_active_enums::MediaType as Model;
 pub enum MediaType {
     Video,
     Audio,
+    Photo,
+    /// Post of several photos and videos, sent as media groups
+    Gallery,
+    /// Audio sent as a voice message
+    Voice,
+    /// Square video sent as a round video note
+    VideoNote,
 }
 
 impl From<Model> for MediaType {
@@ -10,6 +17,10 @@ impl From<Model> for MediaType {
         match value {
             Model::Video => MediaType::Video,
             Model::Audio => MediaType::Audio,
+            Model::Photo => MediaType::Photo,
+            Model::Gallery => MediaType::Gallery,
+            Model::Voice => MediaType::Voice,
+            Model::VideoNote => MediaType::VideoNote,
         }
     }
 }
@@ -19,6 +30,10 @@ impl From<MediaType> for Model {
         match value {
             MediaType::Video => Model::Video,
             MediaType::Audio => Model::Audio,
+            MediaType::Photo => Model::Photo,
+            MediaType::Gallery => Model::Gallery,
+            MediaType::Voice => Model::Voice,
+            MediaType::VideoNote => Model::VideoNote,
         }
     }
 }
@@ -0,0 +1,70 @@
+//! Logs and the OTLP export of the spans shared by the orchestrator and the worker
+
+use opentelemetry::{global, trace::TracerProvider as _};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig as _};
+use opentelemetry_sdk::{
+    Resource,
+    propagation::TraceContextPropagator,
+    trace::{Sampler, SdkTracerProvider},
+};
+use serde::Deserialize;
+use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt as _, util::SubscriberInitExt as _};
+
+pub use opentelemetry_otlp::ExporterBuildError;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Config {
+    /// gRPC endpoint of the OTLP collector
+    pub otlp_endpoint: Box<str>,
+    /// Name of the binary if it's not set
+    #[serde(default)]
+    pub service_name: Option<Box<str>>,
+    /// Share of the traces started here that are exported, the sampling decision of the caller is kept
+    #[serde(default = "Config::default_sample_ratio")]
+    pub sample_ratio: f64,
+}
+
+impl Config {
+    const fn default_sample_ratio() -> f64 {
+        1.0
+    }
+}
+
+/// Set up the logs and the OTLP export of the spans if it's configured.
+/// W3C trace context propagator is installed in any case, so the context crosses the gRPC calls.
+/// # Errors
+/// Returns [`ExporterBuildError`] if the exporter can't be built
+/// # Returns
+/// Returns the tracer provider that must be shut down to flush the remaining spans
+pub fn init(name: &'static str, log_dirs: &str, cfg: Option<&Config>) -> Result<Option<SdkTracerProvider>, ExporterBuildError> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let provider = match cfg {
+        Some(cfg) => {
+            let exporter = SpanExporter::builder()
+                .with_tonic()
+                .with_endpoint(cfg.otlp_endpoint.as_ref())
+                .build()?;
+            let service_name = cfg.service_name.as_deref().unwrap_or(name).to_owned();
+            Some(
+                SdkTracerProvider::builder()
+                    .with_batch_exporter(exporter)
+                    .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(cfg.sample_ratio))))
+                    .with_resource(Resource::builder().with_service_name(service_name).build())
+                    .build(),
+            )
+        }
+        None => None,
+    };
+    let otel_layer = provider
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer(name)));
+
+    tracing_subscriber::registry()
+        .with(fmt::layer())
+        .with(EnvFilter::builder().parse_lossy(log_dirs))
+        .with(otel_layer)
+        .init();
+
+    Ok(provider)
+}
@@ -1,2 +1,11 @@
+pub mod backend;
+pub mod cache;
+pub mod direct;
 pub mod ffmpeg;
+pub mod ffprobe;
+pub mod metrics;
+pub mod rate_limiter;
+pub mod scratch;
+pub mod shared_volume;
+pub mod sponsorblock;
 pub mod ytdl;
@@ -0,0 +1,291 @@
+use bytes::Bytes;
+use futures_util::StreamExt as _;
+use reqwest::{Client, StatusCode};
+use std::{
+    fs::File,
+    io,
+    os::fd::OwnedFd,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    sync::Arc,
+};
+use tokio::{
+    io::AsyncWriteExt as _,
+    sync::mpsc::{UnboundedSender, error::SendError, unbounded_channel},
+};
+use tracing::{Instrument as _, debug_span, error, instrument, trace};
+
+use crate::{
+    adapters::{
+        ffmpeg::{AudioInput, download_thumbnail_to_path, merge_streams},
+        metrics::{self, ProcessTimer},
+        rate_limiter::RateLimiter,
+        ytdl::{audio_args, download_to_path, download_to_pipe, is_rate_limited, pipe_args, video_args},
+    },
+    config,
+    entities::Cookie,
+    utils::format_error_report,
+};
+
+#[cfg(test)]
+pub mod fake;
+
+const RANGE_CHUNK_SIZE: i32 = 1024 * 1024 * 10;
+
+#[derive(thiserror::Error, Debug)]
+pub enum RangeErrorKind {
+    #[error("Channel error: {0}")]
+    Channel(#[from] SendError<Bytes>),
+    #[error("Request error: {0}")]
+    Reqwest(#[from] reqwest::Error),
+}
+
+/// Download of a single format to a file
+pub struct DownloadRequest<'a> {
+    /// Snapshot taken once per job, so all its calls use the same settings
+    pub yt_dlp_cfg: &'a config::YtDlp,
+    pub pot_provider_api_url: &'a str,
+    pub url: &'a str,
+    pub media_id: &'a str,
+    pub format_id: &'a str,
+    pub extension: &'a str,
+    pub output_dir: &'a Path,
+    /// Timeout in seconds
+    pub timeout: u64,
+    pub max_file_size: u32,
+    pub cookie: Option<&'a Cookie>,
+    pub sponsorblock_args: &'a [String],
+}
+
+/// Download of a single format to a pipe, the direct URL is fetched by ranges if the size is known
+pub struct StreamRequest<'a> {
+    /// Snapshot taken once per job, so all its calls use the same settings
+    pub yt_dlp_cfg: &'a config::YtDlp,
+    pub pot_provider_api_url: &'a str,
+    pub url: &'a str,
+    pub format_id: &'a str,
+    pub format_url: &'a str,
+    pub filesize: Option<f64>,
+    pub max_file_size: u32,
+    pub cookie: Option<&'a Cookie>,
+}
+
+/// Source of the media files.
+/// Output file of a download is `<media_id>.<extension>` in the output dir.
+#[allow(async_fn_in_trait)]
+pub trait MediaBackend: Send + Sync {
+    /// # Errors
+    /// Returns [`io::Error`] if the download fails or times out
+    async fn download_video(&self, request: DownloadRequest<'_>) -> Result<(), io::Error>;
+
+    /// Download the format and extract the audio in the requested codec
+    /// # Errors
+    /// Returns [`io::Error`] if the download fails or times out
+    async fn download_audio(&self, request: DownloadRequest<'_>) -> Result<(), io::Error>;
+
+    /// Start the download in the background, the pipe is closed when it ends
+    /// # Errors
+    /// Returns [`io::Error`] if the download can't be started
+    async fn stream_to_pipe(&self, fd: OwnedFd, request: StreamRequest<'_>) -> Result<(), io::Error>;
+
+    /// Convert the thumbnail to `<media_id>.jpg` in the output dir
+    async fn download_thumbnail(&self, url: &str, media_id: &str, output_dir: &Path) -> Option<PathBuf>;
+}
+
+/// Pipe of the audio track to merge
+#[derive(Debug)]
+pub struct AudioPipe {
+    pub fd: OwnedFd,
+    pub language: Option<String>,
+    pub default: bool,
+}
+
+/// Merger of the separate video and audio streams
+#[allow(async_fn_in_trait)]
+pub trait Muxer: Send + Sync {
+    /// Read the streams from the pipes until all of them are closed and write the merged file, audio tracks keep their order
+    /// # Errors
+    /// Returns [`io::Error`] if the merge can't be started or waited
+    async fn merge(
+        &self,
+        video_fd: OwnedFd,
+        audio: Vec<AudioPipe>,
+        extension: &str,
+        output_path: &Path,
+        max_file_size: u32,
+    ) -> Result<ExitStatus, io::Error>;
+}
+
+/// Backend that runs `yt-dlp` and `ffmpeg` with the config snapshot of the request.
+/// Launches and range streams wait for the rate limiter.
+pub struct CliBackend {
+    rate_limiter: Arc<RateLimiter>,
+}
+
+impl CliBackend {
+    #[inline]
+    #[must_use]
+    pub const fn new(rate_limiter: Arc<RateLimiter>) -> Self {
+        Self { rate_limiter }
+    }
+
+    fn inspect_download_err(&self, url: &str, err: &io::Error) {
+        if is_rate_limited(err) {
+            self.rate_limiter.throttled(url);
+        }
+    }
+}
+
+impl MediaBackend for CliBackend {
+    async fn download_video(&self, request: DownloadRequest<'_>) -> Result<(), io::Error> {
+        let args = video_args(&request);
+        self.rate_limiter.acquire(request.url).await;
+        download_to_path(&request.yt_dlp_cfg.executable_path, &args, request.timeout, "download_video")
+            .await
+            .inspect_err(|err| self.inspect_download_err(request.url, err))
+    }
+
+    async fn download_audio(&self, request: DownloadRequest<'_>) -> Result<(), io::Error> {
+        let args = audio_args(&request);
+        self.rate_limiter.acquire(request.url).await;
+        download_to_path(&request.yt_dlp_cfg.executable_path, &args, request.timeout, "download_audio")
+            .await
+            .inspect_err(|err| self.inspect_download_err(request.url, err))
+    }
+
+    async fn stream_to_pipe(&self, fd: OwnedFd, request: StreamRequest<'_>) -> Result<(), io::Error> {
+        let Some(filesize) = request.filesize else {
+            let args = pipe_args(&request);
+            self.rate_limiter.acquire(request.url).await;
+            let mut child = download_to_pipe(fd, &request.yt_dlp_cfg.executable_path, &args)?;
+            tokio::spawn(async move {
+                if let Err(err) = child.wait().await {
+                    error!("{}", format_error_report(&err));
+                }
+            });
+            return Ok(());
+        };
+
+        let (sender, mut receiver) = unbounded_channel();
+        let url = request.format_url.to_owned();
+        let rate_limiter = self.rate_limiter.clone();
+        tokio::spawn(
+            async move {
+                tokio::join!(
+                    async move {
+                        let _ = range_download_to_write(url, filesize, sender, &rate_limiter)
+                            .await
+                            .inspect_err(|err| error!("{}", format_error_report(&err)));
+                    },
+                    async move {
+                        let mut writer = tokio::fs::File::from_std(File::from(fd));
+                        while let Some(bytes) = receiver.recv().await {
+                            if let Err(err) = writer.write(&bytes).await {
+                                match err.kind() {
+                                    io::ErrorKind::BrokenPipe => break,
+                                    _ => error!("{}", format_error_report(&err)),
+                                }
+                            }
+                        }
+                    }
+                )
+            }
+            .instrument(debug_span!("range", format_id = request.format_id)),
+        );
+        Ok(())
+    }
+
+    async fn download_thumbnail(&self, url: &str, media_id: &str, output_dir: &Path) -> Option<PathBuf> {
+        download_thumbnail_to_path(url, media_id, output_dir).await
+    }
+}
+
+/// Muxer that runs `ffmpeg`, the process is killed if the merge is dropped
+pub struct FfmpegMuxer;
+
+impl Muxer for FfmpegMuxer {
+    async fn merge(
+        &self,
+        video_fd: OwnedFd,
+        audio: Vec<AudioPipe>,
+        extension: &str,
+        output_path: &Path,
+        max_file_size: u32,
+    ) -> Result<ExitStatus, io::Error> {
+        let timer = ProcessTimer::start("ffmpeg", "merge");
+        let inputs = audio
+            .iter()
+            .map(|track| AudioInput {
+                fd: &track.fd,
+                language: track.language.as_deref(),
+                default: track.default,
+            })
+            .collect::<Vec<_>>();
+        let mut child = merge_streams(&video_fd, &inputs, extension, output_path, max_file_size)?;
+        // The child has its own copies of the read ends
+        drop(inputs);
+        drop((video_fd, audio));
+
+        let exit_code = child.wait().await?;
+        timer.record_exit(exit_code);
+        Ok(exit_code)
+    }
+}
+
+#[instrument(skip_all)]
+async fn range_download_to_write(
+    url: impl AsRef<str>,
+    filesize: f64,
+    sender: UnboundedSender<Bytes>,
+    rate_limiter: &RateLimiter,
+) -> Result<(), RangeErrorKind> {
+    let client = Client::new();
+    let url = url.as_ref();
+    let _timer = metrics::RANGE_FETCH_DURATION.start_timer();
+    // The chunks are parts of the same stream, so it takes a single token
+    rate_limiter.acquire(url).await;
+
+    let mut start = 0;
+    let mut end = RANGE_CHUNK_SIZE;
+
+    loop {
+        trace!(start, end, "Download chunk");
+
+        #[allow(clippy::cast_possible_truncation)]
+        if end >= filesize as i32 {
+            let mut stream = range_request(&client, url, format!("bytes={start}-"), rate_limiter)
+                .await?
+                .bytes_stream();
+
+            while let Some(chunk_res) = stream.next().await {
+                let chunk = chunk_res?;
+                metrics::RANGE_FETCH_BYTES.inc_by(chunk.len() as u64);
+                sender.send(chunk)?;
+            }
+
+            break;
+        }
+
+        let mut stream = range_request(&client, url, format!("bytes={start}-{end}"), rate_limiter)
+            .await?
+            .bytes_stream();
+
+        while let Some(chunk_res) = stream.next().await {
+            let chunk = chunk_res?;
+            metrics::RANGE_FETCH_BYTES.inc_by(chunk.len() as u64);
+            sender.send(chunk)?;
+        }
+
+        start = end + 1;
+        end += RANGE_CHUNK_SIZE;
+    }
+    Ok(())
+}
+
+async fn range_request(client: &Client, url: &str, range: String, rate_limiter: &RateLimiter) -> Result<reqwest::Response, reqwest::Error> {
+    let response = client.get(url).header("Range", range).send().await?;
+    if matches!(response.status(), StatusCode::TOO_MANY_REQUESTS | StatusCode::FORBIDDEN) {
+        rate_limiter.throttled(url);
+    }
+    response.error_for_status()
+}
@@ -0,0 +1,258 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io,
+    os::{fd::OwnedFd, unix::process::ExitStatusExt as _},
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    sync::Arc,
+    time::Duration,
+};
+use tempfile::TempDir;
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    time::sleep,
+};
+
+use super::{AudioPipe, DownloadRequest, MediaBackend, Muxer, StreamRequest};
+use crate::{
+    adapters::scratch::Scratch,
+    config::{self, Reloadable},
+    entities::{Video, format},
+};
+
+#[derive(Debug, Clone)]
+pub enum Step {
+    Write(&'static [u8]),
+    Delay(Duration),
+    /// Downloads return the error, streams close the pipe early
+    Fail(io::ErrorKind),
+}
+
+/// Hook called with the format id and the config snapshot of every download and stream request
+type OnRequest = Box<dyn Fn(&str, &config::YtDlp, &str) + Send + Sync>;
+
+/// Backend that plays the scripted steps instead of downloading.
+/// Scripts are keyed by the format id, thumbnails by the URL.
+#[derive(Default)]
+pub struct FakeBackend {
+    scripts: HashMap<String, Vec<Step>>,
+    on_request: Option<OnRequest>,
+}
+
+impl FakeBackend {
+    #[must_use]
+    pub fn script(mut self, key: impl Into<String>, steps: impl Into<Vec<Step>>) -> Self {
+        self.scripts.insert(key.into(), steps.into());
+        self
+    }
+
+    #[must_use]
+    pub fn on_request(mut self, hook: impl Fn(&str, &config::YtDlp, &str) + Send + Sync + 'static) -> Self {
+        self.on_request = Some(Box::new(hook));
+        self
+    }
+
+    fn call_hook(&self, format_id: &str, yt_dlp_cfg: &config::YtDlp, pot_provider_api_url: &str) {
+        if let Some(hook) = &self.on_request {
+            hook(format_id, yt_dlp_cfg, pot_provider_api_url);
+        }
+    }
+
+    fn steps(&self, key: &str) -> Result<Vec<Step>, io::Error> {
+        self.scripts
+            .get(key)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No script for `{key}`")))
+    }
+
+    async fn play(steps: Vec<Step>, output_path: &Path) -> Result<(), io::Error> {
+        let mut file = tokio::fs::File::create(output_path).await?;
+        for step in steps {
+            match step {
+                Step::Write(bytes) => file.write_all(bytes).await?,
+                Step::Delay(duration) => sleep(duration).await,
+                Step::Fail(kind) => return Err(kind.into()),
+            }
+        }
+        file.flush().await
+    }
+}
+
+impl MediaBackend for FakeBackend {
+    async fn download_video(&self, request: DownloadRequest<'_>) -> Result<(), io::Error> {
+        self.call_hook(request.format_id, request.yt_dlp_cfg, request.pot_provider_api_url);
+        let output_path = request.output_dir.join(format!("{}.{}", request.media_id, request.extension));
+        Self::play(self.steps(request.format_id)?, &output_path).await
+    }
+
+    async fn download_audio(&self, request: DownloadRequest<'_>) -> Result<(), io::Error> {
+        self.download_video(request).await
+    }
+
+    async fn stream_to_pipe(&self, fd: OwnedFd, request: StreamRequest<'_>) -> Result<(), io::Error> {
+        self.call_hook(request.format_id, request.yt_dlp_cfg, request.pot_provider_api_url);
+        let steps = self.steps(request.format_id)?;
+        tokio::spawn(async move {
+            let mut writer = tokio::fs::File::from_std(File::from(fd));
+            for step in steps {
+                match step {
+                    Step::Write(bytes) => {
+                        if writer.write_all(bytes).await.is_err() {
+                            return;
+                        }
+                    }
+                    Step::Delay(duration) => sleep(duration).await,
+                    Step::Fail(_) => return,
+                }
+            }
+        });
+        Ok(())
+    }
+
+    async fn download_thumbnail(&self, url: &str, media_id: &str, output_dir: &Path) -> Option<PathBuf> {
+        let output_path = output_dir.join(format!("{media_id}.jpg"));
+        Self::play(self.steps(url).ok()?, &output_path).await.ok()?;
+        Some(output_path)
+    }
+}
+
+/// Muxer that writes the video bytes followed by the bytes of the audio tracks in their order
+#[derive(Debug, Default)]
+pub struct FakeMuxer {
+    exit_code: i32,
+}
+
+impl FakeMuxer {
+    #[must_use]
+    pub const fn exit_code(exit_code: i32) -> Self {
+        Self { exit_code }
+    }
+}
+
+impl Muxer for FakeMuxer {
+    async fn merge(
+        &self,
+        video_fd: OwnedFd,
+        audio: Vec<AudioPipe>,
+        _extension: &str,
+        output_path: &Path,
+        _max_file_size: u32,
+    ) -> Result<ExitStatus, io::Error> {
+        let read = |fd: OwnedFd| async move {
+            let mut content = Vec::new();
+            tokio::fs::File::from_std(File::from(fd)).read_to_end(&mut content).await?;
+            Ok::<_, io::Error>(content)
+        };
+        // Pipes are read concurrently, so the writers aren't blocked on the full buffers
+        let (video, audio) = tokio::join!(
+            read(video_fd),
+            futures_util::future::join_all(audio.into_iter().map(|track| read(track.fd)))
+        );
+        let mut content = video?;
+        for track in audio {
+            content.extend(track?);
+        }
+
+        if self.exit_code == 0 {
+            tokio::fs::write(output_path, content).await?;
+        }
+        // Raw wait status keeps the exit code in the second byte
+        Ok(ExitStatus::from_raw(self.exit_code << 8))
+    }
+}
+
+/// Scratch dir and the inputs shared by the download tests
+pub struct Fixture {
+    root: TempDir,
+}
+
+impl Default for Fixture {
+    fn default() -> Self {
+        Self {
+            root: TempDir::new().unwrap(),
+        }
+    }
+}
+
+impl Fixture {
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        self.root.path()
+    }
+
+    /// Scratch on the disk only, without the tmpfs and the reserve
+    #[must_use]
+    pub fn scratch(&self) -> Arc<Scratch> {
+        Arc::new(Scratch::new(Some(self.root()), None, 0, 0).unwrap())
+    }
+
+    #[must_use]
+    pub fn limits() -> Arc<Reloadable<config::Limits>> {
+        Arc::new(Reloadable::new(config::Limits {
+            max_file_size: 1024,
+            max_split_file_size: None,
+        }))
+    }
+
+    #[must_use]
+    pub fn sponsorblock() -> Arc<Reloadable<config::SponsorBlock>> {
+        Arc::new(Reloadable::new(config::SponsorBlock::default()))
+    }
+
+    #[must_use]
+    pub fn yt_dlp() -> Arc<Reloadable<config::YtDlp>> {
+        Arc::new(Reloadable::new(config::YtDlp {
+            executable_path: "yt-dlp".into(),
+            js_runtime: "deno:deno".into(),
+            extractors: HashMap::new(),
+        }))
+    }
+
+    #[must_use]
+    pub fn yt_pot_provider() -> Arc<Reloadable<config::YtPotProvider>> {
+        Arc::new(Reloadable::new(config::YtPotProvider {
+            url: "http://pot:4416".into(),
+        }))
+    }
+
+    /// Output files of the downloads are named `id.<extension>`
+    #[must_use]
+    pub fn video() -> Video {
+        Video {
+            id: "id".to_owned(),
+            url: "https://example.com/id".to_owned(),
+            width: None,
+            height: None,
+            duration: None,
+            title: None,
+            live_status: None,
+            release_timestamp: None,
+        }
+    }
+
+    /// `mp4` format, its script is keyed by `id`
+    #[must_use]
+    pub fn video_format(id: &str) -> format::Video {
+        format::Video {
+            id: id.to_owned(),
+            url: format!("https://cdn.example.com/{id}"),
+            filesize: None,
+            filesize_approx: None,
+            container: "mp4".to_owned(),
+            codec: None,
+        }
+    }
+
+    /// `m4a` format, its script is keyed by `id`
+    #[must_use]
+    pub fn audio_format(id: &str) -> format::Audio {
+        format::Audio {
+            id: id.to_owned(),
+            url: format!("https://cdn.example.com/{id}"),
+            filesize: None,
+            filesize_approx: None,
+            codec: "m4a".to_owned(),
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::SystemTime,
+};
+use tempfile::Builder;
+use tracing::{debug, info, instrument, warn};
+
+const TEMP_EXTENSION: &str = "tmp";
+
+#[derive(Debug)]
+struct Entry {
+    path: PathBuf,
+    size: u64,
+    last_access: u64,
+}
+
+#[derive(Debug, Default)]
+struct Index {
+    entries: HashMap<String, Entry>,
+    size: u64,
+    clock: u64,
+}
+
+impl Index {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Remove the least recently used entries until the size fits the budget
+    fn evict(&mut self, max_size: u64) -> Vec<PathBuf> {
+        let mut evicted = vec![];
+        while self.size > max_size {
+            let Some(key) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_access)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            let entry = self.entries.remove(&key).unwrap();
+            self.size -= entry.size;
+            evicted.push(entry.path);
+        }
+        evicted
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    pub hits: u64,
+    pub misses: u64,
+    pub size: u64,
+    pub entries: usize,
+}
+
+/// Content-addressed cache of the processed media with the LRU eviction by the byte budget.
+/// Files are named by the key, so the index is rebuilt from the directory on startup.
+/// Clones share the same index.
+#[derive(Debug, Clone)]
+pub struct MediaCache {
+    dir: PathBuf,
+    max_size: u64,
+    index: Arc<Mutex<Index>>,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+impl MediaCache {
+    /// Open the cache directory, removing the unfinished writes and the entries over the budget.
+    /// Access order is restored by the modification time, which is updated on hits.
+    /// # Errors
+    /// Returns [`io::Error`] if the directory can't be created or read
+    #[instrument(skip_all, fields(dir = %dir.as_ref().display(), max_size))]
+    pub fn open(dir: impl AsRef<Path>, max_size: u64) -> Result<Self, io::Error> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let mut files = vec![];
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == TEMP_EXTENSION) {
+                debug!(path = %path.display(), "Remove unfinished write");
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+            let Some(key) = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()) else {
+                continue;
+            };
+            let metadata = fs::metadata(&path)?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((modified, key, path, metadata.len()));
+        }
+        files.sort_by_key(|(modified, ..)| *modified);
+
+        let mut index = Index::default();
+        for (_, key, path, size) in files {
+            let last_access = index.tick();
+            index.size += size;
+            index.entries.insert(key, Entry { path, size, last_access });
+        }
+        for path in index.evict(max_size) {
+            let _ = fs::remove_file(path);
+        }
+        info!(entries = index.entries.len(), size = index.size, "Cache opened");
+
+        Ok(Self {
+            dir,
+            max_size,
+            index: Arc::new(Mutex::new(index)),
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    /// Copy the cached file to the directory as `{stem}.{extension}`.
+    /// The copy doesn't share the inode with the entry, so the job may change it in place.
+    /// # Errors
+    /// Returns [`io::Error`] if the cached file can't be copied
+    /// # Returns
+    /// Returns the path of the file, or `None` on a miss
+    #[instrument(skip(self, target_dir))]
+    pub async fn get(&self, key: &str, target_dir: &Path, stem: &str) -> Result<Option<PathBuf>, io::Error> {
+        let path = {
+            let mut index = self.index.lock().unwrap_or_else(PoisonError::into_inner);
+            let last_access = index.tick();
+            index.entries.get_mut(key).map(|entry| {
+                entry.last_access = last_access;
+                entry.path.clone()
+            })
+        };
+        let Some(path) = path else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            debug!("Cache miss");
+            return Ok(None);
+        };
+
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        let target_path = target_dir.join(format!("{stem}.{extension}"));
+        match tokio::fs::copy(&path, &target_path).await {
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                warn!("Cached file is missing");
+                self.remove(key);
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        }
+        // Keep the access order across restarts
+        let _ = fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        debug!("Cache hit");
+        Ok(Some(target_path))
+    }
+
+    /// Store the copy of the file, evicting the least recently used entries if the budget is exceeded.
+    /// Files larger than the budget aren't stored.
+    /// # Errors
+    /// Returns [`io::Error`] if the file can't be copied
+    #[instrument(skip(self, path))]
+    pub async fn put(&self, key: &str, path: &Path) -> Result<(), io::Error> {
+        let size = tokio::fs::metadata(path).await?.len();
+        if size > self.max_size {
+            debug!(size, "File exceeds the cache budget");
+            return Ok(());
+        }
+
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        let cached_path = self.dir.join(format!("{key}.{extension}"));
+        // Unique per write, so the concurrent puts of the key don't share it, it's removed if the write fails
+        let temp_path = Builder::new()
+            .suffix(&format!(".{TEMP_EXTENSION}"))
+            .tempfile_in(&self.dir)?
+            .into_temp_path();
+        // Readers never see a partial file, the rename is atomic
+        tokio::fs::copy(path, &temp_path).await?;
+        tokio::fs::rename(&temp_path, &cached_path).await?;
+        temp_path.keep().map_err(|err| err.error)?;
+
+        let evicted = {
+            let mut index = self.index.lock().unwrap_or_else(PoisonError::into_inner);
+            let last_access = index.tick();
+            if let Some(old) = index.entries.insert(
+                key.to_owned(),
+                Entry {
+                    path: cached_path.clone(),
+                    size,
+                    last_access,
+                },
+            ) {
+                index.size -= old.size;
+                if old.path != cached_path {
+                    let _ = fs::remove_file(old.path);
+                }
+            }
+            index.size += size;
+            index.evict(self.max_size)
+        };
+        for path in evicted {
+            debug!(path = %path.display(), "Evict entry");
+            let _ = tokio::fs::remove_file(path).await;
+        }
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn stats(&self) -> Stats {
+        let index = self.index.lock().unwrap_or_else(PoisonError::into_inner);
+        Stats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            size: index.size,
+            entries: index.entries.len(),
+        }
+    }
+
+    fn remove(&self, key: &str) {
+        let mut index = self.index.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(entry) = index.entries.remove(key) {
+            index.size -= entry.size;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_evict_least_recently_used() {
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let cache = MediaCache::open(cache_dir.path(), 10).unwrap();
+
+        for key in ["a", "b", "c"] {
+            let path = source_dir.path().join(format!("{key}.mp4"));
+            tokio::fs::write(&path, [0u8; 4]).await.unwrap();
+            cache.put(key, &path).await.unwrap();
+            if key == "b" {
+                assert!(cache.get("a", source_dir.path(), "hit").await.unwrap().is_some());
+            }
+        }
+
+        assert!(cache.get("b", source_dir.path(), "hit").await.unwrap().is_none());
+        assert!(cache.get("a", source_dir.path(), "hit").await.unwrap().is_some());
+        assert!(cache.get("c", source_dir.path(), "hit").await.unwrap().is_some());
+
+        let reopened = MediaCache::open(cache_dir.path(), 10).unwrap();
+        assert_eq!(reopened.stats().entries, 2);
+        assert_eq!(reopened.stats().size, 8);
+    }
+
+    #[tokio::test]
+    async fn test_hit_is_a_copy() {
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let cache = MediaCache::open(cache_dir.path(), 100).unwrap();
+        let path = source_dir.path().join("a.mp4");
+        tokio::fs::write(&path, b"media").await.unwrap();
+        cache.put("a", &path).await.unwrap();
+
+        // Changes of the source and the delivered file don't reach the entry
+        tokio::fs::write(&path, b"changed").await.unwrap();
+        let hit = cache.get("a", source_dir.path(), "hit").await.unwrap().unwrap();
+        tokio::fs::write(&hit, b"changed").await.unwrap();
+
+        let hit = cache.get("a", source_dir.path(), "hit").await.unwrap().unwrap();
+        assert_eq!(tokio::fs::read(&hit).await.unwrap(), b"media");
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_puts() {
+        let cache_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let cache = MediaCache::open(cache_dir.path(), 1_000_000).unwrap();
+        let paths = ["a", "b"].map(|name| source_dir.path().join(format!("{name}.mp4")));
+        for path in &paths {
+            tokio::fs::write(path, vec![1u8; 100_000]).await.unwrap();
+        }
+
+        let (a, b) = tokio::join!(cache.put("key", &paths[0]), cache.put("key", &paths[1]));
+        a.unwrap();
+        b.unwrap();
+
+        let hit = cache.get("key", source_dir.path(), "hit").await.unwrap().unwrap();
+        assert_eq!(tokio::fs::read(&hit).await.unwrap().len(), 100_000);
+        // No temp files are left
+        assert_eq!(std::fs::read_dir(cache_dir.path()).unwrap().count(), 1);
+    }
+}
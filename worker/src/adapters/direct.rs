@@ -0,0 +1,617 @@
+use futures_util::StreamExt as _;
+use reqwest::{
+    Client, Response, StatusCode,
+    dns::{Addrs, Name, Resolve, Resolving},
+    header::{CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, HeaderMap, RANGE},
+    redirect::Policy,
+};
+use std::{io, net::IpAddr, path::Path, sync::Arc};
+use tokio::{
+    io::{AsyncSeekExt as _, AsyncWriteExt as _},
+    net::lookup_host,
+};
+use tracing::{debug, instrument, warn};
+use url::{Host, Url};
+
+/// Enough for the `ftyp` box and the `EBML` header
+const SNIFF_SIZE: usize = 64;
+const MAX_RESUMES: u32 = 3;
+const MAX_REDIRECTS: usize = 10;
+/// Content types of the servers that don't know the media type
+const GENERIC_CONTENT_TYPES: [&str; 3] = ["application/octet-stream", "binary/octet-stream", "application/mp4"];
+
+#[derive(thiserror::Error, Debug)]
+pub enum ErrorKind {
+    #[error("URL `{0}` isn't a public HTTP(S) address")]
+    ForbiddenUrl(String),
+    #[error("Request error: {0}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("File error: {0}")]
+    File(#[from] io::Error),
+    #[error("Content type `{0}` isn't a media")]
+    ContentType(String),
+    #[error("Unknown media format")]
+    UnknownFormat,
+    #[error("File is too large: {filesize} bytes")]
+    TooLarge { filesize: u64 },
+    #[error("Download is incomplete: {written} of {filesize} bytes")]
+    Incomplete { written: u64, filesize: u64 },
+}
+
+/// Format of the file detected by its magic bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Mp4,
+    M4a,
+    Mp3,
+    Webm,
+}
+
+impl Format {
+    #[must_use]
+    pub fn sniff(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [_, _, _, _, b'f', b't', b'y', b'p', brand @ ..] => match brand.get(..3) {
+                Some(b"M4A" | b"M4B" | b"M4P") => Some(Self::M4a),
+                _ => Some(Self::Mp4),
+            },
+            [0x1A, 0x45, 0xDF, 0xA3, header @ ..] => header.windows(4).any(|doc_type| doc_type == b"webm").then_some(Self::Webm),
+            [b'I', b'D', b'3', ..] => Some(Self::Mp3),
+            // Frame sync of MPEG audio layer III
+            [0xFF, second, ..] if second & 0xE0 == 0xE0 && second & 0x06 == 0x02 => Some(Self::Mp3),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Mp4 => "mp4",
+            Self::M4a => "m4a",
+            Self::Mp3 => "mp3",
+            Self::Webm => "webm",
+        }
+    }
+}
+
+/// Remote file checked before the download
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteFile {
+    /// URL after the redirects
+    pub url: String,
+    pub format: Format,
+    pub filesize: Option<u64>,
+    /// Server supports the range requests, so the interrupted download is resumed
+    pub ranges: bool,
+}
+
+/// Client that follows the redirects to the public HTTP(S) addresses only
+/// and fails to connect to the hosts that resolve to the loopback, private or link-local ones.
+/// Proxies from the environment are ignored.
+/// # Panics
+/// Panics if the TLS backend can't be initialized, as [`Client::new`] does
+#[must_use]
+pub fn client() -> Client {
+    Client::builder()
+        .redirect(Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("Too many redirects")
+            } else if let Err(err) = check_url(attempt.url()) {
+                attempt.error(err)
+            } else {
+                attempt.follow()
+            }
+        }))
+        .dns_resolver(Arc::new(PublicResolver))
+        // A proxy would resolve the hosts itself, bypassing the resolver
+        .no_proxy()
+        .build()
+        .expect("Failed to build the client")
+}
+
+/// Check that the URL is HTTP(S) and its host isn't a non-public IP,
+/// the domains are checked by the resolver of [`client`] on connect
+/// # Errors
+/// Returns [`ErrorKind::ForbiddenUrl`] otherwise
+pub fn check_url(url: &Url) -> Result<(), ErrorKind> {
+    let allowed = matches!(url.scheme(), "http" | "https")
+        && match url.host() {
+            Some(Host::Domain(_)) => true,
+            Some(Host::Ipv4(ip)) => is_public(ip.into()),
+            Some(Host::Ipv6(ip)) => is_public(ip.into()),
+            None => false,
+        };
+    if allowed {
+        Ok(())
+    } else {
+        Err(ErrorKind::ForbiddenUrl(url.to_string()))
+    }
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            // `0.0.0.0/8`, the shared address space of the carrier-grade NAT `100.64.0.0/10`,
+            // the benchmarking `198.18.0.0/15` and the reserved `240.0.0.0/4`
+            let reserved = first == 0 || (first == 100 && second & 0xC0 == 64) || (first == 198 && second & 0xFE == 18) || first >= 240;
+            !(reserved
+                || ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast())
+        }
+        IpAddr::V6(ip) => ip.to_ipv4_mapped().map_or_else(
+            || {
+                // NAT64 `64:ff9b::/96` translates to any IPv4, including the private ones
+                let nat64 = ip.segments()[..6] == [0x64, 0xff9b, 0, 0, 0, 0];
+                !(nat64
+                    || ip.is_unspecified()
+                    || ip.is_loopback()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+                    || ip.is_multicast())
+            },
+            |ip| is_public(IpAddr::V4(ip)),
+        ),
+    }
+}
+
+/// Resolver that rejects the host if any of its addresses isn't public
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs = lookup_host((name.as_str(), 0)).await?.collect::<Vec<_>>();
+            if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+                return Err(format!("Host `{}` resolves to the non-public address {}", name.as_str(), addr.ip()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Check the content type and the size of the file, then sniff its first bytes.
+/// `HEAD` is optional, some servers reject it, so its headers only complement the ranged `GET`.
+/// # Errors
+/// Returns [`ErrorKind::TooLarge`] if the declared size exceeds the limit,
+/// [`ErrorKind::ContentType`] or [`ErrorKind::UnknownFormat`] if the file isn't a supported media
+#[instrument(skip_all, fields(%url))]
+pub async fn inspect(client: &Client, url: &str, max_file_size: u64) -> Result<RemoteFile, ErrorKind> {
+    let head = client.head(url).send().await.ok().filter(|response| response.status().is_success());
+    let response = client
+        .get(url)
+        .header(RANGE, format!("bytes=0-{}", SNIFF_SIZE - 1))
+        .send()
+        .await?
+        .error_for_status()?;
+    let ranges = response.status() == StatusCode::PARTIAL_CONTENT;
+
+    let headers = head.as_ref().map_or_else(|| response.headers(), Response::headers);
+    if let Some(content_type) = content_type(headers).or_else(|| content_type(response.headers()))
+        && !is_media_type(&content_type)
+    {
+        return Err(ErrorKind::ContentType(content_type));
+    }
+    let filesize = head.as_ref().and_then(|head| content_length(head.headers())).or_else(|| {
+        if ranges {
+            total_size(response.headers())
+        } else {
+            content_length(response.headers())
+        }
+    });
+    if let Some(filesize) = filesize
+        && filesize > max_file_size
+    {
+        return Err(ErrorKind::TooLarge { filesize });
+    }
+
+    let final_url = response.url().to_string();
+    let mut bytes = Vec::with_capacity(SNIFF_SIZE);
+    let mut stream = response.bytes_stream();
+    while bytes.len() < SNIFF_SIZE
+        && let Some(chunk) = stream.next().await
+    {
+        bytes.extend_from_slice(&chunk?);
+    }
+    let format = Format::sniff(&bytes).ok_or(ErrorKind::UnknownFormat)?;
+    debug!(?format, filesize, ranges, "Remote file inspected");
+
+    Ok(RemoteFile {
+        url: final_url,
+        format,
+        filesize,
+        ranges,
+    })
+}
+
+/// Stream the file to the path, the interrupted download is continued from the written size if the server supports ranges
+/// # Errors
+/// Returns [`ErrorKind::TooLarge`] if the file exceeds the limit while downloading,
+/// [`ErrorKind::Incomplete`] if the file is shorter than declared and can't be resumed
+#[instrument(skip_all, fields(url = remote.url))]
+pub async fn download(client: &Client, remote: &RemoteFile, output_path: &Path, max_file_size: u64) -> Result<u64, ErrorKind> {
+    let mut file = tokio::fs::File::create(output_path).await?;
+    let mut written = 0;
+    let mut resumes = 0;
+
+    loop {
+        let res = fetch(client, remote, &mut file, &mut written, max_file_size).await;
+        let interrupted = match res {
+            Ok(()) => remote.filesize.is_some_and(|filesize| written < filesize),
+            Err(ErrorKind::Reqwest(ref err)) => err.is_body() || err.is_decode() || err.is_timeout() || err.is_connect(),
+            Err(_) => false,
+        };
+        if !interrupted {
+            res?;
+            break;
+        }
+        if !remote.ranges || resumes == MAX_RESUMES {
+            return match res {
+                Err(err) => Err(err),
+                Ok(()) => Err(ErrorKind::Incomplete {
+                    written,
+                    filesize: remote.filesize.unwrap_or_default(),
+                }),
+            };
+        }
+        resumes += 1;
+        warn!(written, resumes, "Download interrupted, resume");
+    }
+    file.flush().await?;
+    Ok(written)
+}
+
+async fn fetch(
+    client: &Client,
+    remote: &RemoteFile,
+    file: &mut tokio::fs::File,
+    written: &mut u64,
+    max_file_size: u64,
+) -> Result<(), ErrorKind> {
+    let mut request = client.get(&remote.url);
+    if *written > 0 {
+        request = request.header(RANGE, format!("bytes={written}-"));
+    }
+    let response = request.send().await?.error_for_status()?;
+    if *written > 0 && response.status() != StatusCode::PARTIAL_CONTENT {
+        // Range is ignored, so the content starts over
+        *written = 0;
+        file.set_len(0).await?;
+        file.rewind().await?;
+    }
+
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        *written += chunk.len() as u64;
+        if *written > max_file_size {
+            return Err(ErrorKind::TooLarge { filesize: *written });
+        }
+        file.write_all(&chunk).await?;
+    }
+    Ok(())
+}
+
+fn content_type(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+    let mime = value.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    (!mime.is_empty()).then_some(mime)
+}
+
+fn is_media_type(content_type: &str) -> bool {
+    content_type.starts_with("video/") || content_type.starts_with("audio/") || GENERIC_CONTENT_TYPES.contains(&content_type)
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers.get(CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+}
+
+/// Size from `Content-Range: bytes 0-63/<size>` of the partial response
+fn total_size(headers: &HeaderMap) -> Option<u64> {
+    let value = headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    value.rsplit_once('/')?.1.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    };
+    use tempfile::TempDir;
+    use tokio::{
+        io::AsyncReadExt as _,
+        net::{TcpListener, TcpStream},
+    };
+
+    const MP4: &[u8] = b"\0\0\0\x20ftypisom\0\0\x02\0isomiso2avc1mp41";
+
+    #[derive(Clone)]
+    struct File {
+        content: Vec<u8>,
+        content_type: &'static str,
+        ranges: bool,
+        /// The first full response is cut after the number of bytes
+        cut_at: Option<usize>,
+    }
+
+    /// HTTP server of a single file, a connection per request
+    async fn serve(file: File) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/media/file", listener.local_addr().unwrap());
+        let cut = Arc::new(AtomicBool::new(file.cut_at.is_some()));
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(respond(stream, file.clone(), cut.clone()));
+            }
+        });
+        url
+    }
+
+    async fn respond(mut stream: TcpStream, file: File, cut: Arc<AtomicBool>) {
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            let n = stream.read(&mut buf).await.unwrap();
+            if n == 0 {
+                return;
+            }
+            request.extend_from_slice(&buf[..n]);
+        }
+        let request = String::from_utf8(request).unwrap();
+        let range = request
+            .lines()
+            .find_map(|line| line.to_ascii_lowercase().strip_prefix("range: bytes=").map(ToOwned::to_owned))
+            .filter(|_| file.ranges);
+
+        let len = file.content.len();
+        let (status, start, end) = match range.as_deref().and_then(|range| range.split_once('-')) {
+            Some((start, end)) => {
+                let start = start.parse::<usize>().unwrap();
+                let end = end.parse::<usize>().map_or(len - 1, |end| end.min(len - 1));
+                ("206 Partial Content", start, end)
+            }
+            None => ("200 OK", 0, len - 1),
+        };
+        let mut head = format!(
+            "HTTP/1.1 {status}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            file.content_type,
+            end + 1 - start
+        );
+        if file.ranges {
+            head.push_str("Accept-Ranges: bytes\r\n");
+        }
+        if range.is_some() {
+            head.push_str(&format!("Content-Range: bytes {start}-{end}/{len}\r\n"));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes()).await.unwrap();
+        if request.starts_with("HEAD") {
+            return;
+        }
+
+        let mut body = &file.content[start..=end];
+        if range.is_none()
+            && let Some(cut_at) = file.cut_at
+            && cut.swap(false, Ordering::SeqCst)
+        {
+            body = &body[..cut_at];
+        }
+        let _ = stream.write_all(body).await;
+    }
+
+    fn mp4(len: usize) -> Vec<u8> {
+        let mut content = MP4.to_vec();
+        content.extend((0..len - MP4.len()).map(|i| (i % 251) as u8));
+        content
+    }
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(Format::sniff(MP4), Some(Format::Mp4));
+        assert_eq!(Format::sniff(b"\0\0\0\x1cftypM4A \0\0\0\0"), Some(Format::M4a));
+        assert_eq!(Format::sniff(b"ID3\x04\0\0\0\0\0\0"), Some(Format::Mp3));
+        assert_eq!(Format::sniff(&[0xFF, 0xFB, 0x90, 0x64]), Some(Format::Mp3));
+        assert_eq!(
+            Format::sniff(&[0x1A, 0x45, 0xDF, 0xA3, 0x9F, 0x42, 0x82, 0x84, b'w', b'e', b'b', b'm']),
+            Some(Format::Webm)
+        );
+        // ADTS AAC has the same sync word, but no layer
+        assert_eq!(Format::sniff(&[0xFF, 0xF1, 0x50, 0x80]), None);
+        assert_eq!(Format::sniff(b"<!DOCTYPE html>"), None);
+    }
+
+    #[tokio::test]
+    async fn test_inspect() {
+        let url = serve(File {
+            content: mp4(1000),
+            content_type: "video/mp4",
+            ranges: true,
+            cut_at: None,
+        })
+        .await;
+
+        let remote = inspect(&Client::new(), &url, 1000).await.unwrap();
+
+        assert_eq!(
+            remote,
+            RemoteFile {
+                url,
+                format: Format::Mp4,
+                filesize: Some(1000),
+                ranges: true,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inspect_rejects() {
+        let client = Client::new();
+        let html = serve(File {
+            content: b"<!DOCTYPE html><html></html>".to_vec(),
+            content_type: "text/html; charset=utf-8",
+            ranges: false,
+            cut_at: None,
+        })
+        .await;
+        let unknown = serve(File {
+            content: vec![0; 100],
+            content_type: "application/octet-stream",
+            ranges: false,
+            cut_at: None,
+        })
+        .await;
+        let large = serve(File {
+            content: mp4(1000),
+            content_type: "video/mp4",
+            ranges: false,
+            cut_at: None,
+        })
+        .await;
+
+        assert!(matches!(inspect(&client, &html, 1000).await, Err(ErrorKind::ContentType(content_type)) if content_type == "text/html"));
+        assert!(matches!(inspect(&client, &unknown, 1000).await, Err(ErrorKind::UnknownFormat)));
+        assert!(matches!(
+            inspect(&client, &large, 999).await,
+            Err(ErrorKind::TooLarge { filesize: 1000 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_download_resumes() {
+        let root = TempDir::new().unwrap();
+        let content = mp4(100_000);
+        let url = serve(File {
+            content: content.clone(),
+            content_type: "application/octet-stream",
+            ranges: true,
+            cut_at: Some(30_000),
+        })
+        .await;
+        let client = Client::new();
+        let path = root.path().join("file.mp4");
+
+        let remote = inspect(&client, &url, 100_000).await.unwrap();
+        let written = download(&client, &remote, &path, 100_000).await.unwrap();
+
+        assert_eq!(written, 100_000);
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn test_download_incomplete_without_ranges() {
+        let root = TempDir::new().unwrap();
+        let url = serve(File {
+            content: mp4(100_000),
+            content_type: "video/mp4",
+            ranges: false,
+            cut_at: Some(30_000),
+        })
+        .await;
+        let client = Client::new();
+
+        let remote = RemoteFile {
+            url,
+            format: Format::Mp4,
+            filesize: Some(100_000),
+            ranges: false,
+        };
+        let res = download(&client, &remote, &root.path().join("file.mp4"), 100_000).await;
+
+        // Body shorter than its `Content-Length` fails the request, so it isn't reported as incomplete
+        assert!(matches!(res, Err(ErrorKind::Reqwest(err)) if err.is_decode()));
+    }
+
+    #[tokio::test]
+    async fn test_download_incomplete() {
+        let root = TempDir::new().unwrap();
+        let url = serve(File {
+            content: mp4(30_000),
+            content_type: "video/mp4",
+            ranges: true,
+            cut_at: None,
+        })
+        .await;
+        let client = Client::new();
+
+        let remote = RemoteFile {
+            url,
+            format: Format::Mp4,
+            filesize: Some(100_000),
+            ranges: false,
+        };
+        let res = download(&client, &remote, &root.path().join("file.mp4"), 100_000).await;
+
+        assert!(matches!(
+            res,
+            Err(ErrorKind::Incomplete {
+                written: 30_000,
+                filesize: 100_000
+            })
+        ));
+    }
+
+    #[test]
+    fn test_check_url() {
+        let check = |url: &str| check_url(&Url::parse(url).unwrap()).is_ok();
+
+        assert!(check("https://example.com/file.mp4"));
+        assert!(check("http://93.184.215.14/file.mp4"));
+        assert!(check("http://[2606:2800:21f:cb07:6820:80da:af6b:8b2c]/file.mp4"));
+        assert!(!check("ftp://example.com/file.mp4"));
+        assert!(!check("file:///etc/passwd"));
+        assert!(!check("http://127.0.0.1:8080/file.mp4"));
+        assert!(!check("http://169.254.169.254/latest/meta-data"));
+        assert!(!check("http://10.0.0.1/file.mp4"));
+        assert!(!check("http://192.168.1.1/file.mp4"));
+        assert!(!check("http://100.64.0.1/file.mp4"));
+        assert!(!check("http://0.0.0.0/file.mp4"));
+        assert!(!check("http://198.18.0.1/file.mp4"));
+        assert!(!check("http://198.19.255.254/file.mp4"));
+        assert!(check("http://198.20.0.1/file.mp4"));
+        assert!(!check("http://240.0.0.1/file.mp4"));
+        assert!(!check("http://[64:ff9b::7f00:1]/file.mp4"));
+        assert!(!check("http://[::1]/file.mp4"));
+        assert!(!check("http://[fd00::1]/file.mp4"));
+        assert!(!check("http://[::ffff:127.0.0.1]/file.mp4"));
+    }
+
+    /// Server that redirects every request to the location
+    async fn redirect(location: String) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0; 1024];
+                let _ = stream.read(&mut buf).await;
+                let head = format!("HTTP/1.1 302 Found\r\nLocation: {location}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+                let _ = stream.write_all(head.as_bytes()).await;
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_client_rejects_private_targets() {
+        let file = serve(File {
+            content: mp4(1000),
+            content_type: "video/mp4",
+            ranges: true,
+            cut_at: None,
+        })
+        .await;
+        let port = Url::parse(&file).unwrap().port().unwrap();
+        let client = client();
+
+        let res = inspect(&client, &redirect(file).await, 1000).await;
+        assert!(matches!(res, Err(ErrorKind::Reqwest(err)) if err.is_redirect()));
+
+        let res = inspect(&client, &format!("http://localhost:{port}/media/file"), 1000).await;
+        assert!(matches!(res, Err(ErrorKind::Reqwest(err)) if err.is_connect()));
+    }
+}
@@ -6,61 +6,747 @@ use std::{
     time::Duration,
 };
 use tokio::{
+    io::AsyncReadExt as _,
     process::{Child, Command},
     time::timeout,
 };
 use tracing::{Level, event, instrument};
 
-use crate::utils::format_error_report;
+use crate::{
+    adapters::metrics::ProcessTimer,
+    entities::MediaPart,
+    utils::format_error_report,
+    value_objects::{AnimationFormat, Container},
+};
+
+/// Audio track of the merged file
+#[derive(Debug)]
+pub struct AudioInput<'a> {
+    pub fd: &'a OwnedFd,
+    pub language: Option<&'a str>,
+    pub default: bool,
+}
 
-/// Merge the video and audio streams into a single file.
+/// Merge the video stream and the audio tracks in their order into a single file.
+/// Tracks are tagged with the languages and the default one is marked, if there are several of them or a language is set.
 /// # Errors
 /// Returns [`io::Error`] if the spawn child process fails.
 /// # Returns
 /// Returns the child process
-#[instrument(skip_all, fields(video_fd = video_fd.as_raw_fd(), audio_fd = audio_fd.as_raw_fd(), path = %output_path.as_ref().as_os_str().to_string_lossy()))]
+#[instrument(skip_all, fields(video_fd = video_fd.as_raw_fd(), audio_tracks = audio.len(), path = %output_path.as_ref().as_os_str().to_string_lossy()))]
 pub fn merge_streams(
     video_fd: &OwnedFd,
-    audio_fd: &OwnedFd,
+    audio: &[AudioInput<'_>],
     extension: impl AsRef<str>,
     output_path: impl AsRef<Path>,
     max_file_size: u32,
 ) -> Result<Child, io::Error> {
     let max_file_size_str = max_file_size.to_string();
+    let extension = extension.as_ref();
+
+    let mut command = Command::new("ffmpeg");
+    command.args(["-y", "-hide_banner", "-loglevel", "error", "-i", &format!("pipe:{}", video_fd.as_raw_fd())]);
+    for track in audio {
+        command.args(["-i", &format!("pipe:{}", track.fd.as_raw_fd())]);
+    }
+    command.args(["-map", "0:v"]);
+    for index in 1..=audio.len() {
+        command.args(["-map", &format!("{index}:a")]);
+    }
+    command.args(audio_track_args(
+        &audio.iter().map(|track| (track.language, track.default)).collect::<Vec<_>>(),
+    ));
+    command.args([
+        "-c:v",
+        "copy",
+        "-c:a",
+        "copy",
+        "-shortest",
+        "-nostats",
+        "-preset",
+        "ultrafast",
+        "-fs",
+        max_file_size_str.as_ref(),
+    ]);
+    let container = Container::from_extension(extension);
+    if container.supports_faststart() {
+        command.args(["-movflags", "+faststart"]);
+    }
+
+    command
+        .args(["-f", container.muxer(), output_path.as_ref().to_string_lossy().as_ref()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true)
+        .spawn()
+}
+
+/// Language metadata and dispositions of the `(language, default)` audio tracks.
+/// The first track is the default one if none is marked, a single track without the language is left as is.
+fn audio_track_args(tracks: &[(Option<&str>, bool)]) -> Vec<String> {
+    if let [(None, _)] = tracks {
+        return vec![];
+    }
+    let has_default = tracks.iter().any(|(_, default)| *default);
+
+    let mut args = vec![];
+    for (index, (language, default)) in tracks.iter().enumerate() {
+        if let Some(language) = language.and_then(language_tag) {
+            args.extend([format!("-metadata:s:a:{index}"), format!("language={language}")]);
+        }
+        let default = *default || (!has_default && index == 0);
+        args.extend([
+            format!("-disposition:a:{index}"),
+            if default { "default" } else { "0" }.to_owned(),
+        ]);
+    }
+    args
+}
 
+/// Primary language subtag of the tag, e.g. `en` of `en-US`, that the muxers accept
+fn language_tag(tag: &str) -> Option<String> {
+    let language = tag.split(['-', '_']).next()?;
+    (matches!(language.len(), 2 | 3) && language.chars().all(|char| char.is_ascii_alphabetic())).then(|| language.to_ascii_lowercase())
+}
+
+/// Remux the recorded live stream from the pipe to `mp4` with `faststart`.
+/// Recording stops after `duration` seconds or when the file reaches `max_file_size`,
+/// then the file is finalized, so it's playable.
+/// # Errors
+/// Returns [`io::Error`] if the spawn child process fails.
+/// # Returns
+/// Returns the child process
+#[instrument(skip_all, fields(fd = fd.as_raw_fd(), path = %output_path.as_ref().as_os_str().to_string_lossy(), duration))]
+pub fn record_live(fd: &OwnedFd, output_path: impl AsRef<Path>, duration: u32, max_file_size: u32) -> Result<Child, io::Error> {
     Command::new("ffmpeg")
+        .args(record_live_args(fd.as_raw_fd(), duration, max_file_size))
+        .arg(output_path.as_ref())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+}
+
+fn record_live_args(fd: i32, duration: u32, max_file_size: u32) -> Vec<String> {
+    [
+        "-y",
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-nostats",
+        "-i",
+        &format!("pipe:{fd}"),
+        "-map",
+        "0:v:0?",
+        "-map",
+        "0:a:0?",
+        "-c",
+        "copy",
+        "-t",
+        &duration.to_string(),
+        "-fs",
+        &max_file_size.to_string(),
+        "-movflags",
+        "+faststart",
+        "-f",
+        "mp4",
+    ]
+    .map(ToOwned::to_owned)
+    .to_vec()
+}
+
+/// Convert the file to `mp4` with `faststart`, transcoding only the streams that are passed with the codec.
+/// Streams without the codec are copied.
+/// # Errors
+/// Returns [`io::Error`] if the spawn child process fails, times out or exits with a non-zero code.
+#[instrument(skip_all, fields(input = %input_path.as_ref().as_os_str().to_string_lossy(), ?video_codec, ?audio_codec))]
+pub async fn convert_to_streamable(
+    input_path: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    video_codec: Option<&str>,
+    audio_codec: Option<&str>,
+    max_file_size: u32,
+    timeout_secs: u64,
+) -> Result<(), io::Error> {
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-y", "-hide_banner", "-loglevel", "error", "-nostats", "-i"])
+        .arg(input_path.as_ref())
+        .args(["-map", "0:v:0?", "-map", "0:a?", "-map_metadata", "0"]);
+
+    match video_codec {
+        Some(codec) => command.args(["-c:v", codec, "-preset", "veryfast", "-crf", "23", "-pix_fmt", "yuv420p"]),
+        None => command.args(["-c:v", "copy"]),
+    };
+    match audio_codec {
+        Some(codec) => command.args(["-c:a", codec, "-b:a", "192k"]),
+        None => command.args(["-c:a", "copy"]),
+    };
+
+    let child = command
+        .args(["-fs", &max_file_size.to_string(), "-movflags", "+faststart", "-f", "mp4"])
+        .arg(output_path.as_ref())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    wait_with_timeout(child, timeout_secs, "streamable").await
+}
+
+/// Split the file into consecutive parts on keyframes using the segment muxer.
+/// Parts are written next to the input file and named `{stem}_{index}.{extension}`.
+/// # Errors
+/// Returns [`io::Error`] if the spawn child process fails, times out or exits with a non-zero code.
+/// # Returns
+/// Returns the parts with their time ranges in order
+#[instrument(skip_all, fields(path = %input_path.as_ref().as_os_str().to_string_lossy(), segment_time))]
+pub async fn split_into_segments(
+    input_path: impl AsRef<Path>,
+    extension: impl AsRef<str>,
+    segment_time: f64,
+    timeout_secs: u64,
+) -> Result<Vec<MediaPart>, io::Error> {
+    let input_path = input_path.as_ref();
+    let extension = extension.as_ref();
+    let output_dir = input_path.parent().unwrap_or_else(|| Path::new("."));
+    let stem = input_path.file_stem().map_or_else(|| "part".into(), |stem| stem.to_string_lossy());
+    let list_path = output_dir.join(format!("{stem}_segments.csv"));
+    let output_pattern = output_dir.join(format!("{stem}_%03d.{extension}"));
+
+    let child = Command::new("ffmpeg")
+        .args(["-y", "-hide_banner", "-loglevel", "error", "-nostats", "-i"])
+        .arg(input_path)
         .args([
-            "-y",
-            "-hide_banner",
-            "-loglevel",
-            "error",
-            "-i",
-            &format!("pipe:{}", video_fd.as_raw_fd()),
-            "-i",
-            &format!("pipe:{}", audio_fd.as_raw_fd()),
             "-map",
-            "0:v",
+            "0",
+            "-c",
+            "copy",
+            "-map_metadata",
+            "0",
+            "-f",
+            "segment",
+            "-segment_time",
+            &format!("{segment_time:.3}"),
+            "-reset_timestamps",
+            "1",
+            "-segment_list_type",
+            "csv",
+            "-segment_list",
+        ])
+        .arg(&list_path)
+        .arg(&output_pattern)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    wait_with_timeout(child, timeout_secs, "split").await?;
+
+    let list = tokio::fs::read_to_string(&list_path).await?;
+    let _ = tokio::fs::remove_file(&list_path).await;
+    Ok(parse_segment_list(&list, output_dir))
+}
+
+/// Parse the `csv` segment list, where each line is `filename,start,end`
+fn parse_segment_list(list: &str, dir: &Path) -> Vec<MediaPart> {
+    list.lines()
+        .filter_map(|line| {
+            let mut fields = line.rsplitn(3, ',');
+            let end = fields.next()?.trim().parse().ok()?;
+            let start = fields.next()?.trim().parse().ok()?;
+            let filename = fields.next()?.trim().trim_matches('"');
+            Some(MediaPart::new(dir.join(filename), start, end))
+        })
+        .collect()
+}
+
+/// Convert the video to a silent animation, cutting it to `max_duration` seconds and
+/// scaling it down to fit `max_side` keeping the aspect ratio.
+/// # Errors
+/// Returns [`io::Error`] if the spawn child process fails, times out or exits with a non-zero code.
+#[instrument(skip_all, fields(input = %input_path.as_ref().as_os_str().to_string_lossy(), ?format))]
+pub async fn convert_to_animation(
+    input_path: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    format: AnimationFormat,
+    max_duration: u32,
+    max_side: u32,
+    max_file_size: u32,
+    timeout_secs: u64,
+) -> Result<(), io::Error> {
+    // Scale down only, and keep the sides even as `yuv420p` requires
+    let scale = format!(
+        "scale='min({max_side},iw)':'min({max_side},ih)':force_original_aspect_ratio=decrease,scale=trunc(iw/2)*2:trunc(ih/2)*2"
+    );
+
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-y", "-hide_banner", "-loglevel", "error", "-nostats", "-i"])
+        .arg(input_path.as_ref())
+        .args(["-map", "0:v:0", "-an", "-sn", "-map_metadata", "-1", "-t", &max_duration.to_string()]);
+
+    match format {
+        AnimationFormat::Mp4 => command.args([
+            "-vf",
+            &scale,
+            "-c:v",
+            "libx264",
+            "-preset",
+            "veryfast",
+            "-crf",
+            "26",
+            "-pix_fmt",
+            "yuv420p",
+            "-movflags",
+            "+faststart",
+            "-f",
+            "mp4",
+        ]),
+        AnimationFormat::Gif => command.args([
+            "-vf",
+            &format!("fps=15,{scale}:flags=lanczos,split[s0][s1];[s0]palettegen[p];[s1][p]paletteuse"),
+            "-loop",
+            "0",
+            "-f",
+            "gif",
+        ]),
+        AnimationFormat::Webm => command.args([
+            "-vf",
+            &scale,
+            "-c:v",
+            "libvpx-vp9",
+            "-b:v",
+            "0",
+            "-crf",
+            "35",
+            "-pix_fmt",
+            "yuv420p",
+            "-f",
+            "webm",
+        ]),
+    };
+
+    let child = command
+        .args(["-fs", &max_file_size.to_string()])
+        .arg(output_path.as_ref())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    wait_with_timeout(child, timeout_secs, "animation").await
+}
+
+/// Keep only the `(start, end)` ranges of the input, joining them with the concat demuxer.
+/// Streams are copied, so cuts snap to the nearest keyframes.
+/// # Errors
+/// Returns [`io::Error`] if the spawn child process fails, times out or exits with a non-zero code.
+#[instrument(skip_all, fields(input = %input_path.as_ref().as_os_str().to_string_lossy(), ranges = ranges.len()))]
+pub async fn keep_ranges(
+    input_path: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    ranges: &[(f64, f64)],
+    timeout_secs: u64,
+) -> Result<(), io::Error> {
+    let input_path = input_path.as_ref();
+    let list_path = output_path.as_ref().with_extension("ffconcat");
+    tokio::fs::write(&list_path, concat_list(input_path, ranges)).await?;
+
+    let child = Command::new("ffmpeg")
+        .args(["-y", "-hide_banner", "-loglevel", "error", "-nostats", "-f", "concat", "-safe", "0", "-i"])
+        .arg(&list_path)
+        .args(["-map", "0", "-c", "copy", "-map_metadata", "-1"])
+        .arg(output_path.as_ref())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let res = wait_with_timeout(child, timeout_secs, "cut").await;
+    let _ = tokio::fs::remove_file(&list_path).await;
+    res
+}
+
+/// Add the `(start, end, title)` chapters to the input, replacing its own ones.
+/// # Errors
+/// Returns [`io::Error`] if the spawn child process fails, times out or exits with a non-zero code.
+#[instrument(skip_all, fields(input = %input_path.as_ref().as_os_str().to_string_lossy(), chapters = chapters.len()))]
+pub async fn add_chapters(
+    input_path: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    chapters: &[(f64, f64, &str)],
+    timeout_secs: u64,
+) -> Result<(), io::Error> {
+    let metadata_path = output_path.as_ref().with_extension("ffmetadata");
+    tokio::fs::write(&metadata_path, ffmetadata(chapters)).await?;
+
+    let child = Command::new("ffmpeg")
+        .args(["-y", "-hide_banner", "-loglevel", "error", "-nostats", "-i"])
+        .arg(input_path.as_ref())
+        .arg("-i")
+        .arg(&metadata_path)
+        .args(["-map", "0", "-map_metadata", "0", "-map_chapters", "1", "-c", "copy"])
+        .arg(output_path.as_ref())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let res = wait_with_timeout(child, timeout_secs, "chapters").await;
+    let _ = tokio::fs::remove_file(&metadata_path).await;
+    res
+}
+
+/// Tags of the track cut out of the source
+pub struct TrackTags<'a> {
+    pub title: &'a str,
+    pub album: Option<&'a str>,
+    pub number: u32,
+    pub count: u32,
+}
+
+/// Cut the `start..end` range of the audio stream without re-encoding, replacing its tags.
+/// The cover image is attached if the container supports it.
+/// # Errors
+/// Returns [`io::Error`] if the spawn child process fails, times out or exits with a non-zero code.
+#[instrument(skip_all, fields(input = %input_path.as_ref().as_os_str().to_string_lossy(), start, end))]
+pub async fn extract_track(
+    input_path: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    start: f64,
+    end: f64,
+    tags: &TrackTags<'_>,
+    cover_path: Option<&Path>,
+    timeout_secs: u64,
+) -> Result<(), io::Error> {
+    let output_path = output_path.as_ref();
+    let extension = output_path.extension().unwrap_or_default().to_string_lossy();
+    let cover_path = cover_path.filter(|_| Container::from_extension(&extension).supports_cover_art());
+
+    // Seeking before the input jumps to the start instead of decoding everything before it
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-y", "-hide_banner", "-loglevel", "error", "-nostats", "-ss", &format!("{start:.3}"), "-i"])
+        .arg(input_path.as_ref());
+    if let Some(cover_path) = cover_path {
+        command.arg("-i").arg(cover_path);
+    }
+    command.args(["-t", &format!("{:.3}", end - start), "-map", "0:a:0"]);
+    if cover_path.is_some() {
+        command.args(["-map", "1:v:0", "-c:v", "mjpeg", "-disposition:v:0", "attached_pic"]);
+    }
+    command.args(["-c:a", "copy", "-map_metadata", "-1", "-map_chapters", "-1", "-id3v2_version", "3"]);
+    command.args(track_tag_args(tags));
+
+    let child = command
+        .arg(output_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    wait_with_timeout(child, timeout_secs, "track").await
+}
+
+fn track_tag_args(tags: &TrackTags<'_>) -> Vec<String> {
+    let mut args = vec![
+        "-metadata".to_owned(),
+        format!("title={}", tags.title),
+        "-metadata".to_owned(),
+        format!("track={}/{}", tags.number, tags.count),
+    ];
+    if let Some(album) = tags.album {
+        args.extend(["-metadata".to_owned(), format!("album={album}")]);
+    }
+    args
+}
+
+fn concat_list(path: &Path, ranges: &[(f64, f64)]) -> String {
+    let path = path.to_string_lossy().replace('\'', r"'\''");
+    let mut list = String::from("ffconcat version 1.0\n");
+    for (start, end) in ranges {
+        list.push_str(&format!("file '{path}'\ninpoint {start:.3}\noutpoint {end:.3}\n"));
+    }
+    list
+}
+
+fn ffmetadata(chapters: &[(f64, f64, &str)]) -> String {
+    let mut metadata = String::from(";FFMETADATA1\n");
+    for (start, end, title) in chapters {
+        let title = title.replace(['=', ';', '#', '\\', '\n'], " ");
+        #[allow(clippy::cast_possible_truncation)]
+        metadata.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={title}\n",
+            (start * 1000.0) as i64,
+            (end * 1000.0) as i64,
+        ));
+    }
+    metadata
+}
+
+/// Extract the first audio stream to `m4a`, the stream is copied if the codec isn't passed.
+/// # Errors
+/// Returns [`io::Error`] if the spawn child process fails, times out or exits with a non-zero code.
+#[instrument(skip_all, fields(input = %input_path.as_ref().as_os_str().to_string_lossy(), ?audio_codec))]
+pub async fn extract_audio(
+    input_path: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    audio_codec: Option<&str>,
+    max_file_size: u32,
+    timeout_secs: u64,
+) -> Result<(), io::Error> {
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-y", "-hide_banner", "-loglevel", "error", "-nostats", "-i"])
+        .arg(input_path.as_ref())
+        .args(["-map", "0:a:0", "-vn", "-sn", "-map_metadata", "0"]);
+
+    match audio_codec {
+        Some(codec) => command.args(["-c:a", codec, "-b:a", "192k"]),
+        None => command.args(["-c:a", "copy"]),
+    };
+
+    let child = command
+        .args(["-fs", &max_file_size.to_string(), "-movflags", "+faststart", "-f", "ipod"])
+        .arg(output_path.as_ref())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    wait_with_timeout(child, timeout_secs, "extract_audio").await
+}
+
+/// Transcode the file to `mp4` with the average bitrates, so the output fits the target size.
+/// # Errors
+/// Returns [`io::Error`] if the spawn child process fails, times out or exits with a non-zero code.
+#[instrument(skip_all, fields(input = %input_path.as_ref().as_os_str().to_string_lossy(), video_bitrate, audio_bitrate))]
+pub async fn compress(
+    input_path: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    video_bitrate: u64,
+    audio_bitrate: u64,
+    max_file_size: u32,
+    timeout_secs: u64,
+) -> Result<(), io::Error> {
+    let video_bitrate_str = video_bitrate.to_string();
+
+    let child = Command::new("ffmpeg")
+        .args(["-y", "-hide_banner", "-loglevel", "error", "-nostats", "-i"])
+        .arg(input_path.as_ref())
+        .args([
+            "-map",
+            "0:v:0",
             "-map",
-            "1:a",
+            "0:a:0?",
+            "-map_metadata",
+            "0",
+            "-vf",
+            "scale=trunc(iw/2)*2:trunc(ih/2)*2",
             "-c:v",
-            "copy",
+            "libx264",
+            "-preset",
+            "veryfast",
+            "-b:v",
+            &video_bitrate_str,
+            "-maxrate",
+            &video_bitrate_str,
+            "-bufsize",
+            &(video_bitrate * 2).to_string(),
+            "-pix_fmt",
+            "yuv420p",
             "-c:a",
-            "copy",
-            "-shortest",
-            "-nostats",
+            "aac",
+            "-b:a",
+            &audio_bitrate.to_string(),
+            "-fs",
+            &max_file_size.to_string(),
+            "-movflags",
+            "+faststart",
+            "-f",
+            "mp4",
+        ])
+        .arg(output_path.as_ref())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    wait_with_timeout(child, timeout_secs, "compress").await
+}
+
+/// Encode the first audio stream of at most `max_duration` seconds to mono `OGG/Opus` that Telegram sends as a voice message.
+/// # Errors
+/// Returns [`io::Error`] if the spawn child process fails, times out or exits with a non-zero code.
+#[instrument(skip_all, fields(input = %input_path.as_ref().as_os_str().to_string_lossy(), max_duration))]
+pub async fn convert_to_voice(
+    input_path: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    max_duration: u32,
+    max_file_size: u32,
+    timeout_secs: u64,
+) -> Result<(), io::Error> {
+    let child = Command::new("ffmpeg")
+        .args(["-y", "-hide_banner", "-loglevel", "error", "-nostats", "-i"])
+        .arg(input_path.as_ref())
+        .args([
+            "-map",
+            "0:a:0",
+            "-vn",
+            "-sn",
+            "-map_metadata",
+            "-1",
+            "-t",
+            &max_duration.to_string(),
+            "-ac",
+            "1",
+            "-ar",
+            "48000",
+            "-c:a",
+            "libopus",
+            "-b:a",
+            "64k",
+            "-vbr",
+            "on",
+            "-fs",
+            &max_file_size.to_string(),
+            "-f",
+            "ogg",
+        ])
+        .arg(output_path.as_ref())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    wait_with_timeout(child, timeout_secs, "voice").await
+}
+
+/// Crop the video to the centered square of at most `side` px and `max_duration` seconds,
+/// so Telegram sends it as a round video note.
+/// # Errors
+/// Returns [`io::Error`] if the spawn child process fails, times out or exits with a non-zero code.
+#[instrument(skip_all, fields(input = %input_path.as_ref().as_os_str().to_string_lossy(), max_duration, side))]
+pub async fn convert_to_video_note(
+    input_path: impl AsRef<Path>,
+    output_path: impl AsRef<Path>,
+    max_duration: u32,
+    side: u32,
+    max_file_size: u32,
+    timeout_secs: u64,
+) -> Result<(), io::Error> {
+    // Scale down only, and keep the side even as `yuv420p` requires
+    let filter = format!("crop='min(iw,ih)':'min(iw,ih)',scale='min({side},iw)':-2,scale=trunc(iw/2)*2:trunc(iw/2)*2,setsar=1");
+
+    let child = Command::new("ffmpeg")
+        .args(["-y", "-hide_banner", "-loglevel", "error", "-nostats", "-i"])
+        .arg(input_path.as_ref())
+        .args([
+            "-map",
+            "0:v:0",
+            "-map",
+            "0:a:0?",
+            "-sn",
+            "-map_metadata",
+            "-1",
+            "-t",
+            &max_duration.to_string(),
+            "-vf",
+            &filter,
+            "-c:v",
+            "libx264",
             "-preset",
-            "ultrafast",
+            "veryfast",
+            "-crf",
+            "26",
+            "-pix_fmt",
+            "yuv420p",
+            "-c:a",
+            "aac",
+            "-b:a",
+            "96k",
             "-fs",
-            max_file_size_str.as_ref(),
+            &max_file_size.to_string(),
+            "-movflags",
+            "+faststart",
             "-f",
-            extension.as_ref(),
-            output_path.as_ref().to_string_lossy().as_ref(),
+            "mp4",
         ])
+        .arg(output_path.as_ref())
         .stdin(Stdio::null())
         .stdout(Stdio::null())
-        .stderr(Stdio::inherit())
+        .stderr(Stdio::piped())
         .kill_on_drop(true)
-        .spawn()
+        .spawn()?;
+
+    wait_with_timeout(child, timeout_secs, "video_note").await
+}
+
+/// Decode the first audio stream to mono signed 16-bit little-endian PCM, the samples are passed to `on_chunk` as they're read.
+/// # Errors
+/// Returns [`io::Error`] if the spawn child process fails, times out or exits with a non-zero code.
+#[instrument(skip_all, fields(input = %input_path.as_ref().as_os_str().to_string_lossy(), sample_rate))]
+pub async fn decode_pcm(
+    input_path: impl AsRef<Path>,
+    sample_rate: u32,
+    timeout_secs: u64,
+    mut on_chunk: impl FnMut(&[u8]),
+) -> Result<(), io::Error> {
+    let mut child = Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-nostats", "-i"])
+        .arg(input_path.as_ref())
+        .args(["-map", "0:a:0", "-vn", "-sn", "-ac", "1", "-ar", &sample_rate.to_string(), "-f", "s16le", "pipe:1"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let read = async {
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            match stdout.read(&mut buf).await? {
+                0 => return Ok::<_, io::Error>(()),
+                len => on_chunk(&buf[..len]),
+            }
+        }
+    };
+    timeout(Duration::from_secs(timeout_secs), read)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "FFmpeg process timed out"))??;
+
+    wait_with_timeout(child, timeout_secs, "decode_pcm").await
+}
+
+async fn wait_with_timeout(child: Child, timeout_secs: u64, operation: &'static str) -> Result<(), io::Error> {
+    let timer = ProcessTimer::start("ffmpeg", operation);
+    let res = timeout(Duration::from_secs(timeout_secs), child.wait_with_output()).await;
+    if let Ok(Ok(output)) = &res {
+        timer.record_exit(output.status);
+    }
+    match res {
+        Ok(Ok(output)) if output.status.success() => Ok(()),
+        Ok(Ok(output)) => Err(io::Error::other(format!(
+            "FFmpeg exited with status `{}` and message: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr),
+        ))),
+        Ok(Err(err)) => Err(err),
+        Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "FFmpeg process timed out")),
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -135,3 +821,113 @@ pub async fn download_thumbnail_to_path(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_segment_list() {
+        let parts = parse_segment_list("id_000.mp4,0.000000,10.010000\nid_001.mp4,10.010000,19.986000\n", Path::new("/tmp"));
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].path, Path::new("/tmp/id_000.mp4"));
+        assert!((parts[0].start - 0.0).abs() < f64::EPSILON);
+        assert!((parts[0].end - 10.01).abs() < f64::EPSILON);
+        assert_eq!(parts[1].path, Path::new("/tmp/id_001.mp4"));
+        assert!((parts[1].start - 10.01).abs() < f64::EPSILON);
+        assert!((parts[1].end - 19.986).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_record_live_args() {
+        assert_eq!(
+            record_live_args(5, 60, 1000),
+            [
+                "-y",
+                "-hide_banner",
+                "-loglevel",
+                "error",
+                "-nostats",
+                "-i",
+                "pipe:5",
+                "-map",
+                "0:v:0?",
+                "-map",
+                "0:a:0?",
+                "-c",
+                "copy",
+                "-t",
+                "60",
+                "-fs",
+                "1000",
+                "-movflags",
+                "+faststart",
+                "-f",
+                "mp4",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_track_tag_args() {
+        let tags = TrackTags {
+            title: "Intro",
+            album: Some("Album"),
+            number: 1,
+            count: 12,
+        };
+        assert_eq!(
+            track_tag_args(&tags),
+            ["-metadata", "title=Intro", "-metadata", "track=1/12", "-metadata", "album=Album"]
+        );
+
+        let tags = TrackTags { album: None, ..tags };
+        assert_eq!(track_tag_args(&tags), ["-metadata", "title=Intro", "-metadata", "track=1/12"]);
+    }
+
+    #[test]
+    fn test_audio_track_args() {
+        assert!(audio_track_args(&[(None, false)]).is_empty());
+        assert_eq!(
+            audio_track_args(&[(Some("en-US"), false)]),
+            ["-metadata:s:a:0", "language=en", "-disposition:a:0", "default"]
+        );
+        assert_eq!(
+            audio_track_args(&[(Some("en"), false), (Some("es-419"), true), (Some("???"), false)]),
+            [
+                "-metadata:s:a:0",
+                "language=en",
+                "-disposition:a:0",
+                "0",
+                "-metadata:s:a:1",
+                "language=es",
+                "-disposition:a:1",
+                "default",
+                "-disposition:a:2",
+                "0",
+            ]
+        );
+        assert_eq!(
+            audio_track_args(&[(Some("ja"), false), (Some("DE"), false)]),
+            [
+                "-metadata:s:a:0",
+                "language=ja",
+                "-disposition:a:0",
+                "default",
+                "-metadata:s:a:1",
+                "language=de",
+                "-disposition:a:1",
+                "0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_segment_list_skips_invalid_lines() {
+        let parts = parse_segment_list("\nid_000.mp4,0.0\n\"id,000.mp4\",0.0,1.5\n", Path::new("/tmp"));
+
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].path, Path::new("/tmp/id,000.mp4"));
+    }
+}
@@ -0,0 +1,244 @@
+use serde::Deserialize;
+use std::{io, path::Path, process::Stdio, time::Duration};
+use tokio::{process::Command, time::timeout};
+use tracing::instrument;
+
+use crate::value_objects::MediaKind;
+
+const PROBE_TIMEOUT: u64 = 30;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Format {
+    pub duration: Option<String>,
+    pub bit_rate: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SideData {
+    pub rotation: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StreamTags {
+    pub rotate: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Disposition {
+    /// Cover art of the audio is a video stream with a single frame
+    #[serde(default)]
+    pub attached_pic: u8,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Stream {
+    pub codec_type: Option<String>,
+    pub codec_name: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    #[serde(default)]
+    pub side_data_list: Vec<SideData>,
+    #[serde(default)]
+    pub tags: StreamTags,
+    #[serde(default)]
+    pub disposition: Disposition,
+}
+
+impl Stream {
+    /// Rotation from the display matrix or from the `rotate` tag of older muxers
+    #[allow(clippy::cast_possible_truncation)]
+    #[must_use]
+    pub fn rotation(&self) -> Option<i32> {
+        self.side_data_list
+            .iter()
+            .find_map(|side_data| side_data.rotation)
+            .map(|rotation| rotation.round() as i32)
+            .or_else(|| self.tags.rotate.as_deref().and_then(|rotate| rotate.parse().ok()))
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ChapterTags {
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Chapter {
+    pub start_time: String,
+    pub end_time: String,
+    #[serde(default)]
+    pub tags: ChapterTags,
+}
+
+impl Chapter {
+    #[must_use]
+    pub fn start(&self) -> Option<f64> {
+        self.start_time.parse().ok()
+    }
+
+    #[must_use]
+    pub fn end(&self) -> Option<f64> {
+        self.end_time.parse().ok()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Probe {
+    pub format: Format,
+    #[serde(default)]
+    pub streams: Vec<Stream>,
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,
+}
+
+impl Probe {
+    #[must_use]
+    pub fn duration(&self) -> Option<f64> {
+        self.format.duration.as_deref().and_then(|duration| duration.parse().ok())
+    }
+
+    #[must_use]
+    pub fn bitrate(&self) -> Option<u64> {
+        self.format.bit_rate.as_deref().and_then(|bitrate| bitrate.parse().ok())
+    }
+
+    #[must_use]
+    pub fn video_stream(&self) -> Option<&Stream> {
+        self.stream("video")
+    }
+
+    #[must_use]
+    pub fn audio_stream(&self) -> Option<&Stream> {
+        self.stream("audio")
+    }
+
+    #[must_use]
+    pub fn video_codec(&self) -> Option<&str> {
+        self.video_stream().and_then(|stream| stream.codec_name.as_deref())
+    }
+
+    #[must_use]
+    pub fn audio_codec(&self) -> Option<&str> {
+        self.audio_stream().and_then(|stream| stream.codec_name.as_deref())
+    }
+
+    /// Video if there is a video stream besides the cover art, audio if there is an audio stream only
+    #[must_use]
+    pub fn media_kind(&self) -> Option<MediaKind> {
+        let is_type = |stream: &Stream, codec_type| stream.codec_type.as_deref() == Some(codec_type);
+        if self
+            .streams
+            .iter()
+            .any(|stream| is_type(stream, "video") && stream.disposition.attached_pic == 0)
+        {
+            Some(MediaKind::Video)
+        } else if self.streams.iter().any(|stream| is_type(stream, "audio")) {
+            Some(MediaKind::Audio)
+        } else {
+            None
+        }
+    }
+
+    fn stream(&self, codec_type: &str) -> Option<&Stream> {
+        self.streams.iter().find(|stream| stream.codec_type.as_deref() == Some(codec_type))
+    }
+}
+
+/// Probe the media file.
+/// # Errors
+/// Returns [`Error::Io`] if the spawn child process fails, times out or exits with a non-zero code.
+/// Returns [`Error::Json`] if the output can't be parsed.
+#[instrument(skip_all, fields(path = %path.as_ref().as_os_str().to_string_lossy()))]
+pub async fn probe(path: impl AsRef<Path>) -> Result<Probe, Error> {
+    let child = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-print_format",
+            "json",
+            "-show_format",
+            "-show_streams",
+            "-show_chapters",
+        ])
+        .arg(path.as_ref())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let output = match timeout(Duration::from_secs(PROBE_TIMEOUT), child.wait_with_output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(err)) => return Err(err.into()),
+        Err(_) => return Err(io::Error::new(io::ErrorKind::TimedOut, "FFprobe timed out").into()),
+    };
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "FFprobe exited with status `{}` and message: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr),
+        ))
+        .into());
+    }
+
+    serde_json::from_slice(&output.stdout).map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_probe() {
+        let probe: Probe = serde_json::from_str(
+            r#"{
+                "streams": [
+                    {"codec_type": "video", "codec_name": "h264", "width": 1920, "height": 1080, "side_data_list": [{"rotation": -90}]},
+                    {"codec_type": "audio", "codec_name": "aac"}
+                ],
+                "format": {"duration": "12.500000", "bit_rate": "1500000"},
+                "chapters": [{"start_time": "0.000000", "end_time": "6.250000", "tags": {"title": "Intro"}}]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(probe.duration(), Some(12.5));
+        assert_eq!(probe.bitrate(), Some(1_500_000));
+        assert_eq!(probe.video_codec(), Some("h264"));
+        assert_eq!(probe.audio_codec(), Some("aac"));
+        assert_eq!(probe.video_stream().and_then(Stream::rotation), Some(-90));
+        assert_eq!(probe.chapters[0].end(), Some(6.25));
+        assert_eq!(probe.chapters[0].tags.title.as_deref(), Some("Intro"));
+    }
+
+    #[test]
+    fn test_parse_rotate_tag() {
+        let stream: Stream = serde_json::from_str(r#"{"codec_type": "video", "tags": {"rotate": "90"}}"#).unwrap();
+
+        assert_eq!(stream.rotation(), Some(90));
+    }
+
+    #[test]
+    fn test_media_kind() {
+        let parse = |streams: &str| serde_json::from_str::<Probe>(&format!(r#"{{"streams": {streams}, "format": {{}}}}"#)).unwrap();
+
+        assert_eq!(
+            parse(r#"[{"codec_type": "video", "codec_name": "h264"}, {"codec_type": "audio"}]"#).media_kind(),
+            Some(MediaKind::Video)
+        );
+        assert_eq!(
+            parse(r#"[{"codec_type": "audio", "codec_name": "mp3"}, {"codec_type": "video", "codec_name": "mjpeg", "disposition": {"attached_pic": 1}}]"#)
+                .media_kind(),
+            Some(MediaKind::Audio)
+        );
+        assert_eq!(parse(r#"[{"codec_type": "data"}]"#).media_kind(), None);
+    }
+}
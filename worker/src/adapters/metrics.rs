@@ -0,0 +1,298 @@
+use prometheus::{
+    Encoder as _, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+    exponential_buckets,
+};
+use std::{
+    process::ExitStatus,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+use tracing::{Span, field, info_span};
+use url::Url;
+
+const NAMESPACE: &str = "worker";
+
+pub static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+fn register<T: prometheus::core::Collector + Clone + 'static>(collector: T) -> T {
+    REGISTRY.register(Box::new(collector.clone())).expect("metric is registered once");
+    collector
+}
+
+/// Labels: `kind`, `domain`, `outcome`
+pub static DOWNLOADS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("downloads_total", "Download requests by the outcome").namespace(NAMESPACE),
+            &["kind", "domain", "outcome"],
+        )
+        .unwrap(),
+    )
+});
+
+/// Labels: `kind`
+pub static DOWNLOAD_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(
+        HistogramVec::new(
+            HistogramOpts::new("download_duration_seconds", "Time until the media is ready to be sent")
+                .namespace(NAMESPACE)
+                .buckets(exponential_buckets(0.5, 2.0, 12).unwrap()),
+            &["kind"],
+        )
+        .unwrap(),
+    )
+});
+
+/// Labels: `kind`, `class`
+pub static ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("errors_total", "Failed requests by the error class").namespace(NAMESPACE),
+            &["kind", "class"],
+        )
+        .unwrap(),
+    )
+});
+
+/// Labels: `kind`
+pub static JOBS_IN_FLIGHT: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register(
+        IntGaugeVec::new(
+            Opts::new("jobs_in_flight", "Requests being processed").namespace(NAMESPACE),
+            &["kind"],
+        )
+        .unwrap(),
+    )
+});
+
+/// Labels: `kind`
+pub static STREAMED_BYTES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("streamed_bytes_total", "Bytes sent in the file chunks").namespace(NAMESPACE),
+            &["kind"],
+        )
+        .unwrap(),
+    )
+});
+
+/// Labels: `tool`, `operation`
+pub static PROCESS_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register(
+        HistogramVec::new(
+            HistogramOpts::new("process_duration_seconds", "Run time of the `yt-dlp` and `ffmpeg` processes")
+                .namespace(NAMESPACE)
+                .buckets(exponential_buckets(0.25, 2.0, 12).unwrap()),
+            &["tool", "operation"],
+        )
+        .unwrap(),
+    )
+});
+
+pub static RANGE_FETCH_BYTES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(
+        IntCounter::with_opts(Opts::new("range_fetch_bytes_total", "Bytes fetched by the range requests").namespace(NAMESPACE)).unwrap(),
+    )
+});
+
+pub static RANGE_FETCH_DURATION: LazyLock<Histogram> = LazyLock::new(|| {
+    register(
+        Histogram::with_opts(
+            HistogramOpts::new("range_fetch_duration_seconds", "Time of the whole range fetch of a stream")
+                .namespace(NAMESPACE)
+                .buckets(exponential_buckets(0.25, 2.0, 12).unwrap()),
+        )
+        .unwrap(),
+    )
+});
+
+/// Labels: `root`, `outcome`
+pub static SCRATCH_ADMISSIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("scratch_admissions_total", "Work dir requests by the free space check outcome").namespace(NAMESPACE),
+            &["root", "outcome"],
+        )
+        .unwrap(),
+    )
+});
+
+/// Labels: `root`
+pub static SCRATCH_AVAILABLE_BYTES: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register(
+        IntGaugeVec::new(
+            Opts::new("scratch_available_bytes", "Free space of the scratch root at the last check").namespace(NAMESPACE),
+            &["root"],
+        )
+        .unwrap(),
+    )
+});
+
+pub static SCRATCH_SWEPT_DIRS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(
+        IntCounter::with_opts(Opts::new("scratch_swept_dirs_total", "Stale work dirs removed by the sweeps").namespace(NAMESPACE)).unwrap(),
+    )
+});
+
+/// Labels: `result`
+pub static CACHE_LOOKUPS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("cache_lookups_total", "Media cache lookups by the result").namespace(NAMESPACE),
+            &["result"],
+        )
+        .unwrap(),
+    )
+});
+
+pub static CACHE_SIZE_BYTES: LazyLock<IntGauge> = LazyLock::new(|| {
+    register(IntGauge::with_opts(Opts::new("cache_size_bytes", "Size of the cached media").namespace(NAMESPACE)).unwrap())
+});
+
+/// Register the metrics, so they're exported before their first update
+pub fn init() {
+    LazyLock::force(&DOWNLOADS);
+    LazyLock::force(&DOWNLOAD_DURATION);
+    LazyLock::force(&ERRORS);
+    LazyLock::force(&JOBS_IN_FLIGHT);
+    LazyLock::force(&STREAMED_BYTES);
+    LazyLock::force(&PROCESS_DURATION);
+    LazyLock::force(&RANGE_FETCH_BYTES);
+    LazyLock::force(&RANGE_FETCH_DURATION);
+    LazyLock::force(&SCRATCH_ADMISSIONS);
+    LazyLock::force(&SCRATCH_AVAILABLE_BYTES);
+    LazyLock::force(&SCRATCH_SWEPT_DIRS);
+    LazyLock::force(&CACHE_LOOKUPS);
+    LazyLock::force(&CACHE_SIZE_BYTES);
+}
+
+/// Observe the elapsed time of the process on drop, so the failed runs are counted too.
+/// The run is also traced as a child span of the current one.
+pub struct ProcessTimer {
+    tool: &'static str,
+    operation: &'static str,
+    start: Instant,
+    span: Span,
+}
+
+impl ProcessTimer {
+    #[must_use]
+    pub fn start(tool: &'static str, operation: &'static str) -> Self {
+        Self {
+            tool,
+            operation,
+            start: Instant::now(),
+            span: info_span!("process", tool, operation, exit_code = field::Empty, duration_ms = field::Empty),
+        }
+    }
+
+    pub fn record_exit(&self, status: ExitStatus) {
+        self.span.record("exit_code", status.code());
+    }
+}
+
+impl Drop for ProcessTimer {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        PROCESS_DURATION
+            .with_label_values(&[self.tool, self.operation])
+            .observe(elapsed.as_secs_f64());
+        self.span
+            .record("duration_ms", u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX));
+    }
+}
+
+/// Count the job as in flight until drop
+pub struct InFlight {
+    kind: &'static str,
+    start: Instant,
+}
+
+impl InFlight {
+    #[must_use]
+    pub fn start(kind: &'static str) -> Self {
+        JOBS_IN_FLIGHT.with_label_values(&[kind]).inc();
+        Self {
+            kind,
+            start: Instant::now(),
+        }
+    }
+
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        JOBS_IN_FLIGHT.with_label_values(&[self.kind]).dec();
+    }
+}
+
+/// Label of the supported sites by their domains, subdomains are matched too
+const DOMAIN_LABELS: [(&str, &[&str]); 8] = [
+    ("youtube.com", &["youtube.com", "youtube-nocookie.com", "youtu.be"]),
+    ("vimeo.com", &["vimeo.com"]),
+    ("tiktok.com", &["tiktok.com"]),
+    ("instagram.com", &["instagram.com", "instagr.am"]),
+    ("x.com", &["x.com", "twitter.com", "t.co"]),
+    ("reddit.com", &["reddit.com", "redd.it"]),
+    ("soundcloud.com", &["soundcloud.com"]),
+    ("twitch.tv", &["twitch.tv"]),
+];
+
+/// Site of the URL host, so the label doesn't depend on the subdomains and short links.
+/// Hosts come from the users, so the unsupported ones share a label to keep the cardinality bounded.
+/// # Returns
+/// Returns `other` for the unsupported sites and `unknown` for invalid URLs
+#[must_use]
+pub fn domain_label(url: &str) -> &'static str {
+    let Some(host) = Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_ascii_lowercase)) else {
+        return "unknown";
+    };
+    let host = host.trim_end_matches('.');
+    DOMAIN_LABELS
+        .iter()
+        .find(|(_, domains)| {
+            domains.iter().any(|domain| {
+                host.strip_suffix(domain)
+                    .is_some_and(|prefix| prefix.is_empty() || prefix.ends_with('.'))
+            })
+        })
+        .map_or("other", |(label, _)| label)
+}
+
+/// # Panics
+/// Panics if the metrics can't be encoded, which happens only for the invalid metric names
+#[must_use]
+pub fn encode() -> String {
+    let mut buffer = vec![];
+    TextEncoder::new()
+        .encode(&REGISTRY.gather(), &mut buffer)
+        .expect("metrics are encoded");
+    String::from_utf8(buffer).expect("metrics are UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_domain_label() {
+        assert_eq!(domain_label("https://www.youtube.com/watch?v=id"), "youtube.com");
+        assert_eq!(domain_label("https://m.youtube.com/watch?v=id"), "youtube.com");
+        assert_eq!(domain_label("https://youtu.be/id"), "youtube.com");
+        assert_eq!(domain_label("https://twitter.com/user/status/id"), "x.com");
+        assert_eq!(domain_label("https://example.co.uk/video.mp4"), "other");
+        assert_eq!(domain_label("https://notyoutube.com/watch?v=id"), "other");
+        assert_eq!(domain_label("https://192.168.0.1/video.mp4"), "other");
+        assert_eq!(domain_label("not a url"), "unknown");
+    }
+
+    #[test]
+    fn test_encode() {
+        DOWNLOADS.with_label_values(&["video", "youtube.com", "success"]).inc();
+        assert!(encode().contains(r#"worker_downloads_total{domain="youtube.com",kind="video",outcome="success"}"#));
+    }
+}
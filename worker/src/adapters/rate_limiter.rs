@@ -0,0 +1,358 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher as _, RandomState},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+use tracing::{debug, warn};
+use url::{Host, Url};
+
+use crate::config;
+
+#[derive(Debug)]
+struct Bucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(rate: config::Rate, now: Instant) -> Self {
+        let burst = f64::from(rate.burst);
+        Self {
+            rate: rate.rate,
+            burst,
+            tokens: burst,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, rate: f64, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(self.burst);
+        self.updated = now;
+    }
+
+    /// Time until a token is available
+    fn wait(&self, rate: f64) -> Duration {
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / rate)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct HostBucket {
+    bucket: Bucket,
+    /// The rate is divided by it, doubled on every throttling
+    slowdown: f64,
+    throttled_at: Instant,
+}
+
+impl HostBucket {
+    fn rate(&self) -> f64 {
+        self.bucket.rate / self.slowdown
+    }
+
+    /// Halve the slowdown for every `recovery` period without throttling
+    fn recover(&mut self, recovery: Duration, now: Instant) {
+        while self.slowdown > 1.0 && now.saturating_duration_since(self.throttled_at) >= recovery {
+            self.bucket.refill(self.rate(), self.throttled_at + recovery);
+            self.slowdown = (self.slowdown / 2.0).max(1.0);
+            self.throttled_at += recovery;
+        }
+    }
+
+    /// Full bucket without the slowdown, it's the same as a new one
+    fn is_idle(&self) -> bool {
+        self.slowdown <= 1.0 && self.bucket.tokens >= self.bucket.burst
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    global: Option<Bucket>,
+    hosts: HashMap<String, HostBucket>,
+}
+
+impl State {
+    /// Drop the idle buckets, so the map holds only the hosts that were requested recently
+    fn evict_idle(&mut self, recovery: Duration, now: Instant) {
+        self.hosts.retain(|_, host| {
+            host.recover(recovery, now);
+            host.bucket.refill(host.rate(), now);
+            !host.is_idle()
+        });
+    }
+}
+
+/// Current state of a bucket, `host` is empty for the global one
+#[derive(Debug, Clone, PartialEq)]
+pub struct BucketState {
+    pub host: String,
+    pub tokens: f64,
+    /// Rate with the slowdown applied
+    pub rate: f64,
+    pub burst: u32,
+    pub slowdown: f64,
+}
+
+/// Token buckets of the hosts and a global one for the `yt-dlp` launches and the range requests.
+/// Hosts are grouped by the configured domains, so the CDN nodes share a bucket, other hosts have their own ones.
+/// Buckets that refilled without a slowdown are dropped.
+/// Requests aren't limited if the config is missing.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    cfg: Option<Arc<config::RateLimit>>,
+    state: Arc<Mutex<State>>,
+}
+
+impl RateLimiter {
+    #[must_use]
+    pub fn new(cfg: Option<config::RateLimit>) -> Self {
+        Self {
+            cfg: cfg.map(Arc::new),
+            state: Arc::default(),
+        }
+    }
+
+    /// Wait for a token of the URL host and of the global bucket, then for a random delay
+    pub async fn acquire(&self, url: &str) {
+        let Some(cfg) = &self.cfg else {
+            return;
+        };
+        let key = host_key(cfg, url);
+
+        let mut waited = Duration::ZERO;
+        loop {
+            let wait = self.try_acquire(cfg, &key, Instant::now());
+            if wait.is_zero() {
+                break;
+            }
+            waited += wait;
+            sleep(wait).await;
+        }
+        let jitter = jitter(cfg.jitter);
+        debug!(host = key, ?waited, ?jitter, "Rate limit token acquired");
+        sleep(jitter).await;
+    }
+
+    fn try_acquire(&self, cfg: &config::RateLimit, key: &str, now: Instant) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        state.evict_idle(Duration::from_secs(cfg.recovery), now);
+        let State { global, hosts } = &mut *state;
+
+        let host = hosts.entry(key.to_owned()).or_insert_with(|| HostBucket {
+            bucket: Bucket::new(host_rate(cfg, key), now),
+            slowdown: 1.0,
+            throttled_at: now,
+        });
+        host.recover(Duration::from_secs(cfg.recovery), now);
+        host.bucket.refill(host.rate(), now);
+        let mut wait = host.bucket.wait(host.rate());
+
+        if let Some(global) = cfg.global.map(|rate| global.get_or_insert_with(|| Bucket::new(rate, now))) {
+            global.refill(global.rate, now);
+            wait = wait.max(global.wait(global.rate));
+            if wait.is_zero() {
+                global.tokens -= 1.0;
+            }
+        }
+        if wait.is_zero() {
+            host.bucket.tokens -= 1.0;
+        }
+        wait
+    }
+
+    /// Slow down the requests to the URL host after it responded with 429/403 or `yt-dlp` reported the rate limit
+    pub fn throttled(&self, url: &str) {
+        let Some(cfg) = &self.cfg else {
+            return;
+        };
+        let key = host_key(cfg, url);
+        let now = Instant::now();
+
+        let mut state = self.state.lock().unwrap();
+        let host = state.hosts.entry(key.clone()).or_insert_with(|| HostBucket {
+            bucket: Bucket::new(host_rate(cfg, &key), now),
+            slowdown: 1.0,
+            throttled_at: now,
+        });
+        host.bucket.refill(host.rate(), now);
+        host.slowdown = (host.slowdown * 2.0).min(cfg.max_slowdown);
+        host.throttled_at = now;
+        warn!(host = key, slowdown = host.slowdown, "Host throttles the requests, slow down");
+    }
+
+    /// Global bucket and the buckets of the hosts that were requested recently
+    #[must_use]
+    pub fn snapshot(&self) -> (Option<BucketState>, Vec<BucketState>) {
+        let Some(cfg) = &self.cfg else {
+            return (None, vec![]);
+        };
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state.evict_idle(Duration::from_secs(cfg.recovery), now);
+
+        let global = cfg.global.map(|rate| {
+            let global = state.global.get_or_insert_with(|| Bucket::new(rate, now));
+            global.refill(global.rate, now);
+            BucketState {
+                host: String::new(),
+                tokens: global.tokens,
+                rate: global.rate,
+                burst: rate.burst,
+                slowdown: 1.0,
+            }
+        });
+        let mut hosts = state
+            .hosts
+            .iter()
+            .map(|(key, host)| BucketState {
+                host: key.clone(),
+                tokens: host.bucket.tokens,
+                rate: host.rate(),
+                burst: host_rate(cfg, key).burst,
+                slowdown: host.slowdown,
+            })
+            .collect::<Vec<_>>();
+        hosts.sort_by(|a, b| a.host.cmp(&b.host));
+        (global, hosts)
+    }
+}
+
+/// The most specific configured domain the host matches, otherwise the host itself
+fn host_key(cfg: &config::RateLimit, url: &str) -> String {
+    let host = match Url::parse(url).ok().and_then(|url| url.host().map(|host| host.to_owned())) {
+        Some(Host::Domain(domain)) => domain,
+        Some(host) => return host.to_string(),
+        None => return url.to_owned(),
+    };
+    let host = host.trim_end_matches('.');
+
+    cfg.hosts
+        .keys()
+        .filter(|domain| host == domain.as_ref() || host.ends_with(&format!(".{domain}")))
+        .max_by_key(|domain| domain.len())
+        .map_or_else(|| host.to_owned(), ToString::to_string)
+}
+
+fn host_rate(cfg: &config::RateLimit, key: &str) -> config::Rate {
+    cfg.hosts.get(key).copied().unwrap_or(cfg.default)
+}
+
+/// Random delay up to `max` seconds
+fn jitter(max: f64) -> Duration {
+    if max <= 0.0 {
+        return Duration::ZERO;
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let fraction = (RandomState::new().hash_one(Instant::now()) >> 11) as f64 / (1u64 << 53) as f64;
+    Duration::from_secs_f64(max * fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg() -> config::RateLimit {
+        config::RateLimit {
+            default: config::Rate { rate: 1.0, burst: 2 },
+            global: Some(config::Rate { rate: 10.0, burst: 3 }),
+            hosts: HashMap::from([("googlevideo.com".into(), config::Rate { rate: 5.0, burst: 1 })]),
+            jitter: 0.0,
+            max_slowdown: 4.0,
+            recovery: 60,
+        }
+    }
+
+    #[test]
+    fn test_host_key() {
+        let cfg = cfg();
+
+        assert_eq!(host_key(&cfg, "https://www.youtube.com/watch?v=id"), "www.youtube.com");
+        assert_eq!(
+            host_key(&cfg, "https://rr1---sn-abc.googlevideo.com/videoplayback"),
+            "googlevideo.com"
+        );
+        assert_eq!(host_key(&cfg, "https://youtu.be/id"), "youtu.be");
+        assert_eq!(host_key(&cfg, "https://www.bbc.co.uk/news"), "www.bbc.co.uk");
+        assert_eq!(host_key(&cfg, "https://www.itv.co.uk/watch"), "www.itv.co.uk");
+        assert_eq!(host_key(&cfg, "http://127.0.0.1:8080/file"), "127.0.0.1");
+    }
+
+    #[test]
+    fn test_burst_then_rate() {
+        let limiter = RateLimiter::new(Some(cfg()));
+        let cfg = limiter.cfg.clone().unwrap();
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire(&cfg, "youtube.com", now).is_zero());
+        assert!(limiter.try_acquire(&cfg, "youtube.com", now).is_zero());
+        assert_eq!(limiter.try_acquire(&cfg, "youtube.com", now), Duration::from_secs(1));
+        assert!(limiter.try_acquire(&cfg, "youtube.com", now + Duration::from_secs(1)).is_zero());
+    }
+
+    #[test]
+    fn test_global_cap() {
+        let limiter = RateLimiter::new(Some(cfg()));
+        let cfg = limiter.cfg.clone().unwrap();
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire(&cfg, "a.com", now).is_zero());
+        assert!(limiter.try_acquire(&cfg, "b.com", now).is_zero());
+        assert!(limiter.try_acquire(&cfg, "c.com", now).is_zero());
+        // Host has a token, but the global bucket is empty
+        assert_eq!(limiter.try_acquire(&cfg, "d.com", now), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_slowdown_and_recovery() {
+        let limiter = RateLimiter::new(Some(cfg()));
+        let cfg = limiter.cfg.clone().unwrap();
+
+        for _ in 0..3 {
+            limiter.throttled("https://www.youtube.com/watch?v=id");
+        }
+        let (_, hosts) = limiter.snapshot();
+        assert_eq!(hosts[0].host, "www.youtube.com");
+        assert!((hosts[0].slowdown - 4.0).abs() < f64::EPSILON);
+        assert!((hosts[0].rate - 0.25).abs() < f64::EPSILON);
+
+        let mut state = limiter.state.lock().unwrap();
+        let host = state.hosts.get_mut("www.youtube.com").unwrap();
+        let throttled_at = host.throttled_at;
+        host.recover(Duration::from_secs(cfg.recovery), throttled_at + Duration::from_secs(130));
+        assert!((host.slowdown - 1.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_evict_idle() {
+        let limiter = RateLimiter::new(Some(cfg()));
+        let cfg = limiter.cfg.clone().unwrap();
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire(&cfg, "a.com", now).is_zero());
+        limiter.throttled("https://b.com/");
+        // `a.com` refilled in a second, `b.com` keeps its slowdown until the recovery
+        assert!(limiter.try_acquire(&cfg, "c.com", now + Duration::from_secs(2)).is_zero());
+
+        let mut hosts = limiter.state.lock().unwrap().hosts.keys().cloned().collect::<Vec<_>>();
+        hosts.sort();
+        assert_eq!(hosts, ["b.com", "c.com"]);
+    }
+
+    #[tokio::test]
+    async fn test_disabled() {
+        let limiter = RateLimiter::new(None);
+
+        limiter.acquire("https://www.youtube.com/watch?v=id").await;
+        limiter.throttled("https://www.youtube.com/watch?v=id");
+
+        assert_eq!(limiter.snapshot(), (None, vec![]));
+    }
+}
@@ -0,0 +1,238 @@
+use nix::{
+    errno::Errno,
+    fcntl::{Flock, FlockArg},
+    sys::statvfs::statvfs,
+};
+use std::{
+    env,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tempfile::{Builder, TempDir};
+use tracing::{debug, info, instrument, warn};
+
+use crate::adapters::metrics;
+
+/// Work dirs are recognized by the prefix when the stale ones are swept
+const WORK_DIR_PREFIX: &str = "worker-";
+/// Each worker keeps its work dirs in its own subdir of the root, so the roots can be shared
+const INSTANCE_DIR_PREFIX: &str = "media-downloader-";
+/// Locked by the worker while it runs, so a free lock means the instance dir is left by a dead one
+const LOCK_NAME: &str = ".lock";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Statvfs error: {0}")]
+    Statvfs(#[from] Errno),
+    #[error("Not enough disk space: {required} bytes required, {available} available")]
+    NoSpace { required: u64, available: u64 },
+}
+
+/// Roots of the work dirs: small media go to the tmpfs one if it's configured, others go to the disk one
+#[derive(Debug, Clone)]
+pub struct Scratch {
+    disk: Root,
+    tmpfs: Option<Root>,
+    tmpfs_max_size: u64,
+    reserve: u64,
+}
+
+/// Root shared by the workers with the instance dir of this one
+#[derive(Debug, Clone)]
+struct Root {
+    path: PathBuf,
+    instance_dir: PathBuf,
+    _lock: Arc<Flock<File>>,
+}
+
+impl Root {
+    /// The instance dir is locked before it gets its name, so the sweeps of other workers never see it unlocked
+    fn open(path: &Path) -> Result<Self, io::Error> {
+        fs::create_dir_all(path)?;
+        let new_dir = Builder::new().prefix(".new-").tempdir_in(path)?;
+        let lock = File::create(new_dir.path().join(LOCK_NAME))?;
+        let lock = Flock::lock(lock, FlockArg::LockExclusiveNonblock).map_err(|(_, errno)| io::Error::from(errno))?;
+        let instance_dir = Builder::new()
+            .prefix(INSTANCE_DIR_PREFIX)
+            .make_in(path, |instance_dir| fs::rename(new_dir.path(), instance_dir))?;
+        let instance_dir = instance_dir.into_temp_path().keep().map_err(|err| err.error)?;
+        debug!(instance_dir = %instance_dir.display(), "Scratch root opened");
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            instance_dir,
+            _lock: Arc::new(lock),
+        })
+    }
+}
+
+impl Scratch {
+    /// Create the roots if they don't exist and the instance dirs in them,
+    /// the system temp dir is used if the disk root isn't set
+    /// # Errors
+    /// Returns [`io::Error`] if a root or an instance dir can't be created
+    pub fn new(disk_dir: Option<&Path>, tmpfs_dir: Option<&Path>, tmpfs_max_size: u64, reserve: u64) -> Result<Self, io::Error> {
+        let disk = Root::open(&disk_dir.map_or_else(env::temp_dir, Path::to_path_buf))?;
+        let tmpfs = tmpfs_dir.map(Root::open).transpose()?;
+
+        Ok(Self {
+            disk,
+            tmpfs,
+            tmpfs_max_size,
+            reserve,
+        })
+    }
+
+    /// Create a work dir with enough free space for the expected size.
+    /// Media of unknown size always go to the disk root, only the reserve is checked for them.
+    /// # Errors
+    /// Returns [`Error::NoSpace`] if the root doesn't have enough free space
+    #[instrument(skip(self))]
+    pub fn work_dir(&self, expected_size: Option<u64>) -> Result<TempDir, Error> {
+        let required = expected_size.unwrap_or(0).saturating_add(self.reserve);
+
+        if let (Some(tmpfs), Some(expected_size)) = (&self.tmpfs, expected_size)
+            && expected_size <= self.tmpfs_max_size
+        {
+            match available_space(&tmpfs.path) {
+                Ok(available) if available >= required => {
+                    debug!(available, "Use tmpfs root");
+                    observe_admission("tmpfs", available, true);
+                    return create_work_dir(&tmpfs.instance_dir);
+                }
+                Ok(available) => {
+                    debug!(available, "Not enough space in tmpfs root");
+                    observe_admission("tmpfs", available, false);
+                }
+                Err(err) => warn!("Failed to get tmpfs root space: {err}"),
+            }
+        }
+
+        let available = available_space(&self.disk.path)?;
+        observe_admission("disk", available, available >= required);
+        if available < required {
+            return Err(Error::NoSpace { required, available });
+        }
+        create_work_dir(&self.disk.instance_dir)
+    }
+
+    /// Remove the own work dirs older than the max age, they're left by interrupted jobs,
+    /// and the instance dirs of other workers that aren't running anymore.
+    /// Work dirs of the running workers are never touched.
+    /// # Returns
+    /// Returns the number of removed dirs
+    #[instrument(skip(self))]
+    pub fn sweep(&self, max_age: Duration) -> usize {
+        let now = SystemTime::now();
+        let mut removed = 0;
+        for root in std::iter::once(&self.disk).chain(&self.tmpfs) {
+            for entry in read_dir(&root.instance_dir) {
+                if !entry.file_name().to_string_lossy().starts_with(WORK_DIR_PREFIX) {
+                    continue;
+                }
+                let is_stale = entry
+                    .metadata()
+                    .and_then(|metadata| metadata.modified())
+                    .is_ok_and(|modified| now.duration_since(modified).is_ok_and(|age| age > max_age));
+                if is_stale && remove_dir(&entry.path()) {
+                    removed += 1;
+                }
+            }
+            for entry in read_dir(&root.path) {
+                let path = entry.path();
+                if !entry.file_name().to_string_lossy().starts_with(INSTANCE_DIR_PREFIX) || path == root.instance_dir {
+                    continue;
+                }
+                // The lock is held until the dir is removed, so the owner can't be restarted in it meanwhile
+                let Ok(lock) = File::open(path.join(LOCK_NAME)) else {
+                    continue;
+                };
+                let Ok(_lock) = Flock::lock(lock, FlockArg::LockExclusiveNonblock) else {
+                    debug!(path = %path.display(), "Instance dir is used by another worker");
+                    continue;
+                };
+                if remove_dir(&path) {
+                    removed += 1;
+                }
+            }
+        }
+        if removed > 0 {
+            info!(removed, "Stale work dirs removed");
+            metrics::SCRATCH_SWEPT_DIRS.inc_by(removed as u64);
+        }
+        removed
+    }
+}
+
+fn observe_admission(root: &'static str, available: u64, admitted: bool) {
+    metrics::SCRATCH_AVAILABLE_BYTES
+        .with_label_values(&[root])
+        .set(i64::try_from(available).unwrap_or(i64::MAX));
+    metrics::SCRATCH_ADMISSIONS
+        .with_label_values(&[root, if admitted { "admitted" } else { "rejected" }])
+        .inc();
+}
+
+fn available_space(path: &Path) -> Result<u64, Errno> {
+    let stat = statvfs(path)?;
+    #[allow(clippy::useless_conversion)]
+    Ok(u64::from(stat.blocks_available()) * u64::from(stat.fragment_size()))
+}
+
+fn create_work_dir(instance_dir: &Path) -> Result<TempDir, Error> {
+    Builder::new().prefix(WORK_DIR_PREFIX).tempdir_in(instance_dir).map_err(Into::into)
+}
+
+fn read_dir(dir: &Path) -> impl Iterator<Item = fs::DirEntry> {
+    fs::read_dir(dir)
+        .inspect_err(|err| warn!(dir = %dir.display(), "Failed to read scratch dir: {err}"))
+        .into_iter()
+        .flatten()
+        .flatten()
+}
+
+fn remove_dir(path: &Path) -> bool {
+    fs::remove_dir_all(path)
+        .inspect_err(|err| warn!(path = %path.display(), "Failed to remove scratch dir: {err}"))
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_work_dir_admission() {
+        let root = TempDir::new().unwrap();
+        let scratch = Scratch::new(Some(root.path()), None, 0, 0).unwrap();
+
+        let work_dir = scratch.work_dir(Some(1024)).unwrap();
+        assert!(work_dir.path().starts_with(root.path()));
+        assert!(matches!(scratch.work_dir(Some(u64::MAX)), Err(Error::NoSpace { .. })));
+
+        assert_eq!(scratch.sweep(Duration::from_secs(3600)), 0);
+        assert_eq!(scratch.sweep(Duration::ZERO), 1);
+        assert!(!work_dir.path().exists());
+    }
+
+    #[test]
+    fn test_sweep_keeps_other_workers() {
+        let root = TempDir::new().unwrap();
+        let scratch = Scratch::new(Some(root.path()), None, 0, 0).unwrap();
+        let other = Scratch::new(Some(root.path()), None, 0, 0).unwrap();
+
+        let other_work_dir = other.work_dir(None).unwrap().keep();
+        assert_eq!(scratch.sweep(Duration::ZERO), 0);
+        assert!(other_work_dir.exists());
+
+        drop(other);
+        assert_eq!(scratch.sweep(Duration::ZERO), 1);
+        assert!(!other_work_dir.exists());
+        assert!(scratch.disk.instance_dir.exists());
+    }
+}
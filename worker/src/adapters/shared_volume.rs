@@ -0,0 +1,170 @@
+use std::{
+    fs::{self, Permissions},
+    io,
+    os::unix::fs::PermissionsExt as _,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tempfile::Builder;
+use tracing::{debug, info, instrument, warn};
+
+use crate::entities::Lease;
+
+/// Each delivered file is placed in its own lease dir, the dir name is the lease id
+const LEASE_PREFIX: &str = "lease-";
+/// Bot API server runs as another user, so the published files must be readable by everyone.
+/// Modes are set explicitly, the temp dirs are private and the umask may be strict.
+const LEASE_DIR_MODE: u32 = 0o755;
+const FILE_MODE: u32 = 0o644;
+
+/// Directory shared with the local Bot API server.
+/// Leases are tracked by the modification time of their dirs, so they survive restarts.
+#[derive(Debug, Clone)]
+pub struct SharedVolume {
+    dir: PathBuf,
+    public_dir: PathBuf,
+    ttl: Duration,
+}
+
+impl SharedVolume {
+    /// # Errors
+    /// Returns [`io::Error`] if the directory can't be created
+    pub fn new(dir: impl Into<PathBuf>, public_dir: Option<PathBuf>, ttl: Duration) -> Result<Self, io::Error> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            public_dir: public_dir.unwrap_or_else(|| dir.clone()),
+            dir,
+            ttl,
+        })
+    }
+
+    /// Move the file to a new lease dir, it's copied if the volume is on another filesystem
+    /// # Errors
+    /// Returns [`io::Error`] if the file can't be moved
+    #[instrument(skip_all, fields(path = %path.display()))]
+    pub async fn publish(&self, path: &Path) -> Result<Lease, io::Error> {
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?;
+        let lease_dir = Builder::new().prefix(LEASE_PREFIX).tempdir_in(&self.dir)?.keep();
+        let id = lease_dir.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let shared_path = lease_dir.join(file_name);
+
+        if let Err(err) = move_file(path, &shared_path, &lease_dir).await {
+            let _ = tokio::fs::remove_dir_all(&lease_dir).await;
+            return Err(err);
+        }
+
+        let expires_at = SystemTime::now()
+            .checked_add(self.ttl)
+            .and_then(|expires_at| expires_at.duration_since(UNIX_EPOCH).ok())
+            .map_or(i64::MAX, |expires_at| i64::try_from(expires_at.as_secs()).unwrap_or(i64::MAX));
+        debug!(%id, "File published");
+
+        Ok(Lease {
+            path: self.public_dir.join(&id).join(file_name),
+            id,
+            expires_at,
+        })
+    }
+
+    /// # Errors
+    /// Returns [`io::Error`] if the lease id isn't valid or the lease dir can't be removed
+    /// # Returns
+    /// Returns `false` if the lease is unknown or already expired
+    #[instrument(skip(self))]
+    pub async fn release(&self, id: &str) -> Result<bool, io::Error> {
+        if !id.starts_with(LEASE_PREFIX) || id.contains(['/', '\\']) || id.contains("..") {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid lease id"));
+        }
+
+        match tokio::fs::remove_dir_all(self.dir.join(id)).await {
+            Ok(()) => {
+                debug!("Lease released");
+                Ok(true)
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Remove the lease dirs older than the TTL
+    /// # Returns
+    /// Returns the number of removed dirs
+    #[instrument(skip(self))]
+    pub fn sweep_expired(&self) -> usize {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                warn!("Failed to read shared volume: {err}");
+                return 0;
+            }
+        };
+
+        let now = SystemTime::now();
+        let mut removed = 0;
+        for entry in entries.flatten() {
+            if !entry.file_name().to_string_lossy().starts_with(LEASE_PREFIX) {
+                continue;
+            }
+            let is_expired = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| now.duration_since(modified).is_ok_and(|age| age > self.ttl));
+            if !is_expired {
+                continue;
+            }
+            let path = entry.path();
+            match fs::remove_dir_all(&path) {
+                Ok(()) => removed += 1,
+                Err(err) => warn!(path = %path.display(), "Failed to remove expired lease: {err}"),
+            }
+        }
+        if removed > 0 {
+            info!(removed, "Expired leases removed");
+        }
+        removed
+    }
+}
+
+async fn move_file(path: &Path, shared_path: &Path, lease_dir: &Path) -> Result<(), io::Error> {
+    if tokio::fs::rename(path, shared_path).await.is_err() {
+        tokio::fs::copy(path, shared_path).await?;
+        let _ = tokio::fs::remove_file(path).await;
+    }
+    tokio::fs::set_permissions(shared_path, Permissions::from_mode(FILE_MODE)).await?;
+    tokio::fs::set_permissions(lease_dir, Permissions::from_mode(LEASE_DIR_MODE)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_publish_and_release() {
+        let volume_dir = TempDir::new().unwrap();
+        let source_dir = TempDir::new().unwrap();
+        let volume = SharedVolume::new(volume_dir.path(), Some(PathBuf::from("/shared")), Duration::from_secs(60)).unwrap();
+
+        let source_path = source_dir.path().join("video.mp4");
+        tokio::fs::write(&source_path, b"content").await.unwrap();
+        fs::set_permissions(&source_path, Permissions::from_mode(0o600)).unwrap();
+        let lease = volume.publish(&source_path).await.unwrap();
+
+        assert!(!source_path.exists());
+        assert_eq!(lease.path, Path::new("/shared").join(&lease.id).join("video.mp4"));
+        assert!(volume_dir.path().join(&lease.id).join("video.mp4").exists());
+        assert_eq!(volume.sweep_expired(), 0);
+
+        let mode = |path: PathBuf| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(volume_dir.path().join(&lease.id)), LEASE_DIR_MODE);
+        assert_eq!(mode(volume_dir.path().join(&lease.id).join("video.mp4")), FILE_MODE);
+
+        assert!(volume.release(&lease.id).await.unwrap());
+        assert!(!volume.release(&lease.id).await.unwrap());
+        assert!(volume.release("../etc").await.is_err());
+    }
+}
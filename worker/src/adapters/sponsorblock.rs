@@ -0,0 +1,49 @@
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::time::Duration;
+use tracing::{debug, instrument};
+use url::Url;
+
+use crate::value_objects::SponsorBlockCategory;
+
+const REQUEST_TIMEOUT: u64 = 10;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("URL parse error: {0}")]
+    Url(#[from] url::ParseError),
+    #[error("Request error: {0}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Segment {
+    /// Start and end in seconds
+    pub segment: (f64, f64),
+    pub category: String,
+}
+
+/// Get the segments of the categories submitted for the video.
+/// The API responds with `404` if there are none.
+/// # Errors
+/// Returns [`Error`] if the request fails or the response is invalid
+#[instrument(skip_all, fields(%video_id))]
+pub async fn skip_segments(api_url: &str, video_id: &str, categories: &[SponsorBlockCategory]) -> Result<Vec<Segment>, Error> {
+    let categories = serde_json::to_string(&categories.iter().map(|category| category.as_str()).collect::<Vec<_>>())?;
+    let mut url = Url::parse(api_url)?.join("api/skipSegments")?;
+    url.query_pairs_mut()
+        .append_pair("videoID", video_id)
+        .append_pair("categories", &categories);
+
+    let response = Client::new().get(url).timeout(Duration::from_secs(REQUEST_TIMEOUT)).send().await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        debug!("No segments");
+        return Ok(vec![]);
+    }
+
+    let segments: Vec<Segment> = serde_json::from_slice(&response.error_for_status()?.bytes().await?)?;
+    debug!(count = segments.len(), "Segments received");
+    Ok(segments)
+}
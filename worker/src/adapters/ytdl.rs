@@ -1,13 +1,23 @@
-use crate::entities::Cookie;
+use crate::{
+    adapters::{
+        backend::{DownloadRequest, StreamRequest},
+        metrics::ProcessTimer,
+    },
+    config,
+    entities::{Cookie, SponsorBlock},
+    value_objects::Container,
+};
 
 use std::{
     io,
     os::fd::OwnedFd,
     path::Path,
-    process::{Child, Command, Output, Stdio},
+    process::{Output, Stdio},
     time::Duration,
 };
+use tokio::process::{Child, Command};
 use tracing::{Level, event, instrument};
+use url::Url;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -17,135 +27,364 @@ pub enum Error {
     Json(#[from] serde_json::Error),
 }
 
+/// Gallery item index, the same as the file stem of the item
+const GALLERY_MANIFEST_TEMPLATE: &str = "%(playlist_index|1)03d";
+/// Name of the gallery manifest in the output dir, one item index per line
+pub const GALLERY_MANIFEST_NAME: &str = "manifest";
+
+/// Player clients of the audio downloads if the extractor of the domain doesn't set them
+const AUDIO_PLAYER: Player = Player {
+    extractor: "youtube",
+    clients: &["default", "mweb", "web_music", "web_creator"],
+    skip: &["configs", "initial_data"],
+};
+
+struct Player {
+    extractor: &'static str,
+    clients: &'static [&'static str],
+    skip: &'static [&'static str],
+}
+
+/// Builder of the `yt-dlp` argv.
+/// Options of the operation are kept in the call order,
+/// the overrides of the URL domain from [`config::YtDlp::extractors`] are merged in [`Args::build`].
+pub struct Args<'a> {
+    cfg: &'a config::YtDlp,
+    url: &'a str,
+    args: Vec<String>,
+    extractor_args: Vec<String>,
+    player: Option<&'static Player>,
+}
+
+impl<'a> Args<'a> {
+    #[must_use]
+    pub fn new(cfg: &'a config::YtDlp, url: &'a str) -> Self {
+        Self {
+            cfg,
+            url,
+            args: vec![],
+            extractor_args: vec![],
+            player: None,
+        }
+    }
+
+    #[must_use]
+    pub fn flags(mut self, flags: &[&str]) -> Self {
+        self.args.extend(flags.iter().map(|&flag| flag.to_owned()));
+        self
+    }
+
+    #[must_use]
+    pub fn option(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.args.push(name.to_owned());
+        self.args.push(value.into());
+        self
+    }
+
+    #[must_use]
+    pub fn extend(mut self, args: &[String]) -> Self {
+        self.args.extend_from_slice(args);
+        self
+    }
+
+    #[must_use]
+    pub fn output_to_dir(self, dir: &Path, template: &str) -> Self {
+        self.option("--paths", dir.to_string_lossy()).option("--output", template)
+    }
+
+    #[must_use]
+    pub fn max_filesize(self, max_filesize: u32) -> Self {
+        self.option("--max-filesize", max_filesize.to_string())
+    }
+
+    #[must_use]
+    pub fn format(self, format_id: &str) -> Self {
+        self.option("-f", format_id)
+    }
+
+    #[must_use]
+    pub fn pot_provider(mut self, api_url: &str) -> Self {
+        self.extractor_args.push(format!("youtubepot-bgutilhttp:base_url={api_url}"));
+        self
+    }
+
+    #[must_use]
+    fn player(mut self, player: &'static Player) -> Self {
+        self.player = Some(player);
+        self
+    }
+
+    #[must_use]
+    pub fn cookie(self, cookie: Option<&Cookie>) -> Self {
+        match cookie {
+            Some(cookie) => {
+                let cookie_path = cookie.path.to_string_lossy();
+                event!(Level::TRACE, "Using cookies from: {}", cookie_path);
+
+                self.option("--cookies", cookie_path)
+            }
+            None => {
+                event!(Level::TRACE, "No cookies provided");
+                self
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn build(self) -> Vec<String> {
+        let extractor = Url::parse(self.url)
+            .ok()
+            .and_then(|url| url.host_str().and_then(|host| self.cfg.extractor(host)));
+        let js_runtime = extractor
+            .and_then(|(_, extractor)| extractor.js_runtime.as_deref())
+            .unwrap_or(&self.cfg.js_runtime);
+
+        let mut args: Vec<String> = [
+            "--js-runtimes",
+            js_runtime,
+            "--no-update",
+            "--ignore-config",
+            "--no-colors",
+            "--socket-timeout",
+            "5",
+        ]
+        .into_iter()
+        .map(str::to_owned)
+        .collect();
+        args.extend(self.args);
+
+        let mut extractor_args = self.extractor_args;
+        match extractor {
+            Some((
+                _,
+                config::Extractor {
+                    name: Some(name),
+                    player_clients,
+                    player_skip,
+                    ..
+                },
+            )) if !player_clients.is_empty() || !player_skip.is_empty() => {
+                extractor_args.push(player_arg(name, player_clients, player_skip));
+            }
+            _ => {
+                if let Some(player) = self.player {
+                    extractor_args.push(player_arg(player.extractor, player.clients, player.skip));
+                }
+            }
+        }
+        if let Some((_, extractor)) = extractor {
+            extractor_args.extend(extractor.extractor_args.iter().map(ToString::to_string));
+        }
+        for extractor_arg in extractor_args {
+            args.push("--extractor-args".to_owned());
+            args.push(extractor_arg);
+        }
+
+        if let Some((_, extractor)) = extractor {
+            if !extractor.format_sort.is_empty() {
+                args.push("--format-sort".to_owned());
+                args.push(extractor.format_sort.join(","));
+            }
+            args.extend(extractor.extra_args.iter().map(ToString::to_string));
+        }
+
+        args.push("--".to_owned());
+        args.push(self.url.to_owned());
+        args
+    }
+}
+
+fn player_arg(extractor: &str, clients: &[impl AsRef<str>], skip: &[impl AsRef<str>]) -> String {
+    let mut options = vec![];
+    if !clients.is_empty() {
+        options.push(format!("player_client={}", join(clients)));
+    }
+    if !skip.is_empty() {
+        options.push(format!("player_skip={}", join(skip)));
+    }
+    format!("{extractor}:{}", options.join(";"))
+}
+
+fn join(values: &[impl AsRef<str>]) -> String {
+    values.iter().map(AsRef::as_ref).collect::<Vec<_>>().join(",")
+}
+
+/// Arguments to download the format to stdout
+#[must_use]
+pub fn pipe_args(request: &StreamRequest) -> Vec<String> {
+    Args::new(request.yt_dlp_cfg, request.url)
+        .option("--output", "-")
+        .flags(&[
+            "--no-playlist",
+            "--no-mtime",
+            "--no-write-comments",
+            "--quiet",
+            "--no-simulate",
+            "--no-progress",
+            "--no-check-formats",
+        ])
+        .max_filesize(request.max_file_size)
+        .format(request.format_id)
+        .pot_provider(request.pot_provider_api_url)
+        .cookie(request.cookie)
+        .build()
+}
+
+/// Arguments to record the live stream to stdout
+#[must_use]
+pub fn live_args(
+    cfg: &config::YtDlp,
+    pot_provider_api_url: &str,
+    url: &str,
+    format_id: &str,
+    live_from_start: bool,
+    cookie: Option<&Cookie>,
+) -> Vec<String> {
+    let mut args = Args::new(cfg, url)
+        .option("--output", "-")
+        .flags(&[
+            "--no-playlist",
+            "--no-part",
+            "--quiet",
+            "--no-simulate",
+            "--no-progress",
+            "--no-check-formats",
+        ])
+        .format(format_id)
+        .pot_provider(pot_provider_api_url);
+    if live_from_start {
+        args = args.flags(&["--live-from-start"]);
+    }
+    args.cookie(cookie).build()
+}
+
+/// Arguments to download the format to `<id>.<extension>` in the output dir
+#[must_use]
+pub fn video_args(request: &DownloadRequest) -> Vec<String> {
+    Args::new(request.yt_dlp_cfg, request.url)
+        .output_to_dir(request.output_dir, "%(id)s.%(ext)s")
+        .flags(&[
+            "--no-playlist",
+            "--no-mtime",
+            "--no-write-comments",
+            "--quiet",
+            "--no-simulate",
+            "--no-progress",
+            "--no-check-formats",
+            "--embed-metadata",
+        ])
+        .option("--concurrent-fragments", "4")
+        .max_filesize(request.max_file_size)
+        .format(request.format_id)
+        .option("--merge-output-format", request.extension)
+        .pot_provider(request.pot_provider_api_url)
+        .cookie(request.cookie)
+        .extend(request.sponsorblock_args)
+        .build()
+}
+
+/// Arguments to download the format and extract the audio to `<id>.<extension>` in the output dir
+#[must_use]
+pub fn audio_args(request: &DownloadRequest) -> Vec<String> {
+    Args::new(request.yt_dlp_cfg, request.url)
+        .output_to_dir(request.output_dir, "%(id)s.%(ext)s")
+        .flags(&["--extract-audio"])
+        .option(
+            "--audio-format",
+            Container::from_extension(request.extension)
+                .ytdlp_audio_format()
+                .unwrap_or(request.extension),
+        )
+        .flags(&[
+            "--no-playlist",
+            "--no-mtime",
+            "--no-write-comments",
+            "--quiet",
+            "--no-simulate",
+            "--no-progress",
+            "--no-check-formats",
+            "--embed-metadata",
+            "--embed-chapters",
+        ])
+        .option("--concurrent-fragments", "4")
+        .max_filesize(request.max_file_size)
+        .format(request.format_id)
+        .pot_provider(request.pot_provider_api_url)
+        .player(&AUDIO_PLAYER)
+        .cookie(request.cookie)
+        .extend(request.sponsorblock_args)
+        .build()
+}
+
+/// Arguments to download every item of the post (carousel, gallery or slideshow) to the output dir.
+/// Files are named by the item index, so they keep the original order.
+/// Indexes of the items that reached the download are written to [`GALLERY_MANIFEST_NAME`],
+/// so the items dropped by `--max-filesize` or a failed download can be reported.
+#[must_use]
+pub fn gallery_args(
+    cfg: &config::YtDlp,
+    pot_provider_api_url: &str,
+    url: &str,
+    output_dir: &Path,
+    max_filesize: u32,
+    max_items: u32,
+    cookie: Option<&Cookie>,
+) -> Vec<String> {
+    Args::new(cfg, url)
+        .output_to_dir(output_dir, &format!("{GALLERY_MANIFEST_TEMPLATE}.%(ext)s"))
+        .flags(&["--yes-playlist"])
+        .option("--playlist-items", format!("1:{max_items}"))
+        // Failed items are reported as skipped instead of failing the gallery
+        .flags(&[
+            "--ignore-errors",
+            "--no-mtime",
+            "--no-write-comments",
+            "--quiet",
+            "--no-simulate",
+            "--no-progress",
+            "--no-check-formats",
+        ])
+        .max_filesize(max_filesize)
+        .format("bv*+ba/b")
+        .option("--merge-output-format", "mp4")
+        .flags(&["--print-to-file", GALLERY_MANIFEST_TEMPLATE, &output_dir.join(GALLERY_MANIFEST_NAME).to_string_lossy()])
+        .pot_provider(pot_provider_api_url)
+        .cookie(cookie)
+        .build()
+}
+
 /// Download stream to a pipe.
 /// This function forks a child process and executes `yt-dl` in it.
 /// The child process redirects its stdout to the pipe.
+/// Live recordings run until the stream ends or the child is killed, so the reader must bound them.
+/// The child is killed when it's dropped, so it must be awaited to run in the background.
 /// # Errors
 /// Returns [`io::Error`] if the spawn child process fails
 /// # Returns
 /// Returns the child process
 #[instrument(skip_all)]
-pub fn download_to_pipe(
-    fd: OwnedFd,
-    executable_path: impl AsRef<str>,
-    url: impl AsRef<str>,
-    pot_provider_api_url: impl AsRef<str>,
-    format: impl AsRef<str>,
-    max_filesize: u32,
-    cookie: Option<&Cookie>,
-) -> Result<Child, io::Error> {
-    let max_filesize_str = max_filesize.to_string();
-
-    let mut args = vec![
-        "--js-runtimes",
-        "deno:deno",
-        "--ignore-config",
-        "--no-colors",
-        "--socket-timeout",
-        "5",
-        "--output",
-        "-",
-        "--no-playlist",
-        "--no-mtime",
-        "--no-write-comments",
-        "--quiet",
-        "--no-simulate",
-        "--no-progress",
-        "--no-check-formats",
-        "--max-filesize",
-        max_filesize_str.as_ref(),
-        "-f",
-        format.as_ref(),
-    ];
-
-    let extractor_arg = format!("youtubepot-bgutilhttp:base_url={}", pot_provider_api_url.as_ref());
-    args.push("--extractor-args");
-    args.push(&extractor_arg);
-
-    let cookie_path = cookie.map(|c| c.path.to_string_lossy());
-    if let Some(cookie_path) = cookie_path.as_deref() {
-        event!(Level::TRACE, "Using cookies from: {}", cookie_path);
-
-        args.push("--cookies");
-        args.push(cookie_path);
-    } else {
-        event!(Level::TRACE, "No cookies provided");
-    }
-
-    args.push("--");
-    args.push(url.as_ref());
-
+pub fn download_to_pipe(fd: OwnedFd, executable_path: impl AsRef<str>, args: &[String]) -> Result<Child, io::Error> {
     Command::new(executable_path.as_ref())
         .args(args)
         .stdin(Stdio::null())
         .stdout(Stdio::from(fd))
         .stderr(Stdio::inherit())
+        .kill_on_drop(true)
         .spawn()
 }
 
-#[instrument(skip_all)]
-pub async fn download_video_to_path(
+/// Run `yt-dlp` that writes the output files itself, `operation` labels the process metrics
+/// # Errors
+/// Returns [`io::Error`] if the process fails, exits with an error or times out
+#[instrument(skip_all, fields(operation))]
+pub async fn download_to_path(
     executable_path: impl AsRef<str>,
-    url: impl AsRef<str>,
-    pot_provider_api_url: impl AsRef<str>,
-    format_id: impl AsRef<str>,
-    output_extension: impl AsRef<str>,
-    output_dir_path: impl AsRef<Path>,
+    args: &[String],
     timeout: u64,
-    max_filesize: u32,
-    cookie: Option<&Cookie>,
+    operation: &'static str,
 ) -> Result<(), io::Error> {
-    let output_dir_path = output_dir_path.as_ref().to_string_lossy();
-    let max_filesize_str = max_filesize.to_string();
-
-    let mut args = vec![
-        "--js-runtimes",
-        "deno:deno",
-        "--no-update",
-        "--ignore-config",
-        "--no-colors",
-        "--socket-timeout",
-        "5",
-        "--paths",
-        output_dir_path.as_ref(),
-        "--output",
-        "%(id)s.%(ext)s",
-        "--no-playlist",
-        "--no-mtime",
-        "--no-write-comments",
-        "--quiet",
-        "--no-simulate",
-        "--no-progress",
-        "--no-check-formats",
-        "--embed-metadata",
-        "--concurrent-fragments",
-        "4",
-        "--max-filesize",
-        max_filesize_str.as_ref(),
-        "-f",
-        format_id.as_ref(),
-        "--merge-output-format",
-        output_extension.as_ref(),
-    ];
-
-    let extractor_arg = format!("youtubepot-bgutilhttp:base_url={}", pot_provider_api_url.as_ref());
-    args.push("--extractor-args");
-    args.push(&extractor_arg);
-
-    let cookie_path = cookie.map(|c| c.path.to_string_lossy());
-    if let Some(cookie_path) = cookie_path.as_deref() {
-        event!(Level::TRACE, "Using cookies from: {}", cookie_path);
-
-        args.push("--cookies");
-        args.push(cookie_path);
-    } else {
-        event!(Level::TRACE, "No cookies provided");
-    }
-
-    args.push("--");
-    args.push(url.as_ref());
-
-    let child = tokio::process::Command::new(executable_path.as_ref())
+    let timer = ProcessTimer::start("yt_dlp", operation);
+    let child = Command::new(executable_path.as_ref())
         .args(args)
         .stdin(Stdio::null())
         .stdout(Stdio::inherit())
@@ -153,119 +392,346 @@ pub async fn download_video_to_path(
         .kill_on_drop(true)
         .spawn()?;
 
-    match tokio::time::timeout(Duration::from_secs(timeout), child.wait_with_output()).await {
-        Ok(Ok(Output { status, stderr, .. })) => {
-            if status.success() {
-                Ok(())
-            } else {
-                match status.code() {
-                    Some(code) => Err(io::Error::other(format!(
-                        "Youtube-dl exited with code {code} and message: {}",
-                        String::from_utf8_lossy(&stderr),
-                    ))),
-                    None => Err(io::Error::other(format!(
-                        "Youtube-dl exited with and message: {}",
-                        String::from_utf8_lossy(&stderr),
-                    ))),
-                }
-            }
-        }
+    let res = tokio::time::timeout(Duration::from_secs(timeout), child.wait_with_output()).await;
+    if let Ok(Ok(output)) = &res {
+        timer.record_exit(output.status);
+    }
+    match res {
+        Ok(Ok(Output { status, .. })) if status.success() => Ok(()),
+        Ok(Ok(Output { status, stderr, .. })) => Err(io::Error::other(format!(
+            "Youtube-dl exited with status `{status}` and message: {}",
+            String::from_utf8_lossy(&stderr),
+        ))),
         Ok(Err(err)) => Err(err),
         Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "Youtube-dl timed out")),
     }
 }
 
-#[instrument(skip_all)]
-pub async fn download_audio_to_path(
-    executable_path: impl AsRef<str>,
-    url: impl AsRef<str>,
-    pot_provider_api_url: impl AsRef<str>,
-    format_id: impl AsRef<str>,
-    output_extension: impl AsRef<str>,
-    output_dir_path: impl AsRef<Path>,
-    timeout: u64,
-    max_filesize: u32,
-    cookie: Option<&Cookie>,
-) -> Result<(), io::Error> {
-    let output_dir_path = output_dir_path.as_ref().to_string_lossy();
-    let max_filesize_str = max_filesize.to_string();
-
-    let mut args = vec![
-        "--js-runtimes",
-        "deno:deno",
-        "--no-update",
-        "--ignore-config",
-        "--no-color",
-        "--socket-timeout",
-        "5",
-        "--paths",
-        output_dir_path.as_ref(),
-        "--output",
-        "%(id)s.%(ext)s",
-        "--extract-audio",
-        "--audio-format",
-        output_extension.as_ref(),
-        "--no-playlist",
-        "--no-mtime",
-        "--no-write-comments",
-        "--quiet",
-        "--no-simulate",
-        "--no-progress",
-        "--no-check-formats",
-        "--embed-metadata",
-        "--concurrent-fragments",
-        "4",
-        "--max-filesize",
-        max_filesize_str.as_ref(),
-        "-f",
-        format_id.as_ref(),
-    ];
-
-    let extractor_arg = format!("youtubepot-bgutilhttp:base_url={}", pot_provider_api_url.as_ref());
-    args.push("--extractor-args");
-    args.push(&extractor_arg);
-    args.push("--extractor-args");
-    args.push("youtube:player_client=default,mweb,web_music,web_creator;player_skip=configs,initial_data");
-
-    let cookie_path = cookie.map(|c| c.path.to_string_lossy());
-    if let Some(cookie_path) = cookie_path.as_deref() {
-        event!(Level::TRACE, "Using cookies from: {}", cookie_path);
-
-        args.push("--cookies");
-        args.push(cookie_path);
-    } else {
-        event!(Level::TRACE, "No cookies provided");
+/// Whether the `yt-dlp` error means the host throttles the requests
+#[must_use]
+pub fn is_rate_limited(err: &io::Error) -> bool {
+    let message = err.to_string();
+    ["HTTP Error 429", "HTTP Error 403", "rate-limited", "rate limited"]
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+/// Arguments to remove and mark the `SponsorBlock` segments with the `yt-dlp` postprocessors
+#[must_use]
+pub fn sponsorblock_args(sponsorblock: &SponsorBlock, api_url: &str) -> Vec<String> {
+    if sponsorblock.is_empty() {
+        return vec![];
     }
 
-    args.push("--");
-    args.push(url.as_ref());
+    let mut args = vec!["--sponsorblock-api".to_owned(), api_url.to_owned()];
+    for (flag, categories) in [
+        ("--sponsorblock-remove", &sponsorblock.remove),
+        ("--sponsorblock-mark", &sponsorblock.mark),
+    ] {
+        if !categories.is_empty() {
+            args.push(flag.to_owned());
+            args.push(categories.iter().map(|category| category.as_str()).collect::<Vec<_>>().join(","));
+        }
+    }
+    args
+}
 
-    let child = tokio::process::Command::new(executable_path.as_ref())
-        .args(args)
-        .stdin(Stdio::null())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::piped())
-        .kill_on_drop(true)
-        .spawn()?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
 
-    match tokio::time::timeout(Duration::from_secs(timeout), child.wait_with_output()).await {
-        Ok(Ok(Output { status, stderr, .. })) => {
-            if status.success() {
-                Ok(())
-            } else {
-                match status.code() {
-                    Some(code) => Err(io::Error::other(format!(
-                        "Youtube-dl exited with code {code} and message: {}",
-                        String::from_utf8_lossy(&stderr),
-                    ))),
-                    None => Err(io::Error::other(format!(
-                        "Youtube-dl exited with and message: {}",
-                        String::from_utf8_lossy(&stderr),
-                    ))),
-                }
-            }
+    fn cfg(extractors: impl IntoIterator<Item = (&'static str, config::Extractor)>) -> config::YtDlp {
+        config::YtDlp {
+            executable_path: "yt-dlp".into(),
+            js_runtime: "deno:deno".into(),
+            extractors: extractors
+                .into_iter()
+                .map(|(domain, extractor)| (domain.into(), extractor))
+                .collect(),
         }
-        Ok(Err(err)) => Err(err),
-        Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "Youtube-dl timed out")),
+    }
+
+    fn request<'a>(cfg: &'a config::YtDlp, url: &'a str, sponsorblock_args: &'a [String]) -> DownloadRequest<'a> {
+        DownloadRequest {
+            yt_dlp_cfg: cfg,
+            pot_provider_api_url: "http://pot:4416",
+            url,
+            media_id: "id",
+            format_id: "140",
+            extension: "m4a",
+            output_dir: Path::new("/tmp/worker"),
+            timeout: 60,
+            max_file_size: 1000,
+            cookie: None,
+            sponsorblock_args,
+        }
+    }
+
+    #[test]
+    fn test_audio_args() {
+        let sponsorblock_args = vec!["--sponsorblock-remove".to_owned(), "sponsor".to_owned()];
+        let cfg = cfg([]);
+        let args = audio_args(&request(&cfg, "https://www.youtube.com/watch?v=id", &sponsorblock_args));
+
+        assert_eq!(
+            args,
+            [
+                "--js-runtimes",
+                "deno:deno",
+                "--no-update",
+                "--ignore-config",
+                "--no-colors",
+                "--socket-timeout",
+                "5",
+                "--paths",
+                "/tmp/worker",
+                "--output",
+                "%(id)s.%(ext)s",
+                "--extract-audio",
+                "--audio-format",
+                "m4a",
+                "--no-playlist",
+                "--no-mtime",
+                "--no-write-comments",
+                "--quiet",
+                "--no-simulate",
+                "--no-progress",
+                "--no-check-formats",
+                "--embed-metadata",
+                "--embed-chapters",
+                "--concurrent-fragments",
+                "4",
+                "--max-filesize",
+                "1000",
+                "-f",
+                "140",
+                "--sponsorblock-remove",
+                "sponsor",
+                "--extractor-args",
+                "youtubepot-bgutilhttp:base_url=http://pot:4416",
+                "--extractor-args",
+                "youtube:player_client=default,mweb,web_music,web_creator;player_skip=configs,initial_data",
+                "--",
+                "https://www.youtube.com/watch?v=id",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_video_args_with_overrides() {
+        let cfg = cfg([(
+            "youtube.com",
+            config::Extractor {
+                name: Some("youtube".into()),
+                js_runtime: Some("node".into()),
+                player_clients: vec!["tv".into()],
+                extractor_args: vec!["youtube:lang=en".into()],
+                format_sort: vec!["res:1080".into(), "vcodec:h264".into()],
+                extra_args: vec!["--geo-bypass".into()],
+                ..Default::default()
+            },
+        )]);
+        let args = video_args(&request(&cfg, "https://m.youtube.com/watch?v=id", &[]));
+
+        assert_eq!(
+            args,
+            [
+                "--js-runtimes",
+                "node",
+                "--no-update",
+                "--ignore-config",
+                "--no-colors",
+                "--socket-timeout",
+                "5",
+                "--paths",
+                "/tmp/worker",
+                "--output",
+                "%(id)s.%(ext)s",
+                "--no-playlist",
+                "--no-mtime",
+                "--no-write-comments",
+                "--quiet",
+                "--no-simulate",
+                "--no-progress",
+                "--no-check-formats",
+                "--embed-metadata",
+                "--concurrent-fragments",
+                "4",
+                "--max-filesize",
+                "1000",
+                "-f",
+                "140",
+                "--merge-output-format",
+                "m4a",
+                "--extractor-args",
+                "youtubepot-bgutilhttp:base_url=http://pot:4416",
+                "--extractor-args",
+                "youtube:player_client=tv",
+                "--extractor-args",
+                "youtube:lang=en",
+                "--format-sort",
+                "res:1080,vcodec:h264",
+                "--geo-bypass",
+                "--",
+                "https://m.youtube.com/watch?v=id",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pipe_args_ignore_other_domains() {
+        let cfg = cfg([(
+            "vimeo.com",
+            config::Extractor {
+                extra_args: vec!["--geo-bypass".into()],
+                ..Default::default()
+            },
+        )]);
+        let cookie = Cookie {
+            host: url::Host::Domain("youtube.com".to_owned()),
+            path: "/cookies/youtube.txt".into(),
+        };
+        let request = StreamRequest {
+            yt_dlp_cfg: &cfg,
+            pot_provider_api_url: "http://pot:4416",
+            url: "https://youtu.be/id",
+            format_id: "137",
+            format_url: "https://cdn.example.com/137",
+            filesize: None,
+            max_file_size: 1000,
+            cookie: Some(&cookie),
+        };
+
+        assert_eq!(
+            pipe_args(&request),
+            [
+                "--js-runtimes",
+                "deno:deno",
+                "--no-update",
+                "--ignore-config",
+                "--no-colors",
+                "--socket-timeout",
+                "5",
+                "--output",
+                "-",
+                "--no-playlist",
+                "--no-mtime",
+                "--no-write-comments",
+                "--quiet",
+                "--no-simulate",
+                "--no-progress",
+                "--no-check-formats",
+                "--max-filesize",
+                "1000",
+                "-f",
+                "137",
+                "--cookies",
+                "/cookies/youtube.txt",
+                "--extractor-args",
+                "youtubepot-bgutilhttp:base_url=http://pot:4416",
+                "--",
+                "https://youtu.be/id",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_live_and_gallery_args() {
+        let cfg = cfg([]);
+
+        assert_eq!(
+            live_args(&cfg, "http://pot:4416", "https://www.twitch.tv/id", "best", true, None),
+            [
+                "--js-runtimes",
+                "deno:deno",
+                "--no-update",
+                "--ignore-config",
+                "--no-colors",
+                "--socket-timeout",
+                "5",
+                "--output",
+                "-",
+                "--no-playlist",
+                "--no-part",
+                "--quiet",
+                "--no-simulate",
+                "--no-progress",
+                "--no-check-formats",
+                "-f",
+                "best",
+                "--live-from-start",
+                "--extractor-args",
+                "youtubepot-bgutilhttp:base_url=http://pot:4416",
+                "--",
+                "https://www.twitch.tv/id",
+            ]
+        );
+        assert_eq!(
+            gallery_args(
+                &cfg,
+                "http://pot:4416",
+                "https://www.instagram.com/p/id",
+                Path::new("/tmp/worker"),
+                1000,
+                10,
+                None
+            ),
+            [
+                "--js-runtimes",
+                "deno:deno",
+                "--no-update",
+                "--ignore-config",
+                "--no-colors",
+                "--socket-timeout",
+                "5",
+                "--paths",
+                "/tmp/worker",
+                "--output",
+                "%(playlist_index|1)03d.%(ext)s",
+                "--yes-playlist",
+                "--playlist-items",
+                "1:10",
+                "--ignore-errors",
+                "--no-mtime",
+                "--no-write-comments",
+                "--quiet",
+                "--no-simulate",
+                "--no-progress",
+                "--no-check-formats",
+                "--max-filesize",
+                "1000",
+                "-f",
+                "bv*+ba/b",
+                "--merge-output-format",
+                "mp4",
+                "--print-to-file",
+                "%(playlist_index|1)03d",
+                "/tmp/worker/manifest",
+                "--extractor-args",
+                "youtubepot-bgutilhttp:base_url=http://pot:4416",
+                "--",
+                "https://www.instagram.com/p/id",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extractor_prefers_most_specific_domain() {
+        let named = |name: &str| config::Extractor {
+            name: Some(name.into()),
+            ..Default::default()
+        };
+        let cfg = config::YtDlp {
+            extractors: HashMap::from([
+                ("youtube.com".into(), named("youtube")),
+                ("music.youtube.com".into(), named("music")),
+            ]),
+            ..cfg([])
+        };
+
+        assert_eq!(cfg.extractor("music.youtube.com").unwrap().0, "music.youtube.com");
+        assert_eq!(cfg.extractor("www.youtube.com").unwrap().0, "youtube.com");
+        assert!(cfg.extractor("notyoutube.com").is_none());
     }
 }
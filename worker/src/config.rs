@@ -1,10 +1,14 @@
+use arc_swap::ArcSwap;
 use serde::Deserialize;
 use std::{
+    collections::HashMap,
     env::{self, VarError},
     fmt::Display,
     fs,
     path::Path,
+    sync::Arc,
 };
+use url::Url;
 
 #[derive(Clone, Debug)]
 pub struct Version {
@@ -35,6 +39,33 @@ impl Display for Version {
 pub struct Server {
     pub host: Box<str>,
     pub port: u16,
+    /// Plaintext is used if the section is missing
+    #[serde(default)]
+    pub tls: Option<Tls>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Tls {
+    /// PEM files of the server certificate chain and its key
+    pub cert: Box<str>,
+    pub key: Box<str>,
+    /// PEM file of the CA that signs the client certificates, enables mutual TLS
+    #[serde(default)]
+    pub client_ca: Option<Box<str>>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct AuthClient {
+    /// Identity of the client in the logs
+    pub name: Box<str>,
+    pub token: Box<str>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct Auth {
+    /// Token authentication is disabled if there are no clients
+    #[serde(default)]
+    pub clients: Vec<AuthClient>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -45,11 +76,67 @@ pub struct Logging {
 #[derive(Deserialize, Clone, Debug)]
 pub struct Limits {
     pub max_file_size: u32,
+    /// Max size of the source file that can be split into parts of `max_file_size`
+    #[serde(default)]
+    pub max_split_file_size: Option<u32>,
+}
+
+impl Limits {
+    #[inline]
+    #[must_use]
+    pub fn max_source_file_size(&self, split: bool) -> u32 {
+        if split {
+            self.max_split_file_size.unwrap_or(self.max_file_size)
+        } else {
+            self.max_file_size
+        }
+    }
 }
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct YtDlp {
     pub executable_path: Box<str>,
+    /// Value of `--js-runtimes` for the extractors that run JS
+    #[serde(default = "YtDlp::default_js_runtime")]
+    pub js_runtime: Box<str>,
+    /// Overrides by the domain of the media URL, subdomains are matched too
+    #[serde(default)]
+    pub extractors: HashMap<Box<str>, Extractor>,
+}
+
+impl YtDlp {
+    fn default_js_runtime() -> Box<str> {
+        "deno:deno".into()
+    }
+
+    /// Overrides of the most specific domain the host matches
+    #[must_use]
+    pub fn extractor(&self, host: &str) -> Option<(&str, &Extractor)> {
+        let host = host.trim_end_matches('.');
+        self.extractors
+            .iter()
+            .filter(|(domain, _)| host == domain.as_ref() || host.ends_with(&format!(".{domain}")))
+            .max_by_key(|(domain, _)| domain.len())
+            .map(|(domain, extractor)| (domain.as_ref(), extractor))
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Extractor {
+    /// Name of the `yt-dlp` extractor the player options are passed to, required with them
+    pub name: Option<Box<str>>,
+    /// Replaces the default JS runtime
+    pub js_runtime: Option<Box<str>>,
+    /// Replace the built-in player clients and skipped requests
+    pub player_clients: Vec<Box<str>>,
+    pub player_skip: Vec<Box<str>>,
+    /// Values of `--extractor-args` in the `<extractor>:<key>=<value>` form
+    pub extractor_args: Vec<Box<str>>,
+    /// Fields of `--format-sort`
+    pub format_sort: Vec<Box<str>>,
+    /// Passed before the URL as is
+    pub extra_args: Vec<Box<str>>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -57,6 +144,150 @@ pub struct YtPotProvider {
     pub url: Box<str>,
 }
 
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Animation {
+    /// Max duration in seconds, longer sources are cut
+    pub max_duration: u32,
+    /// Max width and height, larger sources are scaled down keeping the aspect ratio
+    pub max_side: u32,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self {
+            max_duration: 60,
+            max_side: 720,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SponsorBlock {
+    /// Base URL of the API, can be replaced with a local mock
+    pub api_url: Box<str>,
+}
+
+impl Default for SponsorBlock {
+    fn default() -> Self {
+        Self {
+            api_url: "https://sponsor.ajay.app".into(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Live {
+    /// Max recording duration in seconds, requested durations are capped by it
+    pub max_duration: u32,
+}
+
+impl Default for Live {
+    fn default() -> Self {
+        Self { max_duration: 1800 }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Cache {
+    pub dir: Box<str>,
+    /// Budget in bytes, the least recently used media are evicted when it's exceeded
+    pub max_size: u64,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Scratch {
+    /// Root of the work dirs, the system temp dir is used if it's not set.
+    /// Each worker uses its own subdir, so the root can be shared by the workers
+    pub dir: Option<Box<str>>,
+    /// Root for the media up to `tmpfs_max_size` bytes, usually a tmpfs mount
+    pub tmpfs_dir: Option<Box<str>>,
+    pub tmpfs_max_size: u64,
+    /// Free space in bytes that must be left after the expected media size
+    pub reserve: u64,
+    /// Work dirs older than this in seconds are swept
+    pub max_age: u64,
+    /// Interval of the sweeps in seconds
+    pub sweep_interval: u64,
+}
+
+impl Default for Scratch {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            tmpfs_dir: None,
+            tmpfs_max_size: 100_000_000,
+            reserve: 100_000_000,
+            max_age: 21600,
+            sweep_interval: 3600,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SharedVolume {
+    pub dir: Box<str>,
+    /// Path of the directory as seen by the Bot API server, it's `dir` if not set
+    #[serde(default)]
+    pub public_dir: Option<Box<str>>,
+    /// Lifetime of the unreleased files in seconds
+    pub ttl: u64,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct Rate {
+    /// Tokens added per second
+    pub rate: f64,
+    /// Max tokens, the requests that can be made at once after a pause
+    pub burst: u32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RateLimit {
+    /// Limit of every host without an override
+    #[serde(flatten)]
+    pub default: Rate,
+    /// Limit of all the hosts together, not set means no global limit
+    #[serde(default)]
+    pub global: Option<Rate>,
+    /// Overrides by the domain, subdomains are matched too and share the bucket
+    #[serde(default)]
+    pub hosts: HashMap<Box<str>, Rate>,
+    /// Max random delay after a token is taken in seconds
+    #[serde(default = "RateLimit::default_jitter")]
+    pub jitter: f64,
+    /// Max factor the rate of the throttling host is divided by
+    #[serde(default = "RateLimit::default_max_slowdown")]
+    pub max_slowdown: f64,
+    /// The slowdown is halved for every this many seconds without throttling
+    #[serde(default = "RateLimit::default_recovery")]
+    pub recovery: u64,
+}
+
+impl RateLimit {
+    const fn default_jitter() -> f64 {
+        0.5
+    }
+
+    const fn default_max_slowdown() -> f64 {
+        16.0
+    }
+
+    const fn default_recovery() -> u64 {
+        300
+    }
+
+    fn rates(&self) -> impl Iterator<Item = (&'static str, Rate)> {
+        [("rate_limit", self.default)]
+            .into_iter()
+            .chain(self.global.map(|global| ("rate_limit.global", global)))
+            .chain(self.hosts.values().map(|rate| ("rate_limit.hosts", *rate)))
+    }
+}
+
 #[derive(Deserialize, Clone, Debug)]
 pub struct Config {
     pub server: Server,
@@ -64,14 +295,152 @@ pub struct Config {
     pub limits: Limits,
     pub yt_dlp: YtDlp,
     pub yt_pot_provider: YtPotProvider,
+    #[serde(default)]
+    pub animation: Animation,
+    #[serde(default)]
+    pub sponsorblock: SponsorBlock,
+    #[serde(default)]
+    pub live: Live,
+    /// Cache is disabled if the section is missing
+    #[serde(default)]
+    pub cache: Option<Cache>,
+    #[serde(default)]
+    pub scratch: Scratch,
+    /// Shared volume delivery is disabled if the section is missing
+    #[serde(default)]
+    pub shared_volume: Option<SharedVolume>,
+    #[serde(default)]
+    pub auth: Auth,
+    /// Spans aren't exported if the section is missing
+    #[serde(default)]
+    pub tracing: Option<telemetry::Config>,
+    /// Requests aren't limited if the section is missing
+    #[serde(default)]
+    pub rate_limit: Option<RateLimit>,
 }
 
 impl Config {
     pub fn from_fs(path: impl AsRef<Path>) -> Result<Config, anyhow::Error> {
         let raw = fs::read_to_string(path)?;
-        let cfg = toml::from_str(&raw)?;
+        let cfg: Config = toml::from_str(&raw)?;
+        cfg.validate()?;
         Ok(cfg)
     }
+
+    /// Check the values that are parsed but can't be used
+    /// # Errors
+    /// Returns [`ValidationError`] for the first invalid value
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.limits.max_file_size == 0 {
+            return Err(ValidationError::ZeroMaxFileSize);
+        }
+        if let Some(max_split_file_size) = self.limits.max_split_file_size
+            && max_split_file_size < self.limits.max_file_size
+        {
+            return Err(ValidationError::MaxSplitFileSize);
+        }
+        if self.scratch.sweep_interval == 0 {
+            return Err(ValidationError::ZeroSweepInterval);
+        }
+        if let Some((domain, _)) =
+            self.yt_dlp.extractors.iter().find(|(_, extractor)| {
+                extractor.name.is_none() && (!extractor.player_clients.is_empty() || !extractor.player_skip.is_empty())
+            })
+        {
+            return Err(ValidationError::ExtractorName(domain.clone()));
+        }
+        Url::parse(&self.yt_pot_provider.url).map_err(|err| ValidationError::Url("yt_pot_provider.url", err))?;
+        Url::parse(&self.sponsorblock.api_url).map_err(|err| ValidationError::Url("sponsorblock.api_url", err))?;
+        if let Some(rate_limit) = &self.rate_limit {
+            if let Some((section, _)) = rate_limit.rates().find(|(_, rate)| rate.rate <= 0.0 || rate.burst == 0) {
+                return Err(ValidationError::Rate(section));
+            }
+            if rate_limit.max_slowdown < 1.0 {
+                return Err(ValidationError::MaxSlowdown);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ValidationError {
+    #[error("`limits.max_file_size` must be greater than zero")]
+    ZeroMaxFileSize,
+    #[error("`limits.max_split_file_size` must not be less than `limits.max_file_size`")]
+    MaxSplitFileSize,
+    #[error("`scratch.sweep_interval` must be greater than zero")]
+    ZeroSweepInterval,
+    #[error("`yt_dlp.extractors.\"{0}\".name` must be set with the player options")]
+    ExtractorName(Box<str>),
+    #[error("`{0}` is not a valid URL: {1}")]
+    Url(&'static str, url::ParseError),
+    #[error("`{0}` must have a positive rate and burst")]
+    Rate(&'static str),
+    #[error("`rate_limit.max_slowdown` must not be less than one")]
+    MaxSlowdown,
+}
+
+/// Config section that can be replaced while the worker is running.
+/// Jobs take a snapshot with [`Reloadable::load`], so the running ones keep the values they started with.
+#[derive(Debug)]
+pub struct Reloadable<T>(Arc<ArcSwap<T>>);
+
+impl<T> Reloadable<T> {
+    #[must_use]
+    pub fn new(value: T) -> Self {
+        Self(Arc::new(ArcSwap::from_pointee(value)))
+    }
+
+    #[must_use]
+    pub fn load(&self) -> Arc<T> {
+        self.0.load_full()
+    }
+
+    pub fn store(&self, value: T) {
+        self.0.store(Arc::new(value));
+    }
+}
+
+// Clones share the value, derive would require `T: Clone`
+impl<T> Clone for Reloadable<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+/// Sections that are applied on SIGHUP, changes of the others require a restart
+#[derive(Debug, Clone)]
+pub struct ReloadableSections {
+    pub limits: Reloadable<Limits>,
+    pub yt_dlp: Reloadable<YtDlp>,
+    pub yt_pot_provider: Reloadable<YtPotProvider>,
+    pub animation: Reloadable<Animation>,
+    pub sponsorblock: Reloadable<SponsorBlock>,
+    pub live: Reloadable<Live>,
+}
+
+impl ReloadableSections {
+    #[must_use]
+    pub fn new(config: &Config) -> Self {
+        Self {
+            limits: Reloadable::new(config.limits.clone()),
+            yt_dlp: Reloadable::new(config.yt_dlp.clone()),
+            yt_pot_provider: Reloadable::new(config.yt_pot_provider.clone()),
+            animation: Reloadable::new(config.animation.clone()),
+            sponsorblock: Reloadable::new(config.sponsorblock.clone()),
+            live: Reloadable::new(config.live.clone()),
+        }
+    }
+
+    pub fn store(&self, config: Config) {
+        self.limits.store(config.limits);
+        self.yt_dlp.store(config.yt_dlp);
+        self.yt_pot_provider.store(config.yt_pot_provider);
+        self.animation.store(config.animation);
+        self.sponsorblock.store(config.sponsorblock);
+        self.live.store(config.live);
+    }
 }
 
 /// # Panics
@@ -88,3 +457,73 @@ pub fn get_config_path() -> Box<str> {
     };
     path.into_boxed_str()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        [server]
+        host = "127.0.0.1"
+        port = 50051
+
+        [logging]
+        dirs = "info"
+
+        [limits]
+        max_file_size = 100
+
+        [yt_dlp]
+        executable_path = "yt-dlp"
+
+        [yt_pot_provider]
+        url = "http://127.0.0.1:4416"
+    "#;
+
+    #[test]
+    fn test_reload_keeps_snapshot() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        let sections = ReloadableSections::new(&config);
+        let snapshot = sections.limits.load();
+
+        let mut config = config;
+        config.limits.max_file_size = 200;
+        sections.store(config);
+
+        assert_eq!(snapshot.max_file_size, 100);
+        assert_eq!(sections.limits.load().max_file_size, 200);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config: Config = toml::from_str(CONFIG).unwrap();
+        assert!(config.validate().is_ok());
+
+        config.limits.max_split_file_size = Some(50);
+        assert!(matches!(config.validate(), Err(ValidationError::MaxSplitFileSize)));
+
+        config.limits.max_split_file_size = None;
+        config.scratch.sweep_interval = 0;
+        assert!(matches!(config.validate(), Err(ValidationError::ZeroSweepInterval)));
+
+        config.scratch.sweep_interval = 60;
+        let extractor = Extractor {
+            player_clients: vec!["tv".into()],
+            ..Default::default()
+        };
+        config.yt_dlp.extractors.insert("youtu.be".into(), extractor.clone());
+        assert!(matches!(config.validate(), Err(ValidationError::ExtractorName(domain)) if &*domain == "youtu.be"));
+
+        config.yt_dlp.extractors.insert(
+            "youtu.be".into(),
+            Extractor {
+                name: Some("youtube".into()),
+                ..extractor
+            },
+        );
+        assert!(config.validate().is_ok());
+
+        config.yt_pot_provider.url = "not a url".into();
+        assert!(matches!(config.validate(), Err(ValidationError::Url("yt_pot_provider.url", _))));
+    }
+}
@@ -1,28 +1,101 @@
 use froodi::{DefaultScope::App, Inject, async_impl::Container, async_registry, instance, registry};
 
 use crate::{
-    config::{Config, Limits, Version, YtDlp, YtPotProvider},
-    interactors::download::{audio, thumbnail, video},
+    adapters::{
+        backend::{CliBackend, FfmpegMuxer},
+        cache::MediaCache,
+        rate_limiter::RateLimiter,
+        scratch::Scratch,
+        shared_volume::SharedVolume,
+    },
+    config::{Animation, Config, Limits, Live, Reloadable, ReloadableSections, SponsorBlock, Version, YtDlp, YtPotProvider},
+    interactors::{
+        animation, cache, chapters,
+        download::{audio, direct, gallery, live, thumbnail, video},
+        probe, shared_volume, split, streamable, transcode, upload, waveform,
+    },
 };
 
-pub fn init(config: Config, version: Version) -> Container {
+pub fn init(
+    config: Config,
+    reloadable: ReloadableSections,
+    version: Version,
+    cache: Option<MediaCache>,
+    scratch: Scratch,
+    shared_volume: Option<SharedVolume>,
+    rate_limiter: RateLimiter,
+) -> Container {
     let sync_registry = registry! {
         scope(App) [
             provide(instance(config.logging)),
-            provide(instance(config.limits)),
-            provide(instance(config.yt_dlp)),
-            provide(instance(config.yt_pot_provider)),
+            provide(instance(reloadable.limits)),
+            provide(instance(reloadable.animation)),
+            provide(instance(reloadable.sponsorblock)),
+            provide(instance(reloadable.live)),
+            provide(instance(reloadable.yt_dlp)),
+            provide(instance(reloadable.yt_pot_provider)),
             provide(instance(version)),
+            provide(instance(cache)),
+            provide(instance(scratch)),
+            provide(instance(shared_volume)),
+            provide(instance(rate_limiter)),
 
-            provide(|| Ok(thumbnail::Download)),
+            provide(|Inject(rate_limiter): Inject<RateLimiter>| Ok(CliBackend::new(rate_limiter))),
+            provide(|| Ok(FfmpegMuxer)),
+
+            provide(|
+                Inject(scratch): Inject<Scratch>,
+                Inject(backend): Inject<CliBackend>,| Ok(thumbnail::Download::new(scratch, backend))),
+            provide(|| Ok(probe::Probe)),
+            provide(|
+                Inject(cache): Inject<Option<MediaCache>>,
+                Inject(scratch): Inject<Scratch>,| Ok(cache::Lookup::new(cache, scratch))),
+            provide(|Inject(cache): Inject<Option<MediaCache>>| Ok(cache::Store::new(cache))),
+            provide(|Inject(volume): Inject<Option<SharedVolume>>| Ok(shared_volume::Publish::new(volume))),
+            provide(|Inject(volume): Inject<Option<SharedVolume>>| Ok(shared_volume::Release::new(volume))),
+            provide(|| Ok(chapters::Split)),
+            provide(|| Ok(waveform::Compute)),
+            provide(|
+                Inject(limits): Inject<Reloadable<Limits>>,
+                Inject(sponsorblock): Inject<Reloadable<SponsorBlock>>,
+                Inject(yt_dlp): Inject<Reloadable<YtDlp>>,
+                Inject(yt_pot): Inject<Reloadable<YtPotProvider>>,
+                Inject(scratch): Inject<Scratch>,
+                Inject(backend): Inject<CliBackend>,
+                Inject(muxer): Inject<FfmpegMuxer>,| Ok(video::Download::new(limits, sponsorblock, yt_dlp, yt_pot, scratch, backend, muxer))),
+            provide(|
+                Inject(limits): Inject<Reloadable<Limits>>,
+                Inject(sponsorblock): Inject<Reloadable<SponsorBlock>>,
+                Inject(yt_dlp): Inject<Reloadable<YtDlp>>,
+                Inject(yt_pot): Inject<Reloadable<YtPotProvider>>,
+                Inject(scratch): Inject<Scratch>,
+                Inject(backend): Inject<CliBackend>,| Ok(audio::Download::new(limits, sponsorblock, yt_dlp, yt_pot, scratch, backend))),
+            provide(|
+                Inject(yt_dlp): Inject<Reloadable<YtDlp>>,
+                Inject(yt_pot): Inject<Reloadable<YtPotProvider>>,
+                Inject(limits): Inject<Reloadable<Limits>>,
+                Inject(scratch): Inject<Scratch>,
+                Inject(rate_limiter): Inject<RateLimiter>,| Ok(gallery::Download::new(yt_dlp, yt_pot, limits, scratch, rate_limiter))),
+            provide(|
+                Inject(limits): Inject<Reloadable<Limits>>,
+                Inject(scratch): Inject<Scratch>,
+                Inject(rate_limiter): Inject<RateLimiter>,| Ok(direct::Download::new(limits, scratch, rate_limiter))),
+            provide(|
+                Inject(yt_dlp): Inject<Reloadable<YtDlp>>,
+                Inject(yt_pot): Inject<Reloadable<YtPotProvider>>,
+                Inject(limits): Inject<Reloadable<Limits>>,
+                Inject(live): Inject<Reloadable<Live>>,
+                Inject(scratch): Inject<Scratch>,
+                Inject(rate_limiter): Inject<RateLimiter>,| Ok(live::Record::new(yt_dlp, yt_pot, limits, live, scratch, rate_limiter))),
+            provide(|Inject(limits): Inject<Reloadable<Limits>>| Ok(split::Split::new(limits))),
+            provide(|Inject(limits): Inject<Reloadable<Limits>>| Ok(streamable::Convert::new(limits))),
+            provide(|Inject(limits): Inject<Reloadable<Limits>>| Ok(transcode::Transcode::new(limits))),
             provide(|
-                Inject(yt_dlp): Inject<YtDlp>,
-                Inject(limits): Inject<Limits>,
-                Inject(yt_pot): Inject<YtPotProvider>,| Ok(video::Download::new(yt_dlp, limits, yt_pot))),
+                Inject(limits): Inject<Reloadable<Limits>>,
+                Inject(scratch): Inject<Scratch>,| Ok(upload::Receive::new(limits, scratch))),
             provide(|
-                Inject(yt_dlp): Inject<YtDlp>,
-                Inject(limits): Inject<Limits>,
-                Inject(yt_pot): Inject<YtPotProvider>,| Ok(audio::Download::new(yt_dlp, limits, yt_pot))),
+                Inject(animation): Inject<Reloadable<Animation>>,
+                Inject(limits): Inject<Reloadable<Limits>>,| Ok(animation::Convert::new(animation, limits))),
         ],
     };
     let registry = async_registry! {
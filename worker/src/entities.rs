@@ -1,9 +1,17 @@
+mod cache_key;
 mod cookies;
+mod lease;
 mod media;
+mod properties;
+mod sponsorblock;
 mod thumbnail;
 
 pub mod format;
 
+pub use cache_key::CacheKey;
 pub use cookies::Cookie;
-pub use media::{MediaInFS, Video};
+pub use lease::Lease;
+pub use media::{DirectMediaInFS, GalleryInFS, MediaInFS, MediaPart, MediaPartsInFS, Track, TracksInFS, Video};
+pub use properties::MediaProperties;
+pub use sponsorblock::SponsorBlock;
 pub use thumbnail::Thumbnail;
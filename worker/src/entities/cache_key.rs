@@ -0,0 +1,27 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::utils::hash::sha256;
+
+/// Key of the processed media in the cache.
+/// Options must describe every processing step that changes the output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheKey(String);
+
+impl CacheKey {
+    #[must_use]
+    pub fn new(extractor: &str, media_id: &str, format_ids: &[&str], options: &str) -> Self {
+        Self(sha256(format!("{extractor}\0{media_id}\0{}\0{options}", format_ids.join("+"))))
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for CacheKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
@@ -1,5 +1,7 @@
 use std::fmt::{self, Display, Formatter};
 
+use crate::value_objects::{AudioCodec, Container, VideoCodec};
+
 #[derive(Debug, Clone)]
 pub struct Video {
     pub id: String,
@@ -7,6 +9,8 @@ pub struct Video {
     pub filesize: Option<f64>,
     pub filesize_approx: Option<f64>,
     pub container: String,
+    /// Codec string from `yt-dlp`, e.g. `avc1.64001F`
+    pub codec: Option<String>,
 }
 
 impl Video {
@@ -16,11 +20,17 @@ impl Video {
         self.filesize.or(self.filesize_approx)
     }
 
+    /// Extension of the file `yt-dlp` writes for the format
     #[inline]
     #[must_use]
     pub const fn extension(&self) -> &str {
         self.container.as_str()
     }
+
+    #[must_use]
+    pub fn codec(&self) -> Option<VideoCodec> {
+        self.codec.as_deref().and_then(VideoCodec::from_ytdlp)
+    }
 }
 
 impl Display for Video {
@@ -52,10 +62,21 @@ impl Audio {
         self.filesize.or(self.filesize_approx)
     }
 
+    #[must_use]
+    pub fn codec(&self) -> Option<AudioCodec> {
+        AudioCodec::from_ytdlp(&self.codec)
+    }
+
+    /// Container of the extracted audio, the unknown codecs are transcoded to `m4a`
+    #[must_use]
+    pub fn container(&self) -> Container {
+        self.codec().map_or(Container::M4a, Container::for_audio)
+    }
+
     #[inline]
     #[must_use]
-    pub const fn extension(&self) -> &str {
-        self.codec.as_str()
+    pub fn extension(&self) -> &'static str {
+        self.container().as_str()
     }
 }
 
@@ -72,6 +93,15 @@ impl Display for Audio {
     }
 }
 
+/// Audio format merged as its own track, e.g. one of the dubbed languages
+#[derive(Debug, Clone)]
+pub struct AudioTrack {
+    pub format: Audio,
+    /// Tag from `yt-dlp`, e.g. `en-US`
+    pub language: Option<String>,
+    pub default: bool,
+}
+
 pub struct Combined(pub Video, pub Audio);
 
 impl Combined {
@@ -81,10 +111,40 @@ impl Combined {
         self.0.id.as_str()
     }
 
+    /// Container of the merged file that keeps both streams, the single format is kept in its own one
+    #[must_use]
+    pub fn container(&self) -> Container {
+        let preferred = Container::from_extension(self.0.extension());
+        if self.ids_are_equal() {
+            return preferred;
+        }
+        Container::for_video(preferred, self.0.codec(), &self.1.codec().into_iter().collect::<Vec<_>>())
+    }
+
+    /// Container of the video merged with the tracks instead of the audio of the format
+    #[must_use]
+    pub fn container_with_tracks(&self, tracks: &[AudioTrack]) -> Container {
+        let audio = tracks.iter().filter_map(|track| track.format.codec()).collect::<Vec<_>>();
+        Container::for_video(Container::from_extension(self.0.extension()), self.0.codec(), &audio)
+    }
+
+    /// Extension of the output file, the single format keeps the one `yt-dlp` writes for it
+    #[must_use]
+    pub fn extension(&self) -> &str {
+        if self.ids_are_equal() {
+            return self.0.extension();
+        }
+        self.container().as_str()
+    }
+
+    /// Returns the size of the merged file, if both sizes are known
     #[inline]
     #[must_use]
-    pub const fn extension(&self) -> &str {
-        self.0.extension()
+    pub fn filesize_or_approx(&self) -> Option<f64> {
+        if self.ids_are_equal() {
+            return self.0.filesize_or_approx();
+        }
+        Some(self.0.filesize_or_approx()? + self.1.filesize_or_approx()?)
     }
 
     #[inline]
@@ -99,3 +159,76 @@ impl Display for Combined {
         write!(f, "video: {}, audio: {}", self.0, self.1)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video(id: &str, container: &str, codec: Option<&str>) -> Video {
+        Video {
+            id: id.to_owned(),
+            url: String::new(),
+            filesize: None,
+            filesize_approx: None,
+            container: container.to_owned(),
+            codec: codec.map(ToOwned::to_owned),
+        }
+    }
+
+    fn audio(id: &str, codec: &str) -> Audio {
+        Audio {
+            id: id.to_owned(),
+            url: String::new(),
+            filesize: None,
+            filesize_approx: None,
+            codec: codec.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_audio_extension() {
+        let cases = [
+            ("mp4a.40.2", "m4a"),
+            ("opus", "opus"),
+            ("vorbis", "ogg"),
+            ("mp3", "mp3"),
+            ("flac", "flac"),
+            ("ec-3", "m4a"),
+            ("none", "m4a"),
+        ];
+        for (codec, extension) in cases {
+            assert_eq!(audio("140", codec).extension(), extension, "{codec}");
+        }
+    }
+
+    #[test]
+    fn test_combined_extension() {
+        let cases = [
+            (video("137", "mp4", Some("avc1.640028")), audio("140", "mp4a.40.2"), "mp4"),
+            (video("248", "webm", Some("vp9")), audio("251", "opus"), "webm"),
+            (video("248", "webm", Some("vp9")), audio("140", "mp4a.40.2"), "mp4"),
+            (video("137", "mp4", Some("avc1.640028")), audio("251", "opus"), "mp4"),
+            (video("137", "mp4", None), audio("140", "mp4a.40.2"), "mp4"),
+            (video("1", "mp4", Some("vp8")), audio("2", "vorbis"), "webm"),
+            // Single format keeps the extension `yt-dlp` writes
+            (video("18", "3gp", Some("mp4v.20.3")), audio("18", "mp4a.40.2"), "3gp"),
+        ];
+        for (video, audio, extension) in cases {
+            let format = Combined(video, audio);
+            assert_eq!(format.extension(), extension, "{format}");
+        }
+    }
+
+    #[test]
+    fn test_container_with_tracks() {
+        let format = Combined(video("248", "webm", Some("vp9")), audio("251", "opus"));
+        let track = |codec: &str| AudioTrack {
+            format: audio("251", codec),
+            language: None,
+            default: false,
+        };
+
+        assert_eq!(format.container_with_tracks(&[track("opus"), track("opus")]), Container::Webm);
+        assert_eq!(format.container_with_tracks(&[track("opus"), track("mp4a.40.2")]), Container::Mp4);
+    }
+}
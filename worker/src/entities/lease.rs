@@ -0,0 +1,11 @@
+use std::path::PathBuf;
+
+/// File delivered to the shared volume, it's kept until the lease is released or expired
+#[derive(Debug, Clone)]
+pub struct Lease {
+    pub id: String,
+    /// Path as seen by the consumer of the volume
+    pub path: PathBuf,
+    /// Unix timestamp
+    pub expires_at: i64,
+}
@@ -2,6 +2,8 @@ use serde::Deserialize;
 use std::path::PathBuf;
 use tempfile::TempDir;
 
+use crate::value_objects::{LiveStatus, MediaKind};
+
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug, Clone, Deserialize)]
 pub struct Video {
@@ -9,6 +11,13 @@ pub struct Video {
     pub url: String,
     pub width: Option<i64>,
     pub height: Option<i64>,
+    pub duration: Option<f64>,
+    pub title: Option<String>,
+    #[serde(default)]
+    pub live_status: Option<LiveStatus>,
+    /// Unix timestamp of the scheduled start of the upcoming stream
+    #[serde(default)]
+    pub release_timestamp: Option<i64>,
 }
 
 #[derive(Debug)]
@@ -27,3 +36,114 @@ impl MediaInFS {
         }
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct MediaPart {
+    pub path: PathBuf,
+    pub start: f64,
+    pub end: f64,
+}
+
+impl MediaPart {
+    #[inline]
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>, start: f64, end: f64) -> Self {
+        Self {
+            path: path.into(),
+            start,
+            end,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MediaPartsInFS {
+    pub parts: Vec<MediaPart>,
+    pub temp_dir: TempDir,
+}
+
+impl MediaPartsInFS {
+    #[inline]
+    #[must_use]
+    pub const fn new(parts: Vec<MediaPart>, temp_dir: TempDir) -> Self {
+        Self { parts, temp_dir }
+    }
+}
+
+/// File of the direct link classified by its streams
+#[derive(Debug)]
+pub struct DirectMediaInFS {
+    pub media: MediaInFS,
+    pub kind: MediaKind,
+    /// Name of the file in the URL
+    pub title: Option<String>,
+}
+
+impl DirectMediaInFS {
+    #[inline]
+    #[must_use]
+    pub const fn new(media: MediaInFS, kind: MediaKind, title: Option<String>) -> Self {
+        Self { media, kind, title }
+    }
+}
+
+/// Items of the post in their original order
+#[derive(Debug)]
+pub struct GalleryInFS {
+    /// Downloaded items in the source order with their zero-based source indexes
+    pub items: Vec<(u32, PathBuf)>,
+    /// Items of the source, including the skipped ones
+    pub count: u32,
+    /// Zero-based indexes of the source items that weren't downloaded
+    pub skipped: Vec<u32>,
+    pub temp_dir: TempDir,
+}
+
+impl GalleryInFS {
+    #[inline]
+    #[must_use]
+    pub const fn new(items: Vec<(u32, PathBuf)>, count: u32, skipped: Vec<u32>, temp_dir: TempDir) -> Self {
+        Self {
+            items,
+            count,
+            skipped,
+            temp_dir,
+        }
+    }
+}
+
+/// Chapter of the source cut into a separate file
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub path: PathBuf,
+    pub title: String,
+    pub start: f64,
+    pub end: f64,
+}
+
+impl Track {
+    #[inline]
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>, title: String, start: f64, end: f64) -> Self {
+        Self {
+            path: path.into(),
+            title,
+            start,
+            end,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TracksInFS {
+    pub tracks: Vec<Track>,
+    pub temp_dir: TempDir,
+}
+
+impl TracksInFS {
+    #[inline]
+    #[must_use]
+    pub const fn new(tracks: Vec<Track>, temp_dir: TempDir) -> Self {
+        Self { tracks, temp_dir }
+    }
+}
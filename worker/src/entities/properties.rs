@@ -0,0 +1,16 @@
+#[derive(Debug, Clone)]
+pub struct MediaProperties {
+    pub filesize: u64,
+    pub duration: Option<f64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub rotation: Option<i32>,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+    pub bitrate: Option<u64>,
+    pub container: &'static str,
+    pub mime_type: &'static str,
+    pub filename: String,
+    pub streamable: bool,
+    pub sha256: String,
+}
@@ -0,0 +1,22 @@
+use crate::value_objects::SponsorBlockCategory;
+
+/// Segments of the categories to cut out of the media or to mark as chapters
+#[derive(Debug, Clone, Default)]
+pub struct SponsorBlock {
+    pub remove: Vec<SponsorBlockCategory>,
+    pub mark: Vec<SponsorBlockCategory>,
+}
+
+impl SponsorBlock {
+    #[inline]
+    #[must_use]
+    pub const fn new(remove: Vec<SponsorBlockCategory>, mark: Vec<SponsorBlockCategory>) -> Self {
+        Self { remove, mark }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.remove.is_empty() && self.mark.is_empty()
+    }
+}
@@ -2,6 +2,16 @@
 
 mod base;
 
+pub mod animation;
+pub mod cache;
+pub mod chapters;
 pub mod download;
+pub mod probe;
+pub mod shared_volume;
+pub mod split;
+pub mod streamable;
+pub mod transcode;
+pub mod upload;
+pub mod waveform;
 
 pub use base::Interactor;
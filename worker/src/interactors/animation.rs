@@ -0,0 +1,81 @@
+use std::{io, sync::Arc};
+use tracing::{info, instrument};
+
+use crate::{
+    adapters::{ffmpeg::convert_to_animation, ffprobe},
+    config::{self, Reloadable},
+    entities::MediaInFS,
+    interactors::{Interactor, download::truncation},
+    value_objects::AnimationFormat,
+};
+
+const CONVERT_TIMEOUT: u64 = 300;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ErrorKind {
+    #[error("Ffmpeg error: {0}")]
+    Ffmpeg(io::Error),
+    #[error("Ffprobe error: {0}")]
+    Ffprobe(#[from] ffprobe::Error),
+    #[error(transparent)]
+    Truncation(#[from] truncation::ErrorKind),
+}
+
+/// Convert the video to a short silent clip that Telegram shows as an animation
+pub struct Convert {
+    animation_cfg: Arc<Reloadable<config::Animation>>,
+    limits_cfg: Arc<Reloadable<config::Limits>>,
+}
+
+impl Convert {
+    #[inline]
+    #[must_use]
+    pub const fn new(animation_cfg: Arc<Reloadable<config::Animation>>, limits_cfg: Arc<Reloadable<config::Limits>>) -> Self {
+        Self { animation_cfg, limits_cfg }
+    }
+}
+
+pub struct ConvertInput {
+    media: MediaInFS,
+    format: AnimationFormat,
+}
+
+impl ConvertInput {
+    #[inline]
+    #[must_use]
+    pub const fn new(media: MediaInFS, format: AnimationFormat) -> Self {
+        Self { media, format }
+    }
+}
+
+impl Interactor<ConvertInput> for &Convert {
+    type Output = MediaInFS;
+    type Err = ErrorKind;
+
+    #[instrument(skip_all, fields(path = %media.path.as_os_str().to_string_lossy(), ?format))]
+    async fn execute(self, ConvertInput { media, format }: ConvertInput) -> Result<Self::Output, Self::Err> {
+        let animation_cfg = self.animation_cfg.load();
+        let limits_cfg = self.limits_cfg.load();
+        let MediaInFS { path, temp_dir } = media;
+        let max_file_size = limits_cfg.max_file_size;
+        let config::Animation { max_duration, max_side } = *animation_cfg;
+
+        // Longer sources are cut, so the output is expected to be as long as the cut
+        let expected_duration = ffprobe::probe(&path)
+            .await?
+            .duration()
+            .map(|duration| duration.min(f64::from(max_duration)));
+
+        let stem = path.file_stem().map_or_else(|| "media".into(), |stem| stem.to_string_lossy());
+        let output_path = temp_dir.path().join(format!("{stem}.animation.{}", format.extension()));
+
+        convert_to_animation(&path, &output_path, format, max_duration, max_side, max_file_size, CONVERT_TIMEOUT)
+            .await
+            .map_err(Self::Err::Ffmpeg)?;
+        truncation::check(&output_path, max_file_size, expected_duration, None).await?;
+        let _ = tokio::fs::remove_file(&path).await;
+
+        info!("Media converted to animation");
+        Ok(Self::Output::new(output_path, temp_dir))
+    }
+}
@@ -0,0 +1,117 @@
+use std::{io, path::PathBuf, sync::Arc};
+use tracing::{info, instrument};
+
+use crate::{
+    adapters::{
+        cache::MediaCache,
+        metrics,
+        scratch::{self, Scratch},
+    },
+    entities::{CacheKey, MediaInFS},
+    interactors::Interactor,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ErrorKind {
+    #[error("Cache error: {0}")]
+    Cache(io::Error),
+    #[error(transparent)]
+    Scratch(#[from] scratch::Error),
+}
+
+/// Get the cached media, it's `None` on a miss or if the cache is disabled
+pub struct Lookup {
+    cache: Arc<Option<MediaCache>>,
+    scratch: Arc<Scratch>,
+}
+
+impl Lookup {
+    #[inline]
+    #[must_use]
+    pub const fn new(cache: Arc<Option<MediaCache>>, scratch: Arc<Scratch>) -> Self {
+        Self { cache, scratch }
+    }
+}
+
+pub struct LookupInput {
+    key: CacheKey,
+    media_id: String,
+}
+
+impl LookupInput {
+    #[inline]
+    #[must_use]
+    pub const fn new(key: CacheKey, media_id: String) -> Self {
+        Self { key, media_id }
+    }
+}
+
+impl Interactor<LookupInput> for &Lookup {
+    type Output = Option<MediaInFS>;
+    type Err = ErrorKind;
+
+    #[instrument(skip_all, fields(%key))]
+    async fn execute(self, LookupInput { key, media_id }: LookupInput) -> Result<Self::Output, Self::Err> {
+        let Some(cache) = self.cache.as_ref() else {
+            return Ok(None);
+        };
+
+        let temp_dir = self.scratch.work_dir(None)?;
+        let Some(path) = cache
+            .get(key.as_str(), temp_dir.path(), &media_id)
+            .await
+            .map_err(Self::Err::Cache)?
+        else {
+            metrics::CACHE_LOOKUPS.with_label_values(&["miss"]).inc();
+            return Ok(None);
+        };
+        metrics::CACHE_LOOKUPS.with_label_values(&["hit"]).inc();
+
+        info!("Media is taken from the cache");
+        Ok(Some(MediaInFS::new(path, temp_dir)))
+    }
+}
+
+/// Store the copy of the processed media, nothing is done if the cache is disabled
+pub struct Store {
+    cache: Arc<Option<MediaCache>>,
+}
+
+impl Store {
+    #[inline]
+    #[must_use]
+    pub const fn new(cache: Arc<Option<MediaCache>>) -> Self {
+        Self { cache }
+    }
+}
+
+pub struct StoreInput {
+    key: CacheKey,
+    path: PathBuf,
+}
+
+impl StoreInput {
+    #[inline]
+    #[must_use]
+    pub const fn new(key: CacheKey, path: PathBuf) -> Self {
+        Self { key, path }
+    }
+}
+
+impl Interactor<StoreInput> for &Store {
+    type Output = ();
+    type Err = ErrorKind;
+
+    #[instrument(skip_all, fields(%key))]
+    async fn execute(self, StoreInput { key, path }: StoreInput) -> Result<Self::Output, Self::Err> {
+        let Some(cache) = self.cache.as_ref() else {
+            return Ok(());
+        };
+
+        cache.put(key.as_str(), &path).await.map_err(Self::Err::Cache)?;
+        let stats = cache.stats();
+        metrics::CACHE_SIZE_BYTES.set(i64::try_from(stats.size).unwrap_or(i64::MAX));
+        info!(hits = stats.hits, misses = stats.misses, size = stats.size, "Media is cached");
+        Ok(())
+    }
+}
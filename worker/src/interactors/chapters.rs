@@ -0,0 +1,166 @@
+use std::{io, path::PathBuf};
+use tracing::{debug, info, instrument};
+
+use crate::{
+    adapters::{
+        ffmpeg::{TrackTags, extract_track},
+        ffprobe,
+    },
+    entities::{MediaInFS, Track, TracksInFS},
+    interactors::Interactor,
+};
+
+const EXTRACT_TIMEOUT: u64 = 120;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ErrorKind {
+    #[error("Ffmpeg error: {0}")]
+    Ffmpeg(io::Error),
+    #[error("Ffprobe error: {0}")]
+    Ffprobe(#[from] ffprobe::Error),
+    #[error("Media duration is unknown")]
+    UnknownDuration,
+}
+
+/// Cut the audio into tracks by its chapters, media without chapters is a single track
+pub struct Split;
+
+pub struct SplitInput {
+    media: MediaInFS,
+    album: Option<String>,
+    cover_path: Option<PathBuf>,
+}
+
+impl SplitInput {
+    #[inline]
+    #[must_use]
+    pub const fn new(media: MediaInFS, album: Option<String>, cover_path: Option<PathBuf>) -> Self {
+        Self { media, album, cover_path }
+    }
+}
+
+impl Interactor<SplitInput> for &Split {
+    type Output = TracksInFS;
+    type Err = ErrorKind;
+
+    #[instrument(skip_all, fields(path = %media.path.as_os_str().to_string_lossy()))]
+    async fn execute(self, SplitInput { media, album, cover_path }: SplitInput) -> Result<Self::Output, Self::Err> {
+        let MediaInFS { path, temp_dir } = media;
+        let probe = ffprobe::probe(&path).await?;
+
+        let chapters = chapter_ranges(&probe.chapters, probe.duration());
+        if chapters.len() < 2 {
+            debug!("Media has no chapters");
+            let duration = probe.duration().ok_or(Self::Err::UnknownDuration)?;
+            let title = album.unwrap_or_else(|| path.file_stem().unwrap_or_default().to_string_lossy().into_owned());
+            return Ok(Self::Output::new(vec![Track::new(path, title, 0.0, duration)], temp_dir));
+        }
+
+        let extension = path.extension().unwrap_or_default().to_string_lossy().into_owned();
+        let count = u32::try_from(chapters.len()).unwrap_or(u32::MAX);
+        let mut tracks = Vec::with_capacity(chapters.len());
+        for (number, (start, end, title)) in (1..=count).zip(chapters) {
+            let title = title.unwrap_or_else(|| format!("Track {number}"));
+            let track_path = temp_dir.path().join(format!("track_{number:03}.{extension}"));
+            let tags = TrackTags {
+                title: &title,
+                album: album.as_deref(),
+                number,
+                count,
+            };
+
+            extract_track(&path, &track_path, start, end, &tags, cover_path.as_deref(), EXTRACT_TIMEOUT)
+                .await
+                .map_err(Self::Err::Ffmpeg)?;
+            tracks.push(Track::new(track_path, title, start, end));
+        }
+        let _ = tokio::fs::remove_file(&path).await;
+
+        info!(count, "Media split by chapters");
+        Ok(Self::Output::new(tracks, temp_dir))
+    }
+}
+
+/// Ranges of the chapters in the playback order.
+/// A missing end is taken from the next chapter or the media duration,
+/// overlaps are cut from the start of the later chapter and the empty ranges are dropped.
+fn chapter_ranges(chapters: &[ffprobe::Chapter], duration: Option<f64>) -> Vec<(f64, f64, Option<String>)> {
+    let mut chapters = chapters
+        .iter()
+        .filter_map(|chapter| Some((chapter.start()?, chapter.end(), chapter.tags.title.clone())))
+        .collect::<Vec<_>>();
+    chapters.sort_by(|(a, ..), (b, ..)| a.total_cmp(b));
+
+    let mut ranges = Vec::with_capacity(chapters.len());
+    let mut previous_end = 0.0_f64;
+    for (index, (start, end, title)) in chapters.iter().enumerate() {
+        let Some(end) = end.or_else(|| chapters.get(index + 1).map(|(next, ..)| *next)).or(duration) else {
+            continue;
+        };
+        let end = duration.map_or(end, |duration| end.min(duration));
+        let start = start.max(previous_end);
+        if end > start {
+            ranges.push((start, end, title.clone()));
+            previous_end = end;
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(start: &str, end: &str, title: Option<&str>) -> ffprobe::Chapter {
+        ffprobe::Chapter {
+            start_time: start.to_owned(),
+            end_time: end.to_owned(),
+            tags: ffprobe::ChapterTags {
+                title: title.map(ToOwned::to_owned),
+            },
+        }
+    }
+
+    #[test]
+    fn test_chapter_ranges() {
+        let chapters = [
+            chapter("0.000000", "60.000000", Some("Intro")),
+            chapter("60.000000", "180.500000", None),
+        ];
+
+        assert_eq!(
+            chapter_ranges(&chapters, Some(180.5)),
+            [(0.0, 60.0, Some("Intro".to_owned())), (60.0, 180.5, None)]
+        );
+    }
+
+    #[test]
+    fn test_chapter_ranges_edge_cases() {
+        let chapters = [
+            // Out of order
+            chapter("120.000000", "N/A", Some("Missing end")),
+            chapter("0.000000", "60.000000", Some("First")),
+            chapter("60.000000", "60.000000", Some("Zero length")),
+            chapter("50.000000", "120.000000", Some("Overlapping")),
+            chapter("N/A", "130.000000", Some("Missing start")),
+            chapter("200.000000", "300.000000", Some("After the end")),
+        ];
+
+        assert_eq!(
+            chapter_ranges(&chapters, Some(180.0)),
+            [
+                (0.0, 60.0, Some("First".to_owned())),
+                (60.0, 120.0, Some("Overlapping".to_owned())),
+                (120.0, 180.0, Some("Missing end".to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chapter_ranges_missing_end() {
+        let chapters = [chapter("0.000000", "N/A", None), chapter("30.000000", "N/A", None)];
+
+        assert_eq!(chapter_ranges(&chapters, None), [(0.0, 30.0, None)]);
+        assert_eq!(chapter_ranges(&chapters, Some(90.0)), [(0.0, 30.0, None), (30.0, 90.0, None)]);
+    }
+}
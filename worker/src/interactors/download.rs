@@ -1,3 +1,8 @@
 pub mod audio;
+pub mod direct;
+pub mod gallery;
+pub mod live;
+pub mod sponsorblock;
 pub mod thumbnail;
+pub mod truncation;
 pub mod video;
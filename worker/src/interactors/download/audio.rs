@@ -1,12 +1,15 @@
 use std::{io, sync::Arc};
-use tempfile::TempDir;
 use tracing::{info, instrument};
 
 use crate::{
-    adapters::ytdl::download_audio_to_path,
-    config,
-    entities::{Cookie, MediaInFS, Video, format},
-    interactors::Interactor,
+    adapters::{
+        backend::{CliBackend, DownloadRequest, MediaBackend},
+        scratch::{self, Scratch},
+        ytdl::sponsorblock_args,
+    },
+    config::{self, Reloadable},
+    entities::{Cookie, MediaInFS, SponsorBlock, Video, format},
+    interactors::{Interactor, download::truncation},
 };
 
 const DOWNLOAD_TIMEOUT: u64 = 360;
@@ -15,30 +18,41 @@ const DOWNLOAD_TIMEOUT: u64 = 360;
 pub enum ErrorKind {
     #[error("Ytdlp error: {0}")]
     Ytdlp(io::Error),
-    #[error("Temp dir error: {0}")]
-    TempDir(io::Error),
+    #[error(transparent)]
+    Scratch(#[from] scratch::Error),
     #[error("URL parse error: {0}")]
     Url(#[from] url::ParseError),
+    #[error(transparent)]
+    Truncation(#[from] truncation::ErrorKind),
 }
 
-pub struct Download {
-    yt_dlp_cfg: Arc<config::YtDlp>,
-    limits_cfg: Arc<config::Limits>,
-    yt_pot_provider_cfg: Arc<config::YtPotProvider>,
+pub struct Download<B = CliBackend> {
+    limits_cfg: Arc<Reloadable<config::Limits>>,
+    sponsorblock_cfg: Arc<Reloadable<config::SponsorBlock>>,
+    yt_dlp_cfg: Arc<Reloadable<config::YtDlp>>,
+    yt_pot_provider_cfg: Arc<Reloadable<config::YtPotProvider>>,
+    scratch: Arc<Scratch>,
+    backend: Arc<B>,
 }
 
-impl Download {
+impl<B> Download<B> {
     #[inline]
     #[must_use]
     pub const fn new(
-        yt_dlp_cfg: Arc<config::YtDlp>,
-        limits_cfg: Arc<config::Limits>,
-        yt_pot_provider_cfg: Arc<config::YtPotProvider>,
+        limits_cfg: Arc<Reloadable<config::Limits>>,
+        sponsorblock_cfg: Arc<Reloadable<config::SponsorBlock>>,
+        yt_dlp_cfg: Arc<Reloadable<config::YtDlp>>,
+        yt_pot_provider_cfg: Arc<Reloadable<config::YtPotProvider>>,
+        scratch: Arc<Scratch>,
+        backend: Arc<B>,
     ) -> Self {
         Self {
-            yt_dlp_cfg,
             limits_cfg,
+            sponsorblock_cfg,
+            yt_dlp_cfg,
             yt_pot_provider_cfg,
+            scratch,
+            backend,
         }
     }
 }
@@ -47,43 +61,116 @@ pub struct DownloadInput {
     video: Video,
     format: format::Audio,
     cookie: Option<Cookie>,
+    split: bool,
+    sponsorblock: SponsorBlock,
 }
 
 impl DownloadInput {
     #[inline]
     #[must_use]
-    pub const fn new(video: Video, format: format::Audio, cookie: Option<Cookie>) -> Self {
-        Self { video, format, cookie }
+    pub const fn new(video: Video, format: format::Audio, cookie: Option<Cookie>, split: bool, sponsorblock: SponsorBlock) -> Self {
+        Self {
+            video,
+            format,
+            cookie,
+            split,
+            sponsorblock,
+        }
     }
 }
 
-impl Interactor<DownloadInput> for &Download {
+impl<B: MediaBackend> Interactor<DownloadInput> for &Download<B> {
     type Output = MediaInFS;
     type Err = ErrorKind;
 
     #[instrument(skip_all, fields(%format))]
-    async fn execute(self, DownloadInput { video, format, cookie }: DownloadInput) -> Result<Self::Output, Self::Err> {
+    async fn execute(
+        self,
+        DownloadInput {
+            video,
+            format,
+            cookie,
+            split,
+            sponsorblock,
+        }: DownloadInput,
+    ) -> Result<Self::Output, Self::Err> {
+        let limits_cfg = self.limits_cfg.load();
+        let sponsorblock_cfg = self.sponsorblock_cfg.load();
+        let yt_dlp_cfg = self.yt_dlp_cfg.load();
+        let yt_pot_provider_cfg = self.yt_pot_provider_cfg.load();
+        let max_file_size = limits_cfg.max_source_file_size(split);
         let extension = format.extension();
-        let temp_dir = TempDir::new().map_err(Self::Err::TempDir)?;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let temp_dir = self.scratch.work_dir(format.filesize_or_approx().map(|filesize| filesize as u64))?;
+        let sponsorblock_args = sponsorblock_args(&sponsorblock, &sponsorblock_cfg.api_url);
+        // Removed segments make the output shorter than the source
+        let expected_duration = if sponsorblock.remove.is_empty() { video.duration } else { None };
         let file_path = temp_dir.path().join(format!("{}.{}", video.id, extension));
 
-        if let Err(err) = download_audio_to_path(
-            &self.yt_dlp_cfg.executable_path,
-            &video.url,
-            &self.yt_pot_provider_cfg.url,
-            &format.id,
-            extension,
-            temp_dir.path(),
-            DOWNLOAD_TIMEOUT,
-            self.limits_cfg.max_file_size,
-            cookie.as_ref(),
-        )
-        .await
-        {
-            return Err(Self::Err::Ytdlp(err));
-        }
+        self.backend
+            .download_audio(DownloadRequest {
+                yt_dlp_cfg: &yt_dlp_cfg,
+                pot_provider_api_url: &yt_pot_provider_cfg.url,
+                url: &video.url,
+                media_id: &video.id,
+                format_id: &format.id,
+                extension,
+                output_dir: temp_dir.path(),
+                timeout: DOWNLOAD_TIMEOUT,
+                max_file_size,
+                cookie: cookie.as_ref(),
+                sponsorblock_args: &sponsorblock_args,
+            })
+            .await
+            .map_err(Self::Err::Ytdlp)?;
+        truncation::check(&file_path, max_file_size, expected_duration, format.filesize_or_approx()).await?;
 
         info!("Audio downloaded");
         Ok(Self::Output::new(file_path, temp_dir))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::backend::fake::{FakeBackend, Fixture, Step};
+
+    fn download(fixture: &Fixture, backend: FakeBackend) -> Download<FakeBackend> {
+        Download::new(
+            Fixture::limits(),
+            Fixture::sponsorblock(),
+            Fixture::yt_dlp(),
+            Fixture::yt_pot_provider(),
+            fixture.scratch(),
+            Arc::new(backend),
+        )
+    }
+
+    fn input() -> DownloadInput {
+        DownloadInput::new(Fixture::video(), Fixture::audio_format("140"), None, false, SponsorBlock::default())
+    }
+
+    #[tokio::test]
+    async fn test_download() {
+        let fixture = Fixture::default();
+        let download = download(&fixture, FakeBackend::default().script("140", [Step::Write(b"audio")]));
+
+        let media = download.execute(input()).await.unwrap();
+
+        assert_eq!(media.path.file_name().unwrap(), "id.m4a");
+        assert_eq!(std::fs::read(&media.path).unwrap(), b"audio");
+    }
+
+    #[tokio::test]
+    async fn test_download_error() {
+        let fixture = Fixture::default();
+        let download = download(
+            &fixture,
+            FakeBackend::default().script("140", [Step::Fail(io::ErrorKind::TimedOut)]),
+        );
+
+        let err = download.execute(input()).await.unwrap_err();
+
+        assert!(matches!(err, ErrorKind::Ytdlp(err) if err.kind() == io::ErrorKind::TimedOut));
+    }
+}
@@ -0,0 +1,105 @@
+use reqwest::{Client, StatusCode};
+use std::sync::Arc;
+use tracing::{info, instrument};
+use url::Url;
+
+use crate::{
+    adapters::{
+        direct::{self, RemoteFile},
+        ffprobe,
+        rate_limiter::RateLimiter,
+        scratch::{self, Scratch},
+    },
+    config::{self, Reloadable},
+    entities::{DirectMediaInFS, MediaInFS},
+    interactors::Interactor,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ErrorKind {
+    #[error(transparent)]
+    Direct(#[from] direct::ErrorKind),
+    #[error(transparent)]
+    Scratch(#[from] scratch::Error),
+    #[error("Ffprobe error: {0}")]
+    Ffprobe(#[from] ffprobe::Error),
+    #[error("File has no video or audio streams")]
+    NoStreams,
+}
+
+/// Download of the plain media file without `yt-dlp`
+pub struct Download {
+    limits_cfg: Arc<Reloadable<config::Limits>>,
+    scratch: Arc<Scratch>,
+    rate_limiter: Arc<RateLimiter>,
+    client: Client,
+}
+
+impl Download {
+    #[inline]
+    #[must_use]
+    pub fn new(limits_cfg: Arc<Reloadable<config::Limits>>, scratch: Arc<Scratch>, rate_limiter: Arc<RateLimiter>) -> Self {
+        Self {
+            limits_cfg,
+            scratch,
+            rate_limiter,
+            client: direct::client(),
+        }
+    }
+
+    fn inspect_err(&self, url: &str, err: &direct::ErrorKind) {
+        if let direct::ErrorKind::Reqwest(err) = err
+            && matches!(err.status(), Some(StatusCode::TOO_MANY_REQUESTS | StatusCode::FORBIDDEN))
+        {
+            self.rate_limiter.throttled(url);
+        }
+    }
+}
+
+pub struct DownloadInput {
+    url: String,
+}
+
+impl DownloadInput {
+    #[inline]
+    #[must_use]
+    pub const fn new(url: String) -> Self {
+        Self { url }
+    }
+}
+
+impl Interactor<DownloadInput> for &Download {
+    type Output = DirectMediaInFS;
+    type Err = ErrorKind;
+
+    #[instrument(skip_all, fields(%url))]
+    async fn execute(self, DownloadInput { url }: DownloadInput) -> Result<Self::Output, Self::Err> {
+        let max_file_size = u64::from(self.limits_cfg.load().max_file_size);
+
+        let parsed = Url::parse(&url).map_err(|_| direct::ErrorKind::ForbiddenUrl(url.clone()))?;
+        direct::check_url(&parsed)?;
+        self.rate_limiter.acquire(&url).await;
+        let remote = direct::inspect(&self.client, &url, max_file_size)
+            .await
+            .inspect_err(|err| self.inspect_err(&url, err))?;
+        let temp_dir = self.scratch.work_dir(remote.filesize)?;
+        let file_path = temp_dir.path().join(format!("media.{}", remote.format.extension()));
+
+        self.rate_limiter.acquire(&remote.url).await;
+        let filesize = direct::download(&self.client, &remote, &file_path, max_file_size)
+            .await
+            .inspect_err(|err| self.inspect_err(&remote.url, err))?;
+        let kind = ffprobe::probe(&file_path).await?.media_kind().ok_or(ErrorKind::NoStreams)?;
+
+        info!(filesize, ?kind, "Direct file downloaded");
+        Ok(Self::Output::new(MediaInFS::new(file_path, temp_dir), kind, title(&remote)))
+    }
+}
+
+/// Name of the last path segment without the extension
+fn title(remote: &RemoteFile) -> Option<String> {
+    let url = Url::parse(&remote.url).ok()?;
+    let name = url.path_segments()?.next_back()?;
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    (!stem.is_empty()).then(|| stem.to_owned())
+}
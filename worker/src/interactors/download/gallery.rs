@@ -0,0 +1,254 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tracing::{debug, info, instrument, warn};
+
+use crate::{
+    adapters::{
+        rate_limiter::RateLimiter,
+        scratch::{self, Scratch},
+        ytdl::{GALLERY_MANIFEST_NAME, download_to_path, gallery_args, is_rate_limited},
+    },
+    config::{self, Reloadable},
+    entities::{Cookie, GalleryInFS},
+    interactors::Interactor,
+};
+
+const DOWNLOAD_TIMEOUT: u64 = 360;
+/// Leftovers of the interrupted or skipped downloads
+const PARTIAL_EXTENSIONS: [&str; 3] = ["part", "ytdl", "temp"];
+
+#[derive(thiserror::Error, Debug)]
+pub enum ErrorKind {
+    #[error("Ytdlp error: {0}")]
+    Ytdlp(io::Error),
+    #[error(transparent)]
+    Scratch(#[from] scratch::Error),
+    #[error("File error: {0}")]
+    File(io::Error),
+    #[error("No items were downloaded")]
+    Empty,
+}
+
+pub struct Download {
+    yt_dlp_cfg: Arc<Reloadable<config::YtDlp>>,
+    yt_pot_provider_cfg: Arc<Reloadable<config::YtPotProvider>>,
+    limits_cfg: Arc<Reloadable<config::Limits>>,
+    scratch: Arc<Scratch>,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+impl Download {
+    #[inline]
+    #[must_use]
+    pub const fn new(
+        yt_dlp_cfg: Arc<Reloadable<config::YtDlp>>,
+        yt_pot_provider_cfg: Arc<Reloadable<config::YtPotProvider>>,
+        limits_cfg: Arc<Reloadable<config::Limits>>,
+        scratch: Arc<Scratch>,
+        rate_limiter: Arc<RateLimiter>,
+    ) -> Self {
+        Self {
+            yt_dlp_cfg,
+            yt_pot_provider_cfg,
+            limits_cfg,
+            scratch,
+            rate_limiter,
+        }
+    }
+}
+
+pub struct DownloadInput {
+    url: String,
+    max_items: u32,
+    cookie: Option<Cookie>,
+}
+
+impl DownloadInput {
+    #[inline]
+    #[must_use]
+    pub const fn new(url: String, max_items: u32, cookie: Option<Cookie>) -> Self {
+        Self { url, max_items, cookie }
+    }
+}
+
+impl Interactor<DownloadInput> for &Download {
+    type Output = GalleryInFS;
+    type Err = ErrorKind;
+
+    #[instrument(skip_all, fields(%url, max_items))]
+    async fn execute(self, DownloadInput { url, max_items, cookie }: DownloadInput) -> Result<Self::Output, Self::Err> {
+        let yt_dlp_cfg = self.yt_dlp_cfg.load();
+        let yt_pot_provider_cfg = self.yt_pot_provider_cfg.load();
+        let limits_cfg = self.limits_cfg.load();
+        let temp_dir = self.scratch.work_dir(None)?;
+        let manifest = temp_dir.path().join(GALLERY_MANIFEST_NAME);
+
+        let args = gallery_args(
+            &yt_dlp_cfg,
+            &yt_pot_provider_cfg.url,
+            &url,
+            temp_dir.path(),
+            limits_cfg.max_file_size,
+            max_items,
+            cookie.as_ref(),
+        );
+        self.rate_limiter.acquire(&url).await;
+        // With `--ignore-errors` a non-zero exit means some items failed, the rest are still usable
+        let failed = match download_to_path(&yt_dlp_cfg.executable_path, &args, DOWNLOAD_TIMEOUT, "download_gallery")
+            .await
+            .inspect_err(|err| {
+                if is_rate_limited(err) {
+                    self.rate_limiter.throttled(&url);
+                }
+            }) {
+            Ok(()) => None,
+            Err(err) if err.kind() == io::ErrorKind::Other => Some(err),
+            Err(err) => return Err(Self::Err::Ytdlp(err)),
+        };
+
+        let mut items = Vec::new();
+        let mut entries = tokio::fs::read_dir(temp_dir.path()).await.map_err(Self::Err::File)?;
+        while let Some(entry) = entries.next_entry().await.map_err(Self::Err::File)? {
+            let path = entry.path();
+            if path == manifest {
+                continue;
+            }
+            if is_partial(&path) {
+                debug!(path = %path.display(), "Skip partial item");
+                continue;
+            }
+            // Names are the one-based source indexes
+            let Some(index) = path.file_stem().and_then(|stem| stem.to_str()?.parse::<u32>().ok()) else {
+                debug!(path = %path.display(), "Skip unknown file");
+                continue;
+            };
+            items.push((index.saturating_sub(1), path));
+        }
+        if items.is_empty() {
+            return Err(failed.map_or(Self::Err::Empty, Self::Err::Ytdlp));
+        }
+        if let Some(err) = failed {
+            warn!(%err, "Some items failed");
+        }
+        items.sort();
+
+        let manifest = match tokio::fs::read_to_string(&manifest).await {
+            Ok(manifest) => manifest,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(Self::Err::File(err)),
+        };
+        let (count, skipped) = source_items(&manifest, &items);
+        if !skipped.is_empty() {
+            warn!(?skipped, "Some items are skipped");
+        }
+
+        info!(count, downloaded = items.len(), "Gallery downloaded");
+        Ok(Self::Output::new(items, count, skipped, temp_dir))
+    }
+}
+
+fn is_partial(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| PARTIAL_EXTENSIONS.iter().any(|partial| extension.eq_ignore_ascii_case(partial)))
+}
+
+/// Count of the source items and the zero-based indexes of the ones without a downloaded file.
+/// Items up to the last one in the manifest or on the disk are counted, so the gaps are skipped too.
+fn source_items(manifest: &str, items: &[(u32, PathBuf)]) -> (u32, Vec<u32>) {
+    let count = manifest
+        .lines()
+        .filter_map(|line| line.trim().parse::<u32>().ok())
+        .chain(items.iter().map(|(index, _)| index + 1))
+        .max()
+        .unwrap_or_default();
+    let skipped = (0..count)
+        .filter(|index| !items.iter().any(|(downloaded, _)| downloaded == index))
+        .collect();
+    (count, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::backend::fake::Fixture;
+    use std::os::unix::fs::PermissionsExt as _;
+
+    /// Fake `yt-dlp` that lists 3 items in the manifest, downloads the first and the last one and fails
+    fn fake_yt_dlp(fixture: &Fixture, downloaded: &[&str]) -> Arc<Reloadable<config::YtDlp>> {
+        let path = fixture.root().join("yt-dlp");
+        let touch: String = downloaded.iter().map(|name| format!("touch \"$dir/{name}\"\n")).collect();
+        let script = format!(
+            r#"#!/bin/sh
+while [ $# -gt 0 ]; do
+  case "$1" in
+    --paths) dir="$2"; shift ;;
+    --print-to-file) manifest="$3"; shift 2 ;;
+  esac
+  shift
+done
+printf '001\n002\n003\n' > "$manifest"
+{touch}echo 'ERROR: [2] Unsupported item' >&2
+exit 1
+"#
+        );
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let cfg = Fixture::yt_dlp();
+        cfg.store(config::YtDlp {
+            executable_path: path.to_string_lossy().into(),
+            ..(*cfg.load()).clone()
+        });
+        cfg
+    }
+
+    fn download(fixture: &Fixture, yt_dlp_cfg: Arc<Reloadable<config::YtDlp>>) -> Download {
+        Download::new(
+            yt_dlp_cfg,
+            Fixture::yt_pot_provider(),
+            Fixture::limits(),
+            fixture.scratch(),
+            Arc::new(RateLimiter::new(None)),
+        )
+    }
+
+    fn input() -> DownloadInput {
+        DownloadInput::new("https://example.com/gallery".to_owned(), 10, None)
+    }
+
+    #[tokio::test]
+    async fn test_partial_failure() {
+        let fixture = Fixture::default();
+        let download = download(&fixture, fake_yt_dlp(&fixture, &["001.jpg", "003.mp4"]));
+
+        let gallery = download.execute(input()).await.unwrap();
+
+        let indexes: Vec<u32> = gallery.items.iter().map(|(index, _)| *index).collect();
+        assert_eq!(indexes, [0, 2]);
+        assert_eq!(gallery.count, 3);
+        assert_eq!(gallery.skipped, [1]);
+    }
+
+    #[tokio::test]
+    async fn test_total_failure() {
+        let fixture = Fixture::default();
+        let download = download(&fixture, fake_yt_dlp(&fixture, &[]));
+
+        let err = download.execute(input()).await.unwrap_err();
+
+        assert!(matches!(err, ErrorKind::Ytdlp(_)), "{err}");
+    }
+
+    #[test]
+    fn test_source_items() {
+        let items = [(0, PathBuf::from("/tmp/001.jpg")), (2, PathBuf::from("/tmp/003.mp4"))];
+
+        assert_eq!(source_items("001\n002\n003\n004\n", &items), (4, vec![1, 3]));
+        assert_eq!(source_items("001\n003\n", &items), (3, vec![1]));
+        assert_eq!(source_items("", &items), (3, vec![1]));
+        assert_eq!(source_items("", &items[..1]), (1, vec![]));
+    }
+}
@@ -0,0 +1,249 @@
+use nix::{
+    errno::Errno,
+    fcntl::{FcntlArg::F_SETFD, FdFlag, fcntl},
+    unistd::pipe,
+};
+use std::{io, process::Output, sync::Arc, time::Duration};
+use tokio::{process::Child, time::timeout};
+use tracing::{debug, info, instrument, warn};
+
+use crate::{
+    adapters::{
+        ffmpeg::record_live,
+        metrics::ProcessTimer,
+        rate_limiter::RateLimiter,
+        scratch::{self, Scratch},
+        ytdl::{download_to_pipe, live_args},
+    },
+    config::{self, Reloadable},
+    entities::{Cookie, MediaInFS, Video, format},
+    interactors::Interactor,
+    value_objects::LiveStatus,
+};
+
+/// Extra time for the recording to start and to be finalized
+const RECORD_TIMEOUT_MARGIN: u64 = 120;
+/// Separate video and audio formats can't be merged in a pipe, so a muxed one is used instead
+const MUXED_FORMAT: &str = "best";
+
+#[derive(thiserror::Error, Debug)]
+pub enum ErrorKind {
+    #[error("Ytdlp error: {0}")]
+    Ytdlp(io::Error),
+    #[error("Ffmpeg error: {0}")]
+    Ffmpeg(io::Error),
+    #[error("Pipe error: {0}")]
+    Pipe(Errno),
+    #[error(transparent)]
+    Scratch(#[from] scratch::Error),
+    #[error("Live stream hasn't started yet")]
+    Upcoming { release_timestamp: Option<i64> },
+    #[error("Media isn't live")]
+    NotLive,
+}
+
+pub struct Record {
+    yt_dlp_cfg: Arc<Reloadable<config::YtDlp>>,
+    yt_pot_provider_cfg: Arc<Reloadable<config::YtPotProvider>>,
+    limits_cfg: Arc<Reloadable<config::Limits>>,
+    live_cfg: Arc<Reloadable<config::Live>>,
+    scratch: Arc<Scratch>,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+impl Record {
+    #[inline]
+    #[must_use]
+    pub const fn new(
+        yt_dlp_cfg: Arc<Reloadable<config::YtDlp>>,
+        yt_pot_provider_cfg: Arc<Reloadable<config::YtPotProvider>>,
+        limits_cfg: Arc<Reloadable<config::Limits>>,
+        live_cfg: Arc<Reloadable<config::Live>>,
+        scratch: Arc<Scratch>,
+        rate_limiter: Arc<RateLimiter>,
+    ) -> Self {
+        Self {
+            yt_dlp_cfg,
+            yt_pot_provider_cfg,
+            limits_cfg,
+            live_cfg,
+            scratch,
+            rate_limiter,
+        }
+    }
+}
+
+pub struct RecordInput {
+    video: Video,
+    format: format::Combined,
+    cookie: Option<Cookie>,
+    duration: Option<u32>,
+    live_from_start: bool,
+}
+
+impl RecordInput {
+    #[inline]
+    #[must_use]
+    pub const fn new(video: Video, format: format::Combined, cookie: Option<Cookie>, duration: Option<u32>, live_from_start: bool) -> Self {
+        Self {
+            video,
+            format,
+            cookie,
+            duration,
+            live_from_start,
+        }
+    }
+}
+
+impl Interactor<RecordInput> for &Record {
+    type Output = MediaInFS;
+    type Err = ErrorKind;
+
+    #[instrument(skip_all, fields(%format, ?duration, live_from_start))]
+    async fn execute(
+        self,
+        RecordInput {
+            video,
+            format,
+            cookie,
+            duration,
+            live_from_start,
+        }: RecordInput,
+    ) -> Result<Self::Output, Self::Err> {
+        let yt_dlp_cfg = self.yt_dlp_cfg.load();
+        let yt_pot_provider_cfg = self.yt_pot_provider_cfg.load();
+        let limits_cfg = self.limits_cfg.load();
+        let live_cfg = self.live_cfg.load();
+        match video.live_status {
+            Some(LiveStatus::IsLive) => {}
+            Some(LiveStatus::IsUpcoming) => {
+                return Err(Self::Err::Upcoming {
+                    release_timestamp: video.release_timestamp,
+                });
+            }
+            _ => return Err(Self::Err::NotLive),
+        }
+
+        let duration = recording_duration(duration, live_cfg.max_duration);
+        let format_id = if format.ids_are_equal() { format.id() } else { MUXED_FORMAT };
+        // Recording is stopped at the max file size
+        let temp_dir = self.scratch.work_dir(Some(u64::from(limits_cfg.max_file_size)))?;
+        let file_path = temp_dir.path().join(format!("{}.mp4", video.id));
+        debug!(duration, format_id, "Record live stream");
+        // Recording starts only after the launch is allowed, so the wait isn't counted in the duration
+        self.rate_limiter.acquire(&video.url).await;
+
+        let (read_fd, write_fd) = pipe().map_err(Self::Err::Pipe)?;
+        fcntl(&write_fd, F_SETFD(FdFlag::FD_CLOEXEC)).map_err(Self::Err::Pipe)?;
+
+        let record_child = record_live(&read_fd, &file_path, duration, limits_cfg.max_file_size).map_err(Self::Err::Ffmpeg)?;
+        // `yt-dlp` gets a broken pipe after `ffmpeg` stops reading
+        drop(read_fd);
+        let args = live_args(
+            &yt_dlp_cfg,
+            &yt_pot_provider_cfg.url,
+            &video.url,
+            format_id,
+            live_from_start,
+            cookie.as_ref(),
+        );
+        let ytdl_child = download_to_pipe(write_fd, &yt_dlp_cfg.executable_path, &args).map_err(Self::Err::Ytdlp)?;
+
+        let record_timer = ProcessTimer::start("ffmpeg", "record_live");
+        let output = wait_recording(
+            record_child,
+            ytdl_child,
+            Duration::from_secs(u64::from(duration) + RECORD_TIMEOUT_MARGIN),
+        )
+        .await
+        .map_err(Self::Err::Ffmpeg)?;
+        record_timer.record_exit(output.status);
+        drop(record_timer);
+        if !output.status.success() {
+            return Err(Self::Err::Ffmpeg(io::Error::other(format!(
+                "FFmpeg exited with status `{}` and message: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr),
+            ))));
+        }
+
+        info!("Live stream recorded");
+        Ok(Self::Output::new(file_path, temp_dir))
+    }
+}
+
+/// Requested duration bounded by the max one, unset or zero means the max one
+fn recording_duration(duration: Option<u32>, max_duration: u32) -> u32 {
+    duration
+        .filter(|duration| *duration > 0)
+        .map_or(max_duration, |duration| duration.min(max_duration))
+}
+
+/// Wait for the recorder to finish, then stop the writer of the pipe.
+/// The recorder is killed if it doesn't finish in time.
+async fn wait_recording(recorder: Child, mut writer: Child, timeout_duration: Duration) -> Result<Output, io::Error> {
+    let res = timeout(timeout_duration, recorder.wait_with_output()).await;
+    if let Err(err) = writer.kill().await {
+        warn!("Failed to stop recording: {err}");
+    }
+
+    match res {
+        Ok(res) => res,
+        Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "FFmpeg process timed out")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{process::Stdio, time::Instant};
+    use tokio::process::Command;
+
+    use super::*;
+
+    /// Writer that fills the pipe until it's killed and the recorder that reads it with the shell command
+    fn spawn_pair(recorder: &str) -> (Child, Child) {
+        let (read_fd, write_fd) = pipe().unwrap();
+        let recorder = Command::new("sh")
+            .args(["-c", recorder])
+            .stdin(Stdio::from(read_fd))
+            .stdout(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let writer = Command::new("yes")
+            .stdin(Stdio::null())
+            .stdout(Stdio::from(write_fd))
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        (recorder, writer)
+    }
+
+    #[test]
+    fn test_recording_duration() {
+        assert_eq!(recording_duration(None, 600), 600);
+        assert_eq!(recording_duration(Some(0), 600), 600);
+        assert_eq!(recording_duration(Some(60), 600), 60);
+        assert_eq!(recording_duration(Some(3600), 600), 600);
+    }
+
+    #[tokio::test]
+    async fn test_wait_recording_stops_writer() {
+        let (recorder, writer) = spawn_pair("head -c 65536 > /dev/null");
+        let started = Instant::now();
+
+        let output = wait_recording(recorder, writer, Duration::from_secs(10)).await.unwrap();
+        assert!(output.status.success());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_wait_recording_timeout() {
+        let (recorder, writer) = spawn_pair("sleep 30");
+        let started = Instant::now();
+
+        let err = wait_recording(recorder, writer, Duration::from_millis(200)).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
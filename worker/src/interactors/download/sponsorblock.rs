@@ -0,0 +1,158 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info, instrument};
+
+use crate::{
+    adapters::{
+        ffmpeg::{add_chapters, keep_ranges},
+        ffprobe,
+        sponsorblock::{self, skip_segments},
+    },
+    entities::SponsorBlock,
+};
+
+const FFMPEG_TIMEOUT: u64 = 300;
+/// Ranges shorter than this are dropped instead of being kept as a separate piece
+const MIN_RANGE_DURATION: f64 = 0.5;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ErrorKind {
+    #[error("SponsorBlock error: {0}")]
+    Api(#[from] sponsorblock::Error),
+    #[error("Ffmpeg error: {0}")]
+    Ffmpeg(io::Error),
+    #[error("Ffprobe error: {0}")]
+    Ffprobe(#[from] ffprobe::Error),
+    #[error("Media duration is unknown")]
+    UnknownDuration,
+}
+
+/// Cut out and mark the segments of the media that was downloaded without `yt-dlp` postprocessing.
+/// # Returns
+/// Returns the path of the processed media, which is the input one if there are no segments
+#[instrument(skip_all, fields(%video_id))]
+pub async fn apply(api_url: &str, video_id: &str, sponsorblock: &SponsorBlock, path: PathBuf) -> Result<PathBuf, ErrorKind> {
+    let categories = sponsorblock.remove.iter().chain(&sponsorblock.mark).copied().collect::<Vec<_>>();
+    let segments = skip_segments(api_url, video_id, &categories).await?;
+    if segments.is_empty() {
+        return Ok(path);
+    }
+
+    let (removed, marked): (Vec<_>, Vec<_>) = segments
+        .into_iter()
+        .partition(|segment| sponsorblock.remove.iter().any(|category| category.as_str() == segment.category));
+    let removed = merge_ranges(removed.iter().map(|segment| segment.segment).collect());
+    let mut path = path;
+
+    if !removed.is_empty() {
+        let duration = ffprobe::probe(&path).await?.duration().ok_or(ErrorKind::UnknownDuration)?;
+        let output_path = processed_path(&path, "cut");
+
+        keep_ranges(&path, &output_path, &kept_ranges(duration, &removed), FFMPEG_TIMEOUT)
+            .await
+            .map_err(ErrorKind::Ffmpeg)?;
+        let _ = tokio::fs::remove_file(&path).await;
+        debug!(count = removed.len(), "Segments removed");
+        path = output_path;
+    }
+
+    if !marked.is_empty() {
+        let chapters = marked
+            .iter()
+            .map(|segment| {
+                let (start, end) = segment.segment;
+                (
+                    shift_by_removed(start, &removed),
+                    shift_by_removed(end, &removed),
+                    segment.category.as_str(),
+                )
+            })
+            .filter(|(start, end, _)| end > start)
+            .collect::<Vec<_>>();
+        let output_path = processed_path(&path, "marked");
+
+        add_chapters(&path, &output_path, &chapters, FFMPEG_TIMEOUT)
+            .await
+            .map_err(ErrorKind::Ffmpeg)?;
+        let _ = tokio::fs::remove_file(&path).await;
+        debug!(count = chapters.len(), "Segments marked");
+        path = output_path;
+    }
+
+    info!("SponsorBlock applied");
+    Ok(path)
+}
+
+fn processed_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}.{suffix}.{extension}"))
+}
+
+/// Sort the ranges and merge the overlapping ones
+fn merge_ranges(mut ranges: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    ranges.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut merged: Vec<(f64, f64)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Ranges between the removed ones, which must be merged
+fn kept_ranges(duration: f64, removed: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut kept = Vec::with_capacity(removed.len() + 1);
+    let mut start = 0.0;
+    for &(removed_start, removed_end) in removed {
+        if removed_start - start >= MIN_RANGE_DURATION {
+            kept.push((start, removed_start));
+        }
+        start = f64::max(start, removed_end);
+    }
+    if duration - start >= MIN_RANGE_DURATION {
+        kept.push((start, duration));
+    }
+    kept
+}
+
+/// Time in the media after the removed ranges are cut out
+fn shift_by_removed(time: f64, removed: &[(f64, f64)]) -> f64 {
+    let removed_before = removed.iter().map(|&(start, end)| (time.min(end) - start).max(0.0)).sum::<f64>();
+    time - removed_before
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_ranges() {
+        assert_eq!(
+            merge_ranges(vec![(30.0, 40.0), (5.0, 10.0), (8.0, 12.0)]),
+            [(5.0, 12.0), (30.0, 40.0)]
+        );
+    }
+
+    #[test]
+    fn test_kept_ranges() {
+        assert_eq!(
+            kept_ranges(60.0, &[(5.0, 12.0), (30.0, 40.0)]),
+            [(0.0, 5.0), (12.0, 30.0), (40.0, 60.0)]
+        );
+        assert_eq!(kept_ranges(60.0, &[(0.0, 10.0), (50.0, 60.0)]), [(10.0, 50.0)]);
+    }
+
+    #[test]
+    fn test_shift_by_removed() {
+        let removed = [(5.0, 12.0), (30.0, 40.0)];
+        assert!((shift_by_removed(3.0, &removed) - 3.0).abs() < f64::EPSILON);
+        assert!((shift_by_removed(20.0, &removed) - 13.0).abs() < f64::EPSILON);
+        assert!((shift_by_removed(50.0, &removed) - 33.0).abs() < f64::EPSILON);
+    }
+}
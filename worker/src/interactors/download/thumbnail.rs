@@ -1,20 +1,33 @@
-use std::io;
-use tempfile::TempDir;
+use std::sync::Arc;
 use tracing::{info, instrument};
 
 use crate::{
-    adapters::ffmpeg::download_thumbnail_to_path,
+    adapters::{
+        backend::{CliBackend, MediaBackend},
+        scratch::{self, Scratch},
+    },
     entities::{MediaInFS, Thumbnail},
     interactors::Interactor,
 };
 
 #[derive(thiserror::Error, Debug)]
 pub enum ErrorKind {
-    #[error("Temp dir error: {0}")]
-    TempDir(io::Error),
+    #[error(transparent)]
+    Scratch(#[from] scratch::Error),
 }
 
-pub struct Download;
+pub struct Download<B = CliBackend> {
+    scratch: Arc<Scratch>,
+    backend: Arc<B>,
+}
+
+impl<B> Download<B> {
+    #[inline]
+    #[must_use]
+    pub const fn new(scratch: Arc<Scratch>, backend: Arc<B>) -> Self {
+        Self { scratch, backend }
+    }
+}
 
 pub struct DownloadInput {
     thumbnail: Thumbnail,
@@ -28,17 +41,21 @@ impl DownloadInput {
     }
 }
 
-impl Interactor<DownloadInput> for &Download {
+impl<B: MediaBackend> Interactor<DownloadInput> for &Download<B> {
     type Output = Option<MediaInFS>;
     type Err = ErrorKind;
 
     #[instrument(skip_all, fields(%thumbnail))]
     async fn execute(self, DownloadInput { thumbnail }: DownloadInput) -> Result<Self::Output, Self::Err> {
-        let temp_dir = TempDir::new().map_err(Self::Err::TempDir)?;
+        let temp_dir = self.scratch.work_dir(None)?;
 
         let temp_dir_path = temp_dir.path().to_path_buf();
         for thumbnail_url in thumbnail.thumbnail_urls() {
-            if let Some(thumbnail_path) = download_thumbnail_to_path(thumbnail_url, &thumbnail.media_id, &temp_dir_path).await {
+            if let Some(thumbnail_path) = self
+                .backend
+                .download_thumbnail(&thumbnail_url, &thumbnail.media_id, &temp_dir_path)
+                .await
+            {
                 info!("Thumbnail downloaded");
                 return Ok(Some(MediaInFS::new(thumbnail_path, temp_dir)));
             }
@@ -46,3 +63,41 @@ impl Interactor<DownloadInput> for &Download {
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::backend::fake::{FakeBackend, Fixture, Step};
+
+    fn thumbnail() -> Thumbnail {
+        Thumbnail::new(
+            "id".to_owned(),
+            "example.com".to_owned(),
+            vec![
+                "https://example.com/missing.jpg".to_owned(),
+                "https://example.com/id.jpg".to_owned(),
+            ],
+            None,
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_download_falls_back_to_next_url() {
+        let fixture = Fixture::default();
+        let backend = FakeBackend::default().script("https://example.com/id.jpg", [Step::Write(b"jpg")]);
+        let download = Download::new(fixture.scratch(), Arc::new(backend));
+
+        let media = download.execute(DownloadInput::new(thumbnail())).await.unwrap().unwrap();
+
+        assert_eq!(media.path.file_name().unwrap(), "id.jpg");
+    }
+
+    #[tokio::test]
+    async fn test_download_none() {
+        let fixture = Fixture::default();
+        let download = Download::new(fixture.scratch(), Arc::new(FakeBackend::default()));
+
+        assert!(download.execute(DownloadInput::new(thumbnail())).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,136 @@
+use std::{io, path::Path};
+use tracing::{debug, instrument, warn};
+
+use crate::adapters::ffprobe;
+
+/// Output close to the limit by this ratio is considered as stopped by the limit
+const LIMIT_REACHED_RATIO: f64 = 0.99;
+/// Allowed difference between the source and output durations in seconds
+const DURATION_TOLERANCE: f64 = 2.0;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ErrorKind {
+    #[error("File is too large, estimated size is {estimated_filesize} bytes")]
+    FileTooLarge { estimated_filesize: u64 },
+    #[error("Ffprobe error: {0}")]
+    Ffprobe(#[from] ffprobe::Error),
+    #[error("File error: {0}")]
+    File(io::Error),
+}
+
+/// Check that the output wasn't stopped early by `--max-filesize` or `-fs`.
+/// `yt-dlp` doesn't create the file at all if it exceeds the limit,
+/// and `ffmpeg` stops writing when the limit is reached, so the file ends mid-media.
+/// An output close to the limit is truncated only if its duration falls short of the expected one,
+/// without the expected duration the size alone isn't enough to tell.
+/// # Errors
+/// Returns [`ErrorKind::FileTooLarge`] with the estimated real size if the output is truncated or missing
+#[instrument(skip_all, fields(path = %path.as_ref().as_os_str().to_string_lossy()))]
+pub async fn check(
+    path: impl AsRef<Path>,
+    max_file_size: u32,
+    expected_duration: Option<f64>,
+    expected_filesize: Option<f64>,
+) -> Result<(), ErrorKind> {
+    let path = path.as_ref();
+
+    let filesize = match tokio::fs::metadata(path).await {
+        Ok(metadata) => metadata.len(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            warn!("Output is missing, the limit is exceeded");
+            return Err(ErrorKind::FileTooLarge {
+                estimated_filesize: estimate_filesize(u64::from(max_file_size), None, expected_duration, expected_filesize),
+            });
+        }
+        Err(err) => return Err(ErrorKind::File(err)),
+    };
+
+    if !limit_reached(filesize, max_file_size) {
+        debug!(filesize, "Output is less than the limit");
+        return Ok(());
+    }
+
+    let Some(expected_duration) = expected_duration else {
+        debug!(filesize, "Output is close to the limit, but its expected duration is unknown");
+        return Ok(());
+    };
+    let duration = ffprobe::probe(path).await?.duration();
+    if duration.is_some_and(|duration| duration + DURATION_TOLERANCE >= expected_duration) {
+        debug!(filesize, ?duration, expected_duration, "Output is complete");
+        return Ok(());
+    }
+
+    warn!(filesize, ?duration, expected_duration, "Output is truncated by the limit");
+    Err(ErrorKind::FileTooLarge {
+        estimated_filesize: estimate_filesize(filesize, duration, Some(expected_duration), expected_filesize),
+    })
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn limit_reached(filesize: u64, max_file_size: u32) -> bool {
+    filesize as f64 >= f64::from(max_file_size) * LIMIT_REACHED_RATIO
+}
+
+/// Estimate the real size by the known format size or by the share of the downloaded duration
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn estimate_filesize(filesize: u64, duration: Option<f64>, expected_duration: Option<f64>, expected_filesize: Option<f64>) -> u64 {
+    if let Some(expected_filesize) = expected_filesize
+        && expected_filesize > filesize as f64
+    {
+        return expected_filesize as u64;
+    }
+    match (duration, expected_duration) {
+        (Some(duration), Some(expected_duration)) if duration > 0.0 && expected_duration > duration => {
+            (filesize as f64 * expected_duration / duration) as u64
+        }
+        _ => filesize,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_check_without_expected_duration() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("output.mp4");
+        tokio::fs::write(&path, vec![0; 1000]).await.unwrap();
+
+        assert!(check(&path, 1000, None, None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_check_missing_output() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("output.mp4");
+
+        let res = check(&path, 1000, None, Some(5000.0)).await;
+        assert!(matches!(res, Err(ErrorKind::FileTooLarge { estimated_filesize: 5000 })));
+    }
+
+    #[test]
+    fn test_limit_reached() {
+        assert!(limit_reached(1000, 1000));
+        assert!(limit_reached(995, 1000));
+        assert!(!limit_reached(900, 1000));
+    }
+
+    #[test]
+    fn test_estimate_filesize_by_format() {
+        assert_eq!(estimate_filesize(1000, Some(10.0), Some(40.0), Some(5000.0)), 5000);
+    }
+
+    #[test]
+    fn test_estimate_filesize_by_duration() {
+        assert_eq!(estimate_filesize(1000, Some(10.0), Some(40.0), None), 4000);
+        assert_eq!(estimate_filesize(1000, Some(10.0), Some(40.0), Some(500.0)), 4000);
+    }
+
+    #[test]
+    fn test_estimate_filesize_unknown() {
+        assert_eq!(estimate_filesize(1000, None, Some(40.0), None), 1000);
+        assert_eq!(estimate_filesize(1000, Some(0.0), Some(40.0), None), 1000);
+    }
+}
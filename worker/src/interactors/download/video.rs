@@ -1,38 +1,27 @@
-use bytes::Bytes;
-use futures_util::StreamExt as _;
-use mpsc::{UnboundedSender, error::SendError, unbounded_channel};
 use nix::{
     errno::Errno,
     fcntl::{FcntlArg::F_SETFD, FdFlag, fcntl},
     unistd::pipe,
 };
-use reqwest::Client;
-use std::{fs::File, io, sync::Arc, time::Duration};
-use tempfile::TempDir;
-use tokio::{io::AsyncWriteExt as _, sync::mpsc, time::timeout};
-use tracing::{Instrument as _, debug, debug_span, error, info, instrument, trace};
+use std::{io, sync::Arc, time::Duration};
+use tokio::time::timeout;
+use tracing::{debug, info, instrument};
 
 use crate::{
     adapters::{
-        ffmpeg::merge_streams,
-        ytdl::{download_to_pipe, download_video_to_path},
+        backend::{AudioPipe, CliBackend, DownloadRequest, FfmpegMuxer, MediaBackend, Muxer, StreamRequest},
+        scratch::{self, Scratch},
+        ytdl::sponsorblock_args,
+    },
+    config::{self, Reloadable},
+    entities::{Cookie, MediaInFS, SponsorBlock, Video, format},
+    interactors::{
+        Interactor,
+        download::{sponsorblock, truncation},
     },
-    config,
-    entities::{Cookie, MediaInFS, Video, format},
-    interactors::Interactor,
-    utils::format_error_report,
 };
 
 const DOWNLOAD_TIMEOUT: u64 = 360;
-const RANGE_CHUNK_SIZE: i32 = 1024 * 1024 * 10;
-
-#[derive(thiserror::Error, Debug)]
-pub enum RangeErrorKind {
-    #[error("Channel error: {0}")]
-    Channel(#[from] SendError<Bytes>),
-    #[error("Request error: {0}")]
-    Reqwest(#[from] reqwest::Error),
-}
 
 #[derive(thiserror::Error, Debug)]
 pub enum ErrorKind {
@@ -42,30 +31,46 @@ pub enum ErrorKind {
     Ffmpeg(io::Error),
     #[error("Pipe error: {0}")]
     Pipe(Errno),
-    #[error("Temp dir error: {0}")]
-    TempDir(io::Error),
+    #[error(transparent)]
+    Scratch(#[from] scratch::Error),
     #[error("URL parse error: {0}")]
     Url(#[from] url::ParseError),
+    #[error(transparent)]
+    Truncation(#[from] truncation::ErrorKind),
+    #[error(transparent)]
+    SponsorBlock(#[from] sponsorblock::ErrorKind),
 }
 
-pub struct Download {
-    yt_dlp_cfg: Arc<config::YtDlp>,
-    limits_cfg: Arc<config::Limits>,
-    yt_pot_provider_cfg: Arc<config::YtPotProvider>,
+pub struct Download<B = CliBackend, M = FfmpegMuxer> {
+    limits_cfg: Arc<Reloadable<config::Limits>>,
+    sponsorblock_cfg: Arc<Reloadable<config::SponsorBlock>>,
+    yt_dlp_cfg: Arc<Reloadable<config::YtDlp>>,
+    yt_pot_provider_cfg: Arc<Reloadable<config::YtPotProvider>>,
+    scratch: Arc<Scratch>,
+    backend: Arc<B>,
+    muxer: Arc<M>,
 }
 
-impl Download {
+impl<B, M> Download<B, M> {
     #[inline]
     #[must_use]
     pub const fn new(
-        yt_dlp_cfg: Arc<config::YtDlp>,
-        limits_cfg: Arc<config::Limits>,
-        yt_pot_provider_cfg: Arc<config::YtPotProvider>,
+        limits_cfg: Arc<Reloadable<config::Limits>>,
+        sponsorblock_cfg: Arc<Reloadable<config::SponsorBlock>>,
+        yt_dlp_cfg: Arc<Reloadable<config::YtDlp>>,
+        yt_pot_provider_cfg: Arc<Reloadable<config::YtPotProvider>>,
+        scratch: Arc<Scratch>,
+        backend: Arc<B>,
+        muxer: Arc<M>,
     ) -> Self {
         Self {
-            yt_dlp_cfg,
             limits_cfg,
+            sponsorblock_cfg,
+            yt_dlp_cfg,
             yt_pot_provider_cfg,
+            scratch,
+            backend,
+            muxer,
         }
     }
 }
@@ -73,140 +78,170 @@ impl Download {
 pub struct DownloadInput {
     video: Video,
     format: format::Combined,
+    /// Merged in their order instead of the audio of the format, if there are any
+    audio_tracks: Vec<format::AudioTrack>,
     cookie: Option<Cookie>,
+    split: bool,
+    sponsorblock: SponsorBlock,
 }
 
 impl DownloadInput {
     #[inline]
     #[must_use]
-    pub const fn new(video: Video, format: format::Combined, cookie: Option<Cookie>) -> Self {
-        Self { video, format, cookie }
+    pub const fn new(
+        video: Video,
+        format: format::Combined,
+        audio_tracks: Vec<format::AudioTrack>,
+        cookie: Option<Cookie>,
+        split: bool,
+        sponsorblock: SponsorBlock,
+    ) -> Self {
+        Self {
+            video,
+            format,
+            audio_tracks,
+            cookie,
+            split,
+            sponsorblock,
+        }
     }
 }
 
-impl Interactor<DownloadInput> for &Download {
+impl<B: MediaBackend, M: Muxer> Interactor<DownloadInput> for &Download<B, M> {
     type Output = MediaInFS;
     type Err = ErrorKind;
 
     #[instrument(skip_all, fields(%format))]
-    async fn execute(self, DownloadInput { video, format, cookie }: DownloadInput) -> Result<Self::Output, Self::Err> {
-        let extension = format.extension();
+    async fn execute(
+        self,
+        DownloadInput {
+            video,
+            format,
+            audio_tracks,
+            cookie,
+            split,
+            sponsorblock,
+        }: DownloadInput,
+    ) -> Result<Self::Output, Self::Err> {
+        let limits_cfg = self.limits_cfg.load();
+        let sponsorblock_cfg = self.sponsorblock_cfg.load();
+        // The video and audio streams of one merge are started with the same settings
+        let yt_dlp_cfg = self.yt_dlp_cfg.load();
+        let yt_pot_provider_cfg = self.yt_pot_provider_cfg.load();
+        let max_file_size = limits_cfg.max_source_file_size(split);
+        let extension = if audio_tracks.is_empty() {
+            format.extension()
+        } else {
+            format.container_with_tracks(&audio_tracks).as_str()
+        };
         let format_id = format.id();
-        let temp_dir = TempDir::new().map_err(Self::Err::TempDir)?;
+        let expected_filesize = if audio_tracks.is_empty() {
+            format.filesize_or_approx()
+        } else {
+            format.0.filesize_or_approx().and_then(|filesize| {
+                audio_tracks
+                    .iter()
+                    .try_fold(filesize, |filesize, track| Some(filesize + track.format.filesize_or_approx()?))
+            })
+        };
+        // SponsorBlock processing keeps the source until the processed copy is written
+        let copies = if sponsorblock.is_empty() { 1.0 } else { 2.0 };
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let temp_dir = self
+            .scratch
+            .work_dir(expected_filesize.map(|filesize| (filesize * copies) as u64))?;
+        let sponsorblock_args = sponsorblock_args(&sponsorblock, &sponsorblock_cfg.api_url);
+        // Removed segments make the output shorter than the source
+        let expected_duration = if sponsorblock.remove.is_empty() { video.duration } else { None };
         let file_path = temp_dir.path().join(format!("{}.{}", video.id, extension));
 
-        if format.ids_are_equal() {
+        if format.ids_are_equal() && audio_tracks.is_empty() {
             debug!("Formats are the same");
 
-            if let Err(err) = download_video_to_path(
-                &self.yt_dlp_cfg.executable_path,
-                &video.url,
-                &self.yt_pot_provider_cfg.url,
-                format_id,
-                extension,
-                temp_dir.path(),
-                DOWNLOAD_TIMEOUT,
-                self.limits_cfg.max_file_size,
-                cookie.as_ref(),
-            )
-            .await
-            {
-                return Err(Self::Err::Ytdlp(err));
-            }
+            self.backend
+                .download_video(DownloadRequest {
+                    yt_dlp_cfg: &yt_dlp_cfg,
+                    pot_provider_api_url: &yt_pot_provider_cfg.url,
+                    url: &video.url,
+                    media_id: &video.id,
+                    format_id,
+                    extension,
+                    output_dir: temp_dir.path(),
+                    timeout: DOWNLOAD_TIMEOUT,
+                    max_file_size,
+                    cookie: cookie.as_ref(),
+                    sponsorblock_args: &sponsorblock_args,
+                })
+                .await
+                .map_err(Self::Err::Ytdlp)?;
+            truncation::check(&file_path, max_file_size, expected_duration, expected_filesize).await?;
 
             info!("Video downloaded");
             return Ok(Self::Output::new(file_path, temp_dir));
         }
         debug!("Formats are different");
 
-        let (video_read_fd, video_write_fd) = pipe().map_err(Self::Err::Pipe)?;
-        let (audio_read_fd, audio_write_fd) = pipe().map_err(Self::Err::Pipe)?;
+        let audio_tracks = if audio_tracks.is_empty() {
+            vec![format::AudioTrack {
+                format: format.1.clone(),
+                language: None,
+                default: false,
+            }]
+        } else {
+            audio_tracks
+        };
+        debug!(count = audio_tracks.len(), "Audio tracks to merge");
 
+        let (video_read_fd, video_write_fd) = pipe().map_err(Self::Err::Pipe)?;
         fcntl(&video_write_fd, F_SETFD(FdFlag::FD_CLOEXEC)).map_err(Self::Err::Pipe)?;
-        fcntl(&audio_write_fd, F_SETFD(FdFlag::FD_CLOEXEC)).map_err(Self::Err::Pipe)?;
-
-        let mut merge_child = merge_streams(&video_read_fd, &audio_read_fd, extension, &file_path, self.limits_cfg.max_file_size)
-            .map_err(Self::Err::Ffmpeg)?;
-
-        if let Some(filesize) = format.0.filesize_or_approx() {
-            let (sender, mut receiver) = unbounded_channel();
-            let url = format.0.url.to_owned();
-            tokio::spawn(
-                async move {
-                    tokio::join!(
-                        async move {
-                            let _ = range_download_to_write(url, filesize, sender)
-                                .await
-                                .inspect_err(|err| error!("{}", format_error_report(&err)));
-                        },
-                        async move {
-                            let mut writer = tokio::fs::File::from_std(File::from(video_write_fd));
-                            while let Some(bytes) = receiver.recv().await {
-                                if let Err(err) = writer.write(&bytes).await {
-                                    match err.kind() {
-                                        io::ErrorKind::BrokenPipe => break,
-                                        _ => error!("{}", format_error_report(&err)),
-                                    }
-                                }
-                            }
-                        }
-                    )
-                }
-                .instrument(debug_span!("video_range")),
-            );
-        } else {
-            download_to_pipe(
+
+        self.backend
+            .stream_to_pipe(
                 video_write_fd,
-                self.yt_dlp_cfg.executable_path.as_ref(),
-                &video.url,
-                self.yt_pot_provider_cfg.url.as_ref(),
-                format.0.id,
-                self.limits_cfg.max_file_size,
-                cookie.as_ref(),
-            )
-            .map_err(Self::Err::Ytdlp)?;
-        }
-        if let Some(filesize) = format.1.filesize_or_approx() {
-            let (sender, mut receiver) = mpsc::unbounded_channel();
-
-            let url = format.1.url.to_owned();
-            tokio::spawn(
-                async move {
-                    tokio::join!(
-                        async move {
-                            let _ = range_download_to_write(url, filesize, sender)
-                                .await
-                                .inspect_err(|err| error!("{}", format_error_report(&err)));
-                        },
-                        async move {
-                            let mut writer = tokio::fs::File::from_std(File::from(audio_write_fd));
-                            while let Some(bytes) = receiver.recv().await {
-                                if let Err(err) = writer.write(&bytes).await {
-                                    match err.kind() {
-                                        io::ErrorKind::BrokenPipe => break,
-                                        _ => error!("{}", format_error_report(&err)),
-                                    }
-                                }
-                            }
-                        }
-                    )
-                }
-                .instrument(debug_span!("audio_range")),
-            );
-        } else {
-            download_to_pipe(
-                audio_write_fd,
-                self.yt_dlp_cfg.executable_path.as_ref(),
-                &video.url,
-                self.yt_pot_provider_cfg.url.as_ref(),
-                format.1.id,
-                self.limits_cfg.max_file_size,
-                cookie.as_ref(),
+                StreamRequest {
+                    yt_dlp_cfg: &yt_dlp_cfg,
+                    pot_provider_api_url: &yt_pot_provider_cfg.url,
+                    url: &video.url,
+                    format_id: &format.0.id,
+                    format_url: &format.0.url,
+                    filesize: format.0.filesize_or_approx(),
+                    max_file_size,
+                    cookie: cookie.as_ref(),
+                },
             )
+            .await
             .map_err(Self::Err::Ytdlp)?;
+
+        let mut audio = Vec::with_capacity(audio_tracks.len());
+        for track in audio_tracks {
+            let (audio_read_fd, audio_write_fd) = pipe().map_err(Self::Err::Pipe)?;
+            fcntl(&audio_write_fd, F_SETFD(FdFlag::FD_CLOEXEC)).map_err(Self::Err::Pipe)?;
+
+            self.backend
+                .stream_to_pipe(
+                    audio_write_fd,
+                    StreamRequest {
+                        yt_dlp_cfg: &yt_dlp_cfg,
+                        pot_provider_api_url: &yt_pot_provider_cfg.url,
+                        url: &video.url,
+                        format_id: &track.format.id,
+                        format_url: &track.format.url,
+                        filesize: track.format.filesize_or_approx(),
+                        max_file_size,
+                        cookie: cookie.as_ref(),
+                    },
+                )
+                .await
+                .map_err(Self::Err::Ytdlp)?;
+            audio.push(AudioPipe {
+                fd: audio_read_fd,
+                language: track.language,
+                default: track.default,
+            });
         }
 
-        let exit_code = match timeout(Duration::from_secs(DOWNLOAD_TIMEOUT), merge_child.wait()).await {
+        let merge = self.muxer.merge(video_read_fd, audio, extension, &file_path, max_file_size);
+        let exit_code = match timeout(Duration::from_secs(DOWNLOAD_TIMEOUT), merge).await {
             Ok(Ok(exit_code)) => exit_code,
             Ok(Err(err)) => {
                 return Err(Self::Err::Ffmpeg(err));
@@ -223,54 +258,167 @@ impl Interactor<DownloadInput> for &Download {
                 "FFmpeg exited with status `{exit_code}`"
             ))));
         }
+        truncation::check(&file_path, max_file_size, video.duration, expected_filesize).await?;
+        let file_path = if sponsorblock.is_empty() {
+            file_path
+        } else {
+            sponsorblock::apply(&sponsorblock_cfg.api_url, &video.id, &sponsorblock, file_path).await?
+        };
 
         info!("Video downloaded and merged");
         Ok(Self::Output::new(file_path, temp_dir))
     }
 }
 
-#[instrument(skip_all)]
-async fn range_download_to_write(url: impl AsRef<str>, filesize: f64, sender: UnboundedSender<Bytes>) -> Result<(), RangeErrorKind> {
-    let client = Client::new();
-    let url = url.as_ref();
-
-    let mut start = 0;
-    let mut end = RANGE_CHUNK_SIZE;
-
-    loop {
-        trace!(start, end, "Download chunk");
-
-        #[allow(clippy::cast_possible_truncation)]
-        if end >= filesize as i32 {
-            let mut stream = client
-                .get(url)
-                .header("Range", format!("bytes={start}-"))
-                .send()
-                .await?
-                .bytes_stream();
-
-            while let Some(chunk_res) = stream.next().await {
-                let chunk = chunk_res?;
-                sender.send(chunk)?;
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::backend::fake::{FakeBackend, FakeMuxer, Fixture, Step};
+    use std::{sync::Mutex, time::Duration};
 
-            break;
-        }
+    fn download<M>(fixture: &Fixture, backend: FakeBackend, muxer: M) -> Download<FakeBackend, M> {
+        Download::new(
+            Fixture::limits(),
+            Fixture::sponsorblock(),
+            Fixture::yt_dlp(),
+            Fixture::yt_pot_provider(),
+            fixture.scratch(),
+            Arc::new(backend),
+            Arc::new(muxer),
+        )
+    }
 
-        let mut stream = client
-            .get(url)
-            .header("Range", format!("bytes={start}-{end}"))
-            .send()
-            .await?
-            .bytes_stream();
+    fn input(video_id: &str, audio_id: &str) -> DownloadInput {
+        let format = format::Combined(Fixture::video_format(video_id), Fixture::audio_format(audio_id));
+        DownloadInput::new(Fixture::video(), format, vec![], None, false, SponsorBlock::default())
+    }
 
-        while let Some(chunk_res) = stream.next().await {
-            let chunk = chunk_res?;
-            sender.send(chunk)?;
-        }
+    #[tokio::test]
+    async fn test_download_single_format() {
+        let fixture = Fixture::default();
+        let backend = FakeBackend::default().script("18", [Step::Write(b"media")]);
+        let download = download(&fixture, backend, FakeMuxer::default());
+
+        let media = download.execute(input("18", "18")).await.unwrap();
+
+        assert_eq!(media.path.file_name().unwrap(), "id.mp4");
+        assert_eq!(std::fs::read(&media.path).unwrap(), b"media");
+    }
+
+    #[tokio::test]
+    async fn test_download_merges_streams() {
+        let fixture = Fixture::default();
+        let backend = FakeBackend::default()
+            .script(
+                "137",
+                [Step::Write(b"video"), Step::Delay(Duration::from_millis(10)), Step::Write(b"-end")],
+            )
+            .script("140", [Step::Write(b"audio")]);
+        let download = download(&fixture, backend, FakeMuxer::default());
+
+        let media = download.execute(input("137", "140")).await.unwrap();
+
+        assert_eq!(std::fs::read(&media.path).unwrap(), b"video-endaudio");
+    }
+
+    #[tokio::test]
+    async fn test_download_error() {
+        let fixture = Fixture::default();
+        let backend = FakeBackend::default().script("18", [Step::Write(b"me"), Step::Fail(io::ErrorKind::ConnectionReset)]);
+        let download = download(&fixture, backend, FakeMuxer::default());
+
+        let err = download.execute(input("18", "18")).await.unwrap_err();
+
+        assert!(matches!(err, ErrorKind::Ytdlp(err) if err.kind() == io::ErrorKind::ConnectionReset));
+    }
+
+    #[tokio::test]
+    async fn test_download_merges_audio_tracks() {
+        let fixture = Fixture::default();
+        let backend = FakeBackend::default()
+            .script("137", [Step::Write(b"video")])
+            .script("251-en", [Step::Delay(Duration::from_millis(10)), Step::Write(b"-en")])
+            .script("251-es", [Step::Write(b"-es")]);
+        let download = download(&fixture, backend, FakeMuxer::default());
+        let track = |id: &str, language: &str, default: bool| format::AudioTrack {
+            format: format::Audio {
+                codec: "opus".to_owned(),
+                ..Fixture::audio_format(id)
+            },
+            language: Some(language.to_owned()),
+            default,
+        };
+        let mut input = input("137", "251-en");
+        input.audio_tracks = vec![track("251-en", "en", true), track("251-es", "es", false)];
+
+        let media = download.execute(input).await.unwrap();
+
+        assert_eq!(std::fs::read(&media.path).unwrap(), b"video-en-es");
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_job_snapshot() {
+        let fixture = Fixture::default();
+        let yt_dlp_cfg = Fixture::yt_dlp();
+        let seen = Arc::new(Mutex::new(vec![]));
+        let backend = FakeBackend::default()
+            .script("137", [Step::Write(b"video")])
+            .script("140", [Step::Write(b"audio")])
+            .on_request({
+                let yt_dlp_cfg = yt_dlp_cfg.clone();
+                let seen = seen.clone();
+                move |format_id, cfg, _| {
+                    seen.lock().unwrap().push((format_id.to_owned(), cfg.executable_path.clone()));
+                    // SIGHUP between the video and the audio stream of the merge
+                    yt_dlp_cfg.store(config::YtDlp {
+                        executable_path: "reloaded".into(),
+                        ..(*yt_dlp_cfg.load()).clone()
+                    });
+                }
+            });
+        let download = Download::new(
+            Fixture::limits(),
+            Fixture::sponsorblock(),
+            yt_dlp_cfg.clone(),
+            Fixture::yt_pot_provider(),
+            fixture.scratch(),
+            Arc::new(backend),
+            Arc::new(FakeMuxer::default()),
+        );
+
+        download.execute(input("137", "140")).await.unwrap();
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [("137".to_owned(), "yt-dlp".into()), ("140".to_owned(), "yt-dlp".into())]
+        );
+        assert_eq!(&*yt_dlp_cfg.load().executable_path, "reloaded");
+    }
+
+    #[tokio::test]
+    async fn test_stream_not_started() {
+        let fixture = Fixture::default();
+        let backend = FakeBackend::default().script("137", [Step::Write(b"video")]);
+        let download = download(&fixture, backend, FakeMuxer::default());
+
+        let err = download.execute(input("137", "140")).await.unwrap_err();
+
+        assert!(matches!(err, ErrorKind::Ytdlp(err) if err.kind() == io::ErrorKind::NotFound));
+    }
+
+    #[tokio::test]
+    async fn test_merge_error() {
+        let fixture = Fixture::default();
+        let backend = FakeBackend::default()
+            .script("137", [Step::Write(b"video")])
+            .script("140", [Step::Write(b"audio")]);
+        let download = download(&fixture, backend, FakeMuxer::exit_code(1));
+
+        let err = download.execute(input("137", "140")).await.unwrap_err();
 
-        start = end + 1;
-        end += RANGE_CHUNK_SIZE;
+        assert!(matches!(err, ErrorKind::Ffmpeg(_)));
+        // Only the lock file is left in the instance dir
+        let instance_dir = std::fs::read_dir(fixture.root()).unwrap().next().unwrap().unwrap().path();
+        assert_eq!(std::fs::read_dir(instance_dir).unwrap().count(), 1);
     }
-    Ok(())
 }
@@ -0,0 +1,76 @@
+use std::{io, path::PathBuf};
+use tracing::{debug, instrument};
+
+use crate::{
+    adapters::ffprobe,
+    entities::MediaProperties,
+    interactors::Interactor,
+    utils::{hash::sha256_file, mp4::is_faststart, suggest_filename},
+    value_objects::Container,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ErrorKind {
+    #[error("Ffprobe error: {0}")]
+    Ffprobe(#[from] ffprobe::Error),
+    #[error("File error: {0}")]
+    File(#[from] io::Error),
+}
+
+pub struct Probe;
+
+pub struct ProbeInput {
+    path: PathBuf,
+    title: Option<String>,
+    part: Option<(u32, u32)>,
+}
+
+impl ProbeInput {
+    #[inline]
+    #[must_use]
+    pub const fn new(path: PathBuf, title: Option<String>, part: Option<(u32, u32)>) -> Self {
+        Self { path, title, part }
+    }
+}
+
+impl Interactor<ProbeInput> for &Probe {
+    type Output = MediaProperties;
+    type Err = ErrorKind;
+
+    #[instrument(skip_all, fields(path = %path.as_os_str().to_string_lossy()))]
+    async fn execute(self, ProbeInput { path, title, part }: ProbeInput) -> Result<Self::Output, Self::Err> {
+        let filesize = tokio::fs::metadata(&path).await?.len();
+        let probe = ffprobe::probe(&path).await?;
+
+        let extension = path.extension().unwrap_or_default().to_string_lossy().into_owned();
+        let container = Container::from_extension(&extension);
+        let video_stream = probe.video_stream();
+        let streamable = if container.supports_faststart() {
+            is_faststart(&mut tokio::fs::File::open(&path).await?).await?
+        } else {
+            false
+        };
+        let title = title.unwrap_or_else(|| path.file_stem().unwrap_or_default().to_string_lossy().into_owned());
+
+        let sha256 = sha256_file(&path).await?;
+
+        let properties = MediaProperties {
+            filesize,
+            duration: probe.duration(),
+            width: video_stream.and_then(|stream| stream.width),
+            height: video_stream.and_then(|stream| stream.height),
+            rotation: video_stream.and_then(ffprobe::Stream::rotation),
+            video_codec: probe.video_codec().map(ToOwned::to_owned),
+            audio_codec: probe.audio_codec().map(ToOwned::to_owned),
+            bitrate: probe.bitrate(),
+            container: container.as_str(),
+            mime_type: container.mime_type(video_stream.is_some()),
+            filename: suggest_filename(&title, part, &extension),
+            streamable,
+            sha256,
+        };
+        debug!(?properties, "Media probed");
+
+        Ok(properties)
+    }
+}
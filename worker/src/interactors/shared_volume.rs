@@ -0,0 +1,105 @@
+use std::{io, path::PathBuf, sync::Arc};
+use tracing::{info, instrument, warn};
+
+use crate::{adapters::shared_volume::SharedVolume, entities::Lease, interactors::Interactor};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ErrorKind {
+    #[error("Shared volume isn't configured")]
+    Disabled,
+    #[error("Shared volume error: {0}")]
+    Volume(io::Error),
+}
+
+/// Move the file to the shared volume under a new lease
+pub struct Publish {
+    volume: Arc<Option<SharedVolume>>,
+}
+
+impl Publish {
+    #[inline]
+    #[must_use]
+    pub const fn new(volume: Arc<Option<SharedVolume>>) -> Self {
+        Self { volume }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.volume.is_some()
+    }
+}
+
+pub struct PublishInput {
+    path: PathBuf,
+}
+
+impl PublishInput {
+    #[inline]
+    #[must_use]
+    pub const fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl Interactor<PublishInput> for &Publish {
+    type Output = Lease;
+    type Err = ErrorKind;
+
+    #[instrument(skip_all)]
+    async fn execute(self, PublishInput { path }: PublishInput) -> Result<Self::Output, Self::Err> {
+        let volume = self.volume.as_ref().as_ref().ok_or(Self::Err::Disabled)?;
+        let lease = volume.publish(&path).await.map_err(Self::Err::Volume)?;
+
+        info!(id = lease.id, expires_at = lease.expires_at, "File moved to the shared volume");
+        Ok(lease)
+    }
+}
+
+pub struct Release {
+    volume: Arc<Option<SharedVolume>>,
+}
+
+impl Release {
+    #[inline]
+    #[must_use]
+    pub const fn new(volume: Arc<Option<SharedVolume>>) -> Self {
+        Self { volume }
+    }
+}
+
+pub struct ReleaseInput {
+    lease_ids: Vec<String>,
+}
+
+impl ReleaseInput {
+    #[inline]
+    #[must_use]
+    pub const fn new(lease_ids: Vec<String>) -> Self {
+        Self { lease_ids }
+    }
+}
+
+impl Interactor<ReleaseInput> for &Release {
+    /// Number of the released leases
+    type Output = u32;
+    type Err = ErrorKind;
+
+    #[instrument(skip_all, fields(count = lease_ids.len()))]
+    async fn execute(self, ReleaseInput { lease_ids }: ReleaseInput) -> Result<Self::Output, Self::Err> {
+        let volume = self.volume.as_ref().as_ref().ok_or(Self::Err::Disabled)?;
+
+        let mut released = 0;
+        for id in &lease_ids {
+            match volume.release(id).await {
+                Ok(true) => released += 1,
+                Ok(false) => {}
+                Err(err) if err.kind() == io::ErrorKind::InvalidInput => warn!(%id, "Skip invalid lease id"),
+                Err(err) => return Err(Self::Err::Volume(err)),
+            }
+        }
+
+        info!(released, "Leases released");
+        Ok(released)
+    }
+}
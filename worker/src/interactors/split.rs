@@ -0,0 +1,140 @@
+use std::{io, path::Path, sync::Arc};
+use tracing::{debug, info, instrument, warn};
+
+use crate::{
+    adapters::{ffmpeg::split_into_segments, ffprobe},
+    config::{self, Reloadable},
+    entities::{MediaInFS, MediaPart, MediaPartsInFS},
+    interactors::Interactor,
+};
+
+const SPLIT_TIMEOUT: u64 = 360;
+const SPLIT_ATTEMPTS: u8 = 3;
+/// Parts are cut on keyframes after the segment time, so leave some room for them
+const SEGMENT_SIZE_RATIO: f64 = 0.9;
+const SEGMENT_SIZE_RATIO_STEP: f64 = 0.75;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ErrorKind {
+    #[error("Ffmpeg error: {0}")]
+    Ffmpeg(io::Error),
+    #[error("Ffprobe error: {0}")]
+    Ffprobe(#[from] ffprobe::Error),
+    #[error("File error: {0}")]
+    File(io::Error),
+    #[error("Media duration is unknown")]
+    UnknownDuration,
+    #[error("Failed to split media into parts less than {max_file_size} bytes")]
+    PartTooLarge { max_file_size: u32 },
+}
+
+pub struct Split {
+    limits_cfg: Arc<Reloadable<config::Limits>>,
+}
+
+impl Split {
+    #[inline]
+    #[must_use]
+    pub const fn new(limits_cfg: Arc<Reloadable<config::Limits>>) -> Self {
+        Self { limits_cfg }
+    }
+}
+
+pub struct SplitInput {
+    media: MediaInFS,
+}
+
+impl SplitInput {
+    #[inline]
+    #[must_use]
+    pub const fn new(media: MediaInFS) -> Self {
+        Self { media }
+    }
+}
+
+impl Interactor<SplitInput> for &Split {
+    type Output = MediaPartsInFS;
+    type Err = ErrorKind;
+
+    #[instrument(skip_all, fields(path = %media.path.as_os_str().to_string_lossy()))]
+    async fn execute(self, SplitInput { media }: SplitInput) -> Result<Self::Output, Self::Err> {
+        let limits_cfg = self.limits_cfg.load();
+        let MediaInFS { path, temp_dir } = media;
+        let max_file_size = limits_cfg.max_file_size;
+        let filesize = file_size(&path).await?;
+
+        let duration = ffprobe::probe(&path).await?.duration().ok_or(Self::Err::UnknownDuration)?;
+        if filesize <= u64::from(max_file_size) {
+            debug!(filesize, "Media fits the limit");
+            return Ok(Self::Output::new(vec![MediaPart::new(path, 0.0, duration)], temp_dir));
+        }
+
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut ratio = SEGMENT_SIZE_RATIO;
+        for attempt in 1..=SPLIT_ATTEMPTS {
+            let segment_time = calculate_segment_time(duration, filesize, max_file_size, ratio);
+            debug!(attempt, segment_time, "Split media");
+
+            let parts = split_into_segments(&path, &extension, segment_time, SPLIT_TIMEOUT)
+                .await
+                .map_err(Self::Err::Ffmpeg)?;
+
+            let mut fits = true;
+            for part in &parts {
+                if file_size(&part.path).await? > u64::from(max_file_size) {
+                    fits = false;
+                    break;
+                }
+            }
+            if fits && !parts.is_empty() {
+                let _ = tokio::fs::remove_file(&path).await;
+
+                info!(count = parts.len(), "Media split");
+                return Ok(Self::Output::new(parts, temp_dir));
+            }
+
+            warn!(attempt, "Some parts exceed the limit");
+            for part in parts {
+                let _ = tokio::fs::remove_file(part.path).await;
+            }
+            ratio *= SEGMENT_SIZE_RATIO_STEP;
+        }
+
+        Err(Self::Err::PartTooLarge { max_file_size })
+    }
+}
+
+async fn file_size(path: impl AsRef<Path>) -> Result<u64, ErrorKind> {
+    tokio::fs::metadata(path)
+        .await
+        .map(|metadata| metadata.len())
+        .map_err(ErrorKind::File)
+}
+
+/// Calculate the segment duration that gives parts of about `max_file_size * ratio` bytes,
+/// assuming the bitrate is constant.
+#[allow(clippy::cast_precision_loss)]
+fn calculate_segment_time(duration: f64, filesize: u64, max_file_size: u32, ratio: f64) -> f64 {
+    duration * f64::from(max_file_size) / filesize as f64 * ratio
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calculate_segment_time() {
+        let segment_time = calculate_segment_time(3600.0, 300, 100, 1.0);
+        assert!((segment_time - 1200.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_calculate_segment_time_with_ratio() {
+        let segment_time = calculate_segment_time(3600.0, 300, 100, SEGMENT_SIZE_RATIO);
+        assert!((segment_time - 1080.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,87 @@
+use std::{io, sync::Arc};
+use tracing::{debug, info, instrument};
+
+use crate::{
+    adapters::{ffmpeg::convert_to_streamable, ffprobe},
+    config::{self, Reloadable},
+    entities::MediaInFS,
+    interactors::{Interactor, download::truncation},
+    value_objects::{AudioCodec, VideoCodec},
+};
+
+const CONVERT_TIMEOUT: u64 = 600;
+const VIDEO_CODEC: &str = "libx264";
+const AUDIO_CODEC: &str = "aac";
+
+#[derive(thiserror::Error, Debug)]
+pub enum ErrorKind {
+    #[error("Ffmpeg error: {0}")]
+    Ffmpeg(io::Error),
+    #[error("Ffprobe error: {0}")]
+    Ffprobe(#[from] ffprobe::Error),
+    #[error(transparent)]
+    Truncation(#[from] truncation::ErrorKind),
+}
+
+/// Convert the media to `mp4` that Telegram clients can play inline while it's downloading
+pub struct Convert {
+    limits_cfg: Arc<Reloadable<config::Limits>>,
+}
+
+impl Convert {
+    #[inline]
+    #[must_use]
+    pub const fn new(limits_cfg: Arc<Reloadable<config::Limits>>) -> Self {
+        Self { limits_cfg }
+    }
+}
+
+pub struct ConvertInput {
+    media: MediaInFS,
+    split: bool,
+}
+
+impl ConvertInput {
+    #[inline]
+    #[must_use]
+    pub const fn new(media: MediaInFS, split: bool) -> Self {
+        Self { media, split }
+    }
+}
+
+impl Interactor<ConvertInput> for &Convert {
+    type Output = MediaInFS;
+    type Err = ErrorKind;
+
+    #[instrument(skip_all, fields(path = %media.path.as_os_str().to_string_lossy()))]
+    async fn execute(self, ConvertInput { media, split }: ConvertInput) -> Result<Self::Output, Self::Err> {
+        let limits_cfg = self.limits_cfg.load();
+        let MediaInFS { path, temp_dir } = media;
+        let max_file_size = limits_cfg.max_source_file_size(split);
+
+        let probe = ffprobe::probe(&path).await?;
+        let video_codec = probe
+            .video_codec()
+            .map(VideoCodec::from_ffprobe)
+            .filter(|codec| !codec.is_telegram_compatible())
+            .map(|_| VIDEO_CODEC);
+        let audio_codec = probe
+            .audio_codec()
+            .map(AudioCodec::from_ffprobe)
+            .filter(|codec| !codec.is_telegram_compatible())
+            .map(|_| AUDIO_CODEC);
+        debug!(?video_codec, ?audio_codec, "Streams to transcode");
+
+        let stem = path.file_stem().map_or_else(|| "media".into(), |stem| stem.to_string_lossy());
+        let output_path = temp_dir.path().join(format!("{stem}.streamable.mp4"));
+
+        convert_to_streamable(&path, &output_path, video_codec, audio_codec, max_file_size, CONVERT_TIMEOUT)
+            .await
+            .map_err(Self::Err::Ffmpeg)?;
+        truncation::check(&output_path, max_file_size, probe.duration(), None).await?;
+        let _ = tokio::fs::remove_file(&path).await;
+
+        info!("Media converted");
+        Ok(Self::Output::new(output_path, temp_dir))
+    }
+}
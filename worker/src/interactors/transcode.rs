@@ -0,0 +1,196 @@
+use std::{io, sync::Arc};
+use tracing::{debug, info, instrument};
+
+use crate::{
+    adapters::{
+        ffmpeg::{compress, convert_to_video_note, convert_to_voice, extract_audio},
+        ffprobe,
+    },
+    config::{self, Reloadable},
+    entities::MediaInFS,
+    interactors::{Interactor, download::truncation},
+    value_objects::AudioCodec,
+};
+
+const CONVERT_TIMEOUT: u64 = 600;
+const AUDIO_CODEC: &str = "aac";
+/// Voice messages are cut at this duration in seconds
+const VOICE_MAX_DURATION: u32 = 3600;
+/// Telegram limits of the video notes
+const VIDEO_NOTE_MAX_DURATION: u32 = 60;
+const VIDEO_NOTE_SIDE: u32 = 640;
+/// Part of the target size reserved for the container overhead and the bitrate deviations
+const COMPRESS_SIZE_MARGIN: f64 = 0.05;
+const COMPRESS_AUDIO_BITRATE: u64 = 128_000;
+const COMPRESS_LOW_AUDIO_BITRATE: u64 = 64_000;
+const COMPRESS_MIN_VIDEO_BITRATE: u64 = 100_000;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ErrorKind {
+    #[error("Ffmpeg error: {0}")]
+    Ffmpeg(io::Error),
+    #[error("Ffprobe error: {0}")]
+    Ffprobe(#[from] ffprobe::Error),
+    #[error(transparent)]
+    Truncation(#[from] truncation::ErrorKind),
+    #[error("Media has no audio stream")]
+    NoAudio,
+    #[error("Media has no video stream")]
+    NoVideo,
+    #[error("Media duration is unknown")]
+    NoDuration,
+    #[error("Target size {target_size} bytes is too small for the duration")]
+    TargetTooSmall { target_size: u64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// First audio stream in `m4a`
+    ExtractAudio,
+    /// `mp4` that fits the target size, the max file size is used if it's unset or larger
+    Compress { target_size: Option<u64> },
+    /// Mono `OGG/Opus` voice message of at most an hour
+    Voice,
+    /// Square `mp4` video note
+    VideoNote,
+}
+
+/// Convert the media with one of the operations that don't depend on its source
+pub struct Transcode {
+    limits_cfg: Arc<Reloadable<config::Limits>>,
+}
+
+impl Transcode {
+    #[inline]
+    #[must_use]
+    pub const fn new(limits_cfg: Arc<Reloadable<config::Limits>>) -> Self {
+        Self { limits_cfg }
+    }
+}
+
+pub struct TranscodeInput {
+    media: MediaInFS,
+    operation: Operation,
+}
+
+impl TranscodeInput {
+    #[inline]
+    #[must_use]
+    pub const fn new(media: MediaInFS, operation: Operation) -> Self {
+        Self { media, operation }
+    }
+}
+
+impl Interactor<TranscodeInput> for &Transcode {
+    type Output = MediaInFS;
+    type Err = ErrorKind;
+
+    #[instrument(skip_all, fields(path = %media.path.as_os_str().to_string_lossy(), ?operation))]
+    async fn execute(self, TranscodeInput { media, operation }: TranscodeInput) -> Result<Self::Output, Self::Err> {
+        let limits_cfg = self.limits_cfg.load();
+        let MediaInFS { path, temp_dir } = media;
+        let max_file_size = limits_cfg.max_file_size;
+
+        let probe = ffprobe::probe(&path).await?;
+        let mut expected_duration = probe.duration();
+        let stem = path.file_stem().map_or_else(|| "media".into(), |stem| stem.to_string_lossy());
+
+        let output_path = match operation {
+            Operation::ExtractAudio => {
+                let audio_codec = probe.audio_codec().ok_or(Self::Err::NoAudio)?;
+                let audio_codec = (AudioCodec::from_ffprobe(audio_codec) != AudioCodec::Aac).then_some(AUDIO_CODEC);
+                debug!(?audio_codec, "Audio to transcode");
+
+                let output_path = temp_dir.path().join(format!("{stem}.audio.m4a"));
+                extract_audio(&path, &output_path, audio_codec, max_file_size, CONVERT_TIMEOUT)
+                    .await
+                    .map_err(Self::Err::Ffmpeg)?;
+                output_path
+            }
+            Operation::Compress { target_size } => {
+                if probe.video_stream().is_none() {
+                    return Err(Self::Err::NoVideo);
+                }
+                let duration = expected_duration.ok_or(Self::Err::NoDuration)?;
+                let target_size = target_size.map_or(u64::from(max_file_size), |size| size.min(u64::from(max_file_size)));
+                let (video_bitrate, audio_bitrate) =
+                    compress_bitrates(target_size, duration).ok_or(Self::Err::TargetTooSmall { target_size })?;
+                debug!(target_size, video_bitrate, audio_bitrate, "Bitrates to compress");
+
+                let output_path = temp_dir.path().join(format!("{stem}.compressed.mp4"));
+                compress(&path, &output_path, video_bitrate, audio_bitrate, max_file_size, CONVERT_TIMEOUT)
+                    .await
+                    .map_err(Self::Err::Ffmpeg)?;
+                output_path
+            }
+            Operation::Voice => {
+                if probe.audio_stream().is_none() {
+                    return Err(Self::Err::NoAudio);
+                }
+                expected_duration = expected_duration.map(|duration| duration.min(f64::from(VOICE_MAX_DURATION)));
+
+                let output_path = temp_dir.path().join(format!("{stem}.voice.ogg"));
+                convert_to_voice(&path, &output_path, VOICE_MAX_DURATION, max_file_size, CONVERT_TIMEOUT)
+                    .await
+                    .map_err(Self::Err::Ffmpeg)?;
+                output_path
+            }
+            Operation::VideoNote => {
+                if probe.video_stream().is_none() {
+                    return Err(Self::Err::NoVideo);
+                }
+                expected_duration = expected_duration.map(|duration| duration.min(f64::from(VIDEO_NOTE_MAX_DURATION)));
+
+                let output_path = temp_dir.path().join(format!("{stem}.video_note.mp4"));
+                convert_to_video_note(
+                    &path,
+                    &output_path,
+                    VIDEO_NOTE_MAX_DURATION,
+                    VIDEO_NOTE_SIDE,
+                    max_file_size,
+                    CONVERT_TIMEOUT,
+                )
+                .await
+                .map_err(Self::Err::Ffmpeg)?;
+                output_path
+            }
+        };
+        truncation::check(&output_path, max_file_size, expected_duration, None).await?;
+        let _ = tokio::fs::remove_file(&path).await;
+
+        info!("Media transcoded");
+        Ok(Self::Output::new(output_path, temp_dir))
+    }
+}
+
+/// Video and audio bitrates in bits per second, so the output of the duration fits the target size.
+/// Returns `None` if the video bitrate is too low to be watchable.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+fn compress_bitrates(target_size: u64, duration: f64) -> Option<(u64, u64)> {
+    if duration <= 0.0 {
+        return None;
+    }
+    let total_bitrate = (target_size as f64 * 8.0 * (1.0 - COMPRESS_SIZE_MARGIN) / duration) as u64;
+    let audio_bitrate = if total_bitrate >= COMPRESS_AUDIO_BITRATE * 4 {
+        COMPRESS_AUDIO_BITRATE
+    } else {
+        COMPRESS_LOW_AUDIO_BITRATE
+    };
+    let video_bitrate = total_bitrate.checked_sub(audio_bitrate)?;
+    (video_bitrate >= COMPRESS_MIN_VIDEO_BITRATE).then_some((video_bitrate, audio_bitrate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_bitrates() {
+        // 50 MB for 10 minutes
+        assert_eq!(compress_bitrates(50_000_000, 600.0), Some((505_333, 128_000)));
+        // 5 MB for 3 minutes
+        assert_eq!(compress_bitrates(5_000_000, 180.0), Some((147_111, 64_000)));
+        assert_eq!(compress_bitrates(1_000_000, 600.0), None);
+        assert_eq!(compress_bitrates(1_000_000, 0.0), None);
+    }
+}
@@ -0,0 +1,165 @@
+use std::{fmt::Display, io, path::Path, sync::Arc};
+use tokio::io::AsyncWriteExt as _;
+use tokio_stream::{Stream, StreamExt as _};
+use tracing::{debug, info, instrument};
+
+use crate::{
+    adapters::scratch::{self, Scratch},
+    config::{self, Reloadable},
+    entities::MediaInFS,
+    interactors::Interactor,
+};
+
+/// Extension of the uploaded file is kept only if it looks like one, `ffmpeg` probes the content anyway
+const MAX_EXTENSION_LEN: usize = 5;
+const DEFAULT_EXTENSION: &str = "bin";
+
+#[derive(thiserror::Error, Debug)]
+pub enum ErrorKind {
+    #[error("File is too large, size is {filesize} bytes")]
+    TooLarge { filesize: u64 },
+    #[error("Received {received} bytes instead of {filesize}")]
+    SizeMismatch { filesize: u64, received: u64 },
+    #[error("Stream error: {0}")]
+    Stream(String),
+    #[error("File error: {0}")]
+    File(io::Error),
+    #[error(transparent)]
+    Scratch(#[from] scratch::Error),
+}
+
+/// Receive the file uploaded by the client into a work dir
+pub struct Receive {
+    limits_cfg: Arc<Reloadable<config::Limits>>,
+    scratch: Arc<Scratch>,
+}
+
+impl Receive {
+    #[inline]
+    #[must_use]
+    pub const fn new(limits_cfg: Arc<Reloadable<config::Limits>>, scratch: Arc<Scratch>) -> Self {
+        Self { limits_cfg, scratch }
+    }
+}
+
+pub struct ReceiveInput<S> {
+    filename: String,
+    filesize: u64,
+    chunks: S,
+}
+
+impl<S> ReceiveInput<S> {
+    #[inline]
+    #[must_use]
+    pub const fn new(filename: String, filesize: u64, chunks: S) -> Self {
+        Self {
+            filename,
+            filesize,
+            chunks,
+        }
+    }
+}
+
+impl<S, E> Interactor<ReceiveInput<S>> for &Receive
+where
+    S: Stream<Item = Result<Vec<u8>, E>> + Unpin,
+    E: Display,
+{
+    type Output = MediaInFS;
+    type Err = ErrorKind;
+
+    #[instrument(skip_all, fields(%filename, filesize))]
+    async fn execute(
+        self,
+        ReceiveInput {
+            filename,
+            filesize,
+            mut chunks,
+        }: ReceiveInput<S>,
+    ) -> Result<Self::Output, Self::Err> {
+        let max_file_size = u64::from(self.limits_cfg.load().max_file_size);
+        if filesize > max_file_size {
+            return Err(Self::Err::TooLarge { filesize });
+        }
+
+        // Space for the upload and the converted output
+        let temp_dir = self.scratch.work_dir(Some(filesize.saturating_mul(2)))?;
+        let path = temp_dir.path().join(format!("upload.{}", extension(&filename)));
+        let mut file = tokio::fs::File::create(&path).await.map_err(Self::Err::File)?;
+
+        let mut received = 0u64;
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk.map_err(|err| Self::Err::Stream(err.to_string()))?;
+            received += chunk.len() as u64;
+            if received > filesize {
+                return Err(Self::Err::SizeMismatch { filesize, received });
+            }
+            file.write_all(&chunk).await.map_err(Self::Err::File)?;
+        }
+        if received != filesize {
+            return Err(Self::Err::SizeMismatch { filesize, received });
+        }
+        file.flush().await.map_err(Self::Err::File)?;
+        debug!(received, "Upload written");
+
+        info!("File received");
+        Ok(Self::Output::new(path, temp_dir))
+    }
+}
+
+fn extension(filename: &str) -> String {
+    Path::new(filename)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .filter(|extension| extension.len() <= MAX_EXTENSION_LEN && extension.chars().all(|char| char.is_ascii_alphanumeric()))
+        .unwrap_or_else(|| DEFAULT_EXTENSION.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension() {
+        assert_eq!(extension("clip.MP4"), "mp4");
+        assert_eq!(extension("song.final.m4a"), "m4a");
+        assert_eq!(extension("noext"), "bin");
+        assert_eq!(extension("../../etc/passwd"), "bin");
+        assert_eq!(extension("file.a/b"), "bin");
+        assert_eq!(extension("file.toolongext"), "bin");
+    }
+
+    #[tokio::test]
+    async fn test_receive() {
+        let root = tempfile::tempdir().unwrap();
+        let receive = Receive::new(
+            Arc::new(Reloadable::new(config::Limits {
+                max_file_size: 8,
+                max_split_file_size: None,
+            })),
+            Arc::new(Scratch::new(Some(root.path()), None, 0, 0).unwrap()),
+        );
+        let chunks =
+            |chunks: &[&[u8]]| tokio_stream::iter(chunks.iter().map(|chunk| Ok::<_, io::Error>(chunk.to_vec())).collect::<Vec<_>>());
+
+        let media = receive
+            .execute(ReceiveInput::new("clip.mp4".into(), 6, chunks(&[b"abc", b"def"])))
+            .await
+            .unwrap();
+        assert_eq!(media.path.extension().unwrap(), "mp4");
+        assert_eq!(tokio::fs::read(&media.path).await.unwrap(), b"abcdef");
+
+        assert!(matches!(
+            receive.execute(ReceiveInput::new("clip.mp4".into(), 9, chunks(&[]))).await,
+            Err(ErrorKind::TooLarge { filesize: 9 })
+        ));
+        assert!(matches!(
+            receive.execute(ReceiveInput::new("clip.mp4".into(), 4, chunks(&[b"abc"]))).await,
+            Err(ErrorKind::SizeMismatch { filesize: 4, received: 3 })
+        ));
+        assert!(matches!(
+            receive.execute(ReceiveInput::new("clip.mp4".into(), 2, chunks(&[b"abc"]))).await,
+            Err(ErrorKind::SizeMismatch { filesize: 2, received: 3 })
+        ));
+    }
+}
@@ -0,0 +1,46 @@
+use std::{io, path::PathBuf};
+use tracing::{debug, instrument};
+
+use crate::{adapters::ffmpeg::decode_pcm, interactors::Interactor, utils::waveform::Peaks};
+
+const DECODE_TIMEOUT: u64 = 120;
+const SAMPLE_RATE: u32 = 8000;
+/// 10 ms blocks
+const BLOCK_SIZE: usize = SAMPLE_RATE as usize / 100;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ErrorKind {
+    #[error("Ffmpeg error: {0}")]
+    Ffmpeg(#[from] io::Error),
+}
+
+/// Compute the packed 5-bit waveform of the audio that Telegram shows for voice messages.
+/// MTProto clients pass it when uploading, the Bot API computes its own
+pub struct Compute;
+
+pub struct ComputeInput {
+    path: PathBuf,
+}
+
+impl ComputeInput {
+    #[inline]
+    #[must_use]
+    pub const fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl Interactor<ComputeInput> for &Compute {
+    type Output = Vec<u8>;
+    type Err = ErrorKind;
+
+    #[instrument(skip_all, fields(path = %path.as_os_str().to_string_lossy()))]
+    async fn execute(self, ComputeInput { path }: ComputeInput) -> Result<Self::Output, Self::Err> {
+        let mut peaks = Peaks::new(BLOCK_SIZE);
+        decode_pcm(&path, SAMPLE_RATE, DECODE_TIMEOUT, |chunk| peaks.push_pcm(chunk)).await?;
+
+        let waveform = peaks.waveform();
+        debug!(len = waveform.len(), "Waveform computed");
+        Ok(waveform)
+    }
+}
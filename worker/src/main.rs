@@ -1,20 +1,33 @@
+use axum::{middleware::from_fn, routing::get};
 use froodi::axum::setup_async_default;
-use std::net::SocketAddr;
-use tokio::sync::broadcast::{Receiver, Sender, channel};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::{
+    sync::broadcast::{Receiver, Sender, channel},
+    time::interval,
+};
 use tonic::{
-    service::Routes,
-    transport::{self, Server},
+    service::{InterceptorLayer, Routes},
+    transport::{self, Server, ServerTlsConfig},
 };
 use tracing::info;
-use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt as _, util::SubscriberInitExt as _};
 
 use crate::{
-    config::{Config, Version, get_config_path},
-    presentation::grpc::{
-        api::version::{self, VersionServiceServer},
-        test::{self, EchoServiceServer},
+    adapters::{cache::MediaCache, rate_limiter::RateLimiter, scratch::Scratch, shared_volume::SharedVolume},
+    config::{Config, ReloadableSections, Version, get_config_path},
+    presentation::{
+        grpc::{
+            api::version::{self, VersionServiceServer},
+            auth::{self, Auth},
+            test::{self, EchoServiceServer},
+            trace_context,
+        },
+        http::metrics,
     },
-    signal::shutdown_signal,
+    signal::{reload_on_hangup, shutdown_signal},
 };
 
 mod di_container;
@@ -28,38 +41,97 @@ pub mod presentation;
 pub mod utils;
 pub mod value_objects;
 
+const SHARED_VOLUME_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> Result<(), anyhow::Error> {
     let config_path = &*get_config_path();
     let config = Config::from_fs(config_path)?;
     let version = Version::from_env("CARGO_PKG_VERSION")?;
 
-    tracing_subscriber::registry()
-        .with(fmt::layer())
-        .with(EnvFilter::builder().parse_lossy(config.logging.dirs.as_ref()))
-        .init();
+    let tracer_provider = telemetry::init("worker", &config.logging.dirs, config.tracing.as_ref())?;
 
+    adapters::metrics::init();
     let addr = format!("{}:{}", config.server.host, config.server.port).parse()?;
-    info!("Listening on {addr}. Version: {version}");
+    let tls = config.server.tls.as_ref().map(auth::tls_config).transpose()?;
+    let auth = Auth::new(&config.auth);
+    info!(
+        tls = tls.is_some(),
+        mutual_tls = config.server.tls.as_ref().is_some_and(|tls| tls.client_ca.is_some()),
+        token_auth = !config.auth.clients.is_empty(),
+        "Listening on {addr}. Version: {version}"
+    );
 
-    let container = di_container::init(config, version);
+    let cache = config
+        .cache
+        .as_ref()
+        .map(|cache| MediaCache::open(cache.dir.as_ref(), cache.max_size))
+        .transpose()?;
+    let scratch = Scratch::new(
+        config.scratch.dir.as_deref().map(Path::new),
+        config.scratch.tmpfs_dir.as_deref().map(Path::new),
+        config.scratch.tmpfs_max_size,
+        config.scratch.reserve,
+    )?;
+    tokio::spawn(sweep_scratch(
+        scratch.clone(),
+        Duration::from_secs(config.scratch.max_age),
+        Duration::from_secs(config.scratch.sweep_interval),
+    ));
+    let shared_volume = config
+        .shared_volume
+        .as_ref()
+        .map(|volume| {
+            SharedVolume::new(
+                volume.dir.as_ref(),
+                volume.public_dir.as_deref().map(PathBuf::from),
+                Duration::from_secs(volume.ttl),
+            )
+        })
+        .transpose()?;
+    if let Some(volume) = shared_volume.clone() {
+        tokio::spawn(sweep_shared_volume(volume));
+    }
+    let rate_limiter = RateLimiter::new(config.rate_limit.clone());
+    let reloadable = ReloadableSections::new(&config);
+    tokio::spawn(reload_on_hangup(config_path.into(), reloadable.clone()));
+    let container = di_container::init(config, reloadable, version, cache, scratch, shared_volume, rate_limiter);
 
     let routes = Routes::default()
         .add_service(EchoServiceServer::new(test::Service))
         .add_service(VersionServiceServer::new(version::Service));
-    let router = setup_async_default(routes.into_axum_router(), container);
+    // Interceptor is applied to the gRPC routes only, the metrics are scraped without a token
+    let router = routes
+        .into_axum_router()
+        .layer(InterceptorLayer::new(auth))
+        .layer(from_fn(trace_context::extract))
+        .route("/metrics", get(metrics::handler));
+    let router = setup_async_default(router, container);
 
     let (shutdown_tx, _) = channel(1);
 
     let (err, _) = tokio::join!(
-        tokio::spawn(run_server(router.into(), addr, shutdown_tx.subscribe())),
+        tokio::spawn(run_server(router.into(), addr, tls, shutdown_tx.subscribe())),
         tokio::spawn(handle_shutdown(shutdown_tx))
     );
+    if let Some(tracer_provider) = tracer_provider {
+        let _ = tracer_provider.shutdown();
+    }
     err.unwrap().map_err(Into::into)
 }
 
-async fn run_server(routes: Routes, addr: SocketAddr, mut shutdown_rx: Receiver<()>) -> Result<(), transport::Error> {
-    Server::builder()
+async fn run_server(
+    routes: Routes,
+    addr: SocketAddr,
+    tls: Option<ServerTlsConfig>,
+    mut shutdown_rx: Receiver<()>,
+) -> Result<(), transport::Error> {
+    // Prometheus scrapes the metrics over HTTP/1.1
+    let mut builder = Server::builder().accept_http1(true);
+    if let Some(tls) = tls {
+        builder = builder.tls_config(tls)?;
+    }
+    builder
         .add_routes(routes)
         .serve_with_shutdown(addr, async move {
             let _ = shutdown_rx.recv().await;
@@ -67,6 +139,26 @@ async fn run_server(routes: Routes, addr: SocketAddr, mut shutdown_rx: Receiver<
         .await
 }
 
+/// Remove the work dirs left by crashed workers, the first sweep happens at startup
+async fn sweep_scratch(scratch: Scratch, max_age: Duration, period: Duration) {
+    let mut interval = interval(period);
+    loop {
+        interval.tick().await;
+        let scratch = scratch.clone();
+        let _ = tokio::task::spawn_blocking(move || scratch.sweep(max_age)).await;
+    }
+}
+
+/// Remove the files of the expired leases, the first sweep happens at startup
+async fn sweep_shared_volume(volume: SharedVolume) {
+    let mut interval = interval(SHARED_VOLUME_SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+        let volume = volume.clone();
+        let _ = tokio::task::spawn_blocking(move || volume.sweep_expired()).await;
+    }
+}
+
 async fn handle_shutdown(shutdown_tx: Sender<()>) {
     let () = shutdown_signal().await;
     let _ = shutdown_tx.send(());
@@ -1,4 +1,6 @@
 pub(super) mod utils;
 
 pub mod api;
+pub mod auth;
 pub mod test;
+pub mod trace_context;
@@ -1,2 +1,3 @@
 pub mod download;
 pub mod limits;
+pub mod media;
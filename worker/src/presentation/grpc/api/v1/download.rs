@@ -1,77 +1,204 @@
+#[allow(clippy::large_enum_variant)]
 mod generated {
     tonic::include_proto!("worker.api.v1");
 }
+use froodi::async_impl::Container;
 pub use generated::download_service_server::DownloadServiceServer;
 use generated::{
-    AudioFormat, DownloadAudioRequest, DownloadAudioResponse, DownloadThumbnailRequest, DownloadThumbnailResponse, DownloadVideoRequest,
-    DownloadVideoResponse, FileHeader, Video, VideoFormat, download_audio_response, download_service_server::DownloadService,
-    download_thumbnail_response, download_video_response,
+    AudioFormat, AudioOutputMode, ConvertOperation, ConvertRequest, ConvertResponse, DeliveryMode, DownloadAudioRequest,
+    DownloadAudioResponse, DownloadDirectRequest, DownloadDirectResponse, DownloadGalleryRequest, DownloadGalleryResponse,
+    DownloadThumbnailRequest, DownloadThumbnailResponse, DownloadVideoRequest, DownloadVideoResponse, ErrorCode, ErrorDetails, FileHeader,
+    FilePart, GalleryItem, LiveStatus, MediaKind, ReleaseRequest, ReleaseResponse, SharedFile, SponsorBlock, SponsorBlockCategory, Video,
+    VideoFormat, VideoOutputMode, convert_request, convert_response, download_audio_response, download_direct_response,
+    download_gallery_response, download_service_server::DownloadService, download_thumbnail_response, download_video_response,
 };
-use tokio::io::AsyncReadExt as _;
+use prost::Message as _;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tempfile::TempDir;
+use tokio::{io::AsyncReadExt as _, sync::mpsc::Sender};
+use tokio_stream::StreamExt as _;
 use tokio_stream::wrappers::ReceiverStream;
-use tonic::{Request, Response, Status, async_trait};
-use tracing::error;
+use tonic::{Code, Request, Response, Status, Streaming, async_trait};
+use tracing::{error, instrument, warn};
 
 use crate::{
-    entities::{self, MediaInFS, Thumbnail, format::Combined},
+    adapters::{
+        direct,
+        metrics::{self, InFlight, domain_label},
+        scratch,
+    },
+    entities::{
+        self, CacheKey, DirectMediaInFS, GalleryInFS, MediaInFS, MediaPartsInFS, MediaProperties, Thumbnail, TracksInFS, format::Combined,
+    },
     impl_from_format,
     interactors::{
-        Interactor as _,
-        download::{audio, thumbnail, video},
+        Interactor as _, animation, cache, chapters,
+        download::{audio, direct as direct_download, gallery, live, thumbnail, truncation, video},
+        probe, shared_volume, split, streamable, transcode, upload, waveform,
     },
     presentation::grpc::{
         api::v1::download::generated::FileChunk,
+        auth,
         utils::{di_container, parse::required_field},
     },
+    utils::{
+        hash::sha256_file,
+        suggest_filename,
+        url::{MediaIdentity, canonicalize},
+    },
+    value_objects::{self, AnimationFormat},
 };
 
 const CHUNK_SIZE_BYTES: u64 = 64 * 1024;
 const CHANNEL_BUFFER_SIZE: usize = 512;
+const MAX_GALLERY_ITEMS: u32 = 50;
 
 trait StreamResponse: Send + 'static {
     type Message;
+    /// Label of the streamed bytes metric
+    const KIND: &'static str;
 
-    fn with_header(filesize: u64) -> Self;
+    fn with_header(header: FileHeader) -> Self;
     fn with_chunk(content: Vec<u8>) -> Self;
 }
 
-async fn create_file_stream<R>(MediaInFS { path, temp_dir }: MediaInFS) -> Result<ReceiverStream<Result<R, Status>>, Status>
+/// Where the finished files are delivered
+enum Delivery {
+    /// Content is sent in chunks after the header
+    Stream,
+    /// Files are moved to the shared volume, only the headers are sent
+    SharedVolume(Arc<shared_volume::Publish>),
+}
+
+/// Operation of the convert request, checked before the upload is received
+enum Conversion {
+    Remux,
+    Animation,
+    Transcode(transcode::Operation),
+}
+
+impl Conversion {
+    fn new(operation: ConvertOperation, target_size: Option<u64>) -> Result<Self, Status> {
+        Ok(match operation {
+            ConvertOperation::Unspecified => return Err(Status::invalid_argument("Operation is required")),
+            ConvertOperation::Remux => Self::Remux,
+            ConvertOperation::Animation => Self::Animation,
+            ConvertOperation::ExtractAudio => Self::Transcode(transcode::Operation::ExtractAudio),
+            ConvertOperation::Compress if target_size == Some(0) => {
+                return Err(Status::invalid_argument("Target size must be greater than zero"));
+            }
+            ConvertOperation::Compress => Self::Transcode(transcode::Operation::Compress { target_size }),
+            ConvertOperation::Voice => Self::Transcode(transcode::Operation::Voice),
+            ConvertOperation::VideoNote => Self::Transcode(transcode::Operation::VideoNote),
+        })
+    }
+}
+
+async fn get_delivery(container: &Container, mode: DeliveryMode) -> Result<Delivery, Status> {
+    match mode {
+        DeliveryMode::Stream => Ok(Delivery::Stream),
+        DeliveryMode::SharedVolume => {
+            let interactor = container
+                .get::<shared_volume::Publish>()
+                .await
+                .inspect_err(|err| error!("Failed to get interactor: {err}"))
+                .map_err(|err| Status::internal(err.to_string()))?;
+            if !interactor.is_enabled() {
+                return Err(Status::failed_precondition("Shared volume isn't configured"));
+            }
+            Ok(Delivery::SharedVolume(interactor))
+        }
+    }
+}
+
+async fn create_file_stream<R>(
+    MediaInFS { path, temp_dir }: MediaInFS,
+    header: FileHeader,
+    delivery: Delivery,
+) -> Result<ReceiverStream<Result<R, Status>>, Status>
 where
     R: StreamResponse,
 {
+    if let Delivery::SharedVolume(_) = delivery {
+        return create_files_stream(vec![path], temp_dir, vec![header], delivery).await;
+    }
+
     let mut file = tokio::fs::File::open(path)
         .await
         .inspect_err(|err| error!("Failed to open file: {err}"))
         .map_err(|err| Status::internal(format!("Failed to open downloaded file: {err}")))?;
     let (tx, rx) = tokio::sync::mpsc::channel(CHANNEL_BUFFER_SIZE);
 
-    let metadata = file
-        .metadata()
-        .await
-        .inspect_err(|err| error!("Failed to get file metadata: {err}"))
-        .map_err(|err| Status::internal(format!("Failed to get file metadata: {err}")))?;
+    tx.send(Ok(R::with_header(header))).await.unwrap();
+
+    tokio::spawn(async move {
+        let _ = send_file_chunks(&mut file, &tx).await;
+        drop(temp_dir);
+    });
+
+    Ok(ReceiverStream::new(rx))
+}
 
-    tx.send(Ok(R::with_header(metadata.len()))).await.unwrap();
+/// Stream the files one after another, each of them is preceded by its own header
+async fn create_files_stream<R>(
+    paths: Vec<PathBuf>,
+    temp_dir: TempDir,
+    headers: Vec<FileHeader>,
+    delivery: Delivery,
+) -> Result<ReceiverStream<Result<R, Status>>, Status>
+where
+    R: StreamResponse,
+{
+    let (tx, rx) = tokio::sync::mpsc::channel(CHANNEL_BUFFER_SIZE);
 
     tokio::spawn(async move {
-        let mut buf = vec![0u8; CHUNK_SIZE_BYTES as usize];
-        loop {
-            let n = match file.read(&mut buf).await {
-                Ok(0) => break,
-                Ok(val) => val,
+        for (path, mut header) in paths.into_iter().zip(headers) {
+            if let Delivery::SharedVolume(interactor) = &delivery {
+                let message = match interactor.execute(shared_volume::PublishInput::new(path)).await {
+                    Ok(lease) => {
+                        header.shared_file = Some(SharedFile {
+                            path: lease.path.to_string_lossy().into_owned(),
+                            lease_id: lease.id,
+                            expires_at: lease.expires_at,
+                        });
+                        Ok(R::with_header(header))
+                    }
+                    Err(err) => {
+                        error!("Failed to move file to the shared volume: {err}");
+                        Err(Status::internal(format!("Failed to move file to the shared volume: {err}")))
+                    }
+                };
+                let is_err = message.is_err();
+                if tx.send(message).await.is_err() {
+                    error!("Client disconnected during transfer");
+                    break;
+                }
+                if is_err {
+                    break;
+                }
+                continue;
+            }
+
+            let mut file = match tokio::fs::File::open(&path).await {
+                Ok(file) => file,
                 Err(err) => {
-                    error!("Failed to read chunk: {err}");
-                    let _ = tx.send(Err(Status::internal(format!("Failed to read chunk: {err}")))).await;
+                    error!("Failed to open file: {err}");
+                    let _ = tx
+                        .send(Err(Status::internal(format!("Failed to open downloaded file: {err}"))))
+                        .await;
                     break;
                 }
             };
-            let mut chunk = Vec::with_capacity(n);
-            chunk.extend_from_slice(&buf[..n]);
-
-            if tx.send(Ok(R::with_chunk(chunk))).await.is_err() {
+            if tx.send(Ok(R::with_header(header))).await.is_err() {
                 error!("Client disconnected during transfer");
                 break;
             }
+            if send_file_chunks(&mut file, &tx).await.is_err() {
+                break;
+            }
         }
         drop(temp_dir);
     });
@@ -79,17 +206,539 @@ where
     Ok(ReceiverStream::new(rx))
 }
 
+async fn send_file_chunks<R>(file: &mut tokio::fs::File, tx: &Sender<Result<R, Status>>) -> Result<(), ()>
+where
+    R: StreamResponse,
+{
+    let mut buf = vec![0u8; CHUNK_SIZE_BYTES as usize];
+    loop {
+        let n = match file.read(&mut buf).await {
+            Ok(0) => return Ok(()),
+            Ok(val) => val,
+            Err(err) => {
+                error!("Failed to read chunk: {err}");
+                let _ = tx.send(Err(Status::internal(format!("Failed to read chunk: {err}")))).await;
+                return Err(());
+            }
+        };
+        let mut chunk = Vec::with_capacity(n);
+        chunk.extend_from_slice(&buf[..n]);
+
+        metrics::STREAMED_BYTES.with_label_values(&[R::KIND]).inc_by(n as u64);
+        if tx.send(Ok(R::with_chunk(chunk))).await.is_err() {
+            error!("Client disconnected during transfer");
+            return Err(());
+        }
+    }
+}
+
+/// Stream the media as a single file or split it into parts of the max file size
+async fn stream_media<R>(
+    container: &Container,
+    media: MediaInFS,
+    title: Option<String>,
+    split: bool,
+    delivery: Delivery,
+) -> Result<ReceiverStream<Result<R, Status>>, Status>
+where
+    R: StreamResponse,
+{
+    if !split {
+        let header = probe_header(container, media.path.clone(), title, None).await?;
+        return create_file_stream(media, header, delivery).await;
+    }
+
+    let interactor = container
+        .get::<split::Split>()
+        .await
+        .inspect_err(|err| error!("Failed to get interactor: {err}"))
+        .map_err(|err| Status::internal(err.to_string()))?;
+
+    let parts = interactor
+        .execute(split::SplitInput::new(media))
+        .await
+        .inspect_err(|err| error!("Failed to split media: {err}"))
+        .map_err(|err| Status::internal(format!("Failed to split media: {err}")))?;
+
+    let count = u32::try_from(parts.parts.len()).map_err(|_| Status::internal("Too many parts"))?;
+    let mut headers = Vec::with_capacity(parts.parts.len());
+    for (index, part) in (0..count).zip(parts.parts.iter()) {
+        let mut header = probe_header(container, part.path.clone(), title.clone(), Some((index, count))).await?;
+        header.part = Some(FilePart {
+            index,
+            count,
+            start_time: part.start,
+            end_time: part.end,
+        });
+        headers.push(header);
+    }
+
+    let MediaPartsInFS { parts, temp_dir } = parts;
+    create_files_stream(parts.into_iter().map(|part| part.path).collect(), temp_dir, headers, delivery).await
+}
+
+/// Cut the audio by its chapters and stream the tracks one after another
+async fn stream_tracks<R>(
+    container: &Container,
+    media: MediaInFS,
+    album: Option<String>,
+    cover: Option<Thumbnail>,
+    delivery: Delivery,
+) -> Result<ReceiverStream<Result<R, Status>>, Status>
+where
+    R: StreamResponse,
+{
+    let cover = match cover {
+        Some(cover) => container
+            .get::<thumbnail::Download>()
+            .await
+            .inspect_err(|err| error!("Failed to get interactor: {err}"))
+            .map_err(|err| Status::internal(err.to_string()))?
+            .execute(thumbnail::DownloadInput::new(cover))
+            .await
+            .inspect_err(|err| error!("Failed to download cover: {err}"))
+            .ok()
+            .flatten(),
+        None => None,
+    };
+
+    let interactor = container
+        .get::<chapters::Split>()
+        .await
+        .inspect_err(|err| error!("Failed to get interactor: {err}"))
+        .map_err(|err| Status::internal(err.to_string()))?;
+
+    let TracksInFS { tracks, temp_dir } = interactor
+        .execute(chapters::SplitInput::new(
+            media,
+            album,
+            cover.as_ref().map(|cover| cover.path.clone()),
+        ))
+        .await
+        .inspect_err(|err| error!("Failed to split media by chapters: {err}"))
+        .map_err(|err| Status::internal(format!("Failed to split media by chapters: {err}")))?;
+    drop(cover);
+
+    let count = u32::try_from(tracks.len()).map_err(|_| Status::internal("Too many tracks"))?;
+    let mut headers = Vec::with_capacity(tracks.len());
+    for (number, track) in (1..=count).zip(tracks.iter()) {
+        let mut header = probe_header(container, track.path.clone(), Some(track.title.clone()), None).await?;
+        header.track = Some(generated::Track {
+            number,
+            count,
+            title: track.title.clone(),
+            start_time: track.start,
+            end_time: track.end,
+        });
+        headers.push(header);
+    }
+
+    create_files_stream(tracks.into_iter().map(|track| track.path).collect(), temp_dir, headers, delivery).await
+}
+
+/// Probe the file for the header, falling back to the header without the probed fields,
+/// so a file `ffprobe` can't read is still delivered
+async fn probe_header(container: &Container, path: PathBuf, title: Option<String>, part: Option<(u32, u32)>) -> Result<FileHeader, Status> {
+    let interactor = container
+        .get::<probe::Probe>()
+        .await
+        .inspect_err(|err| error!("Failed to get interactor: {err}"))
+        .map_err(|err| Status::internal(err.to_string()))?;
+
+    match interactor.execute(probe::ProbeInput::new(path.clone(), title.clone(), part)).await {
+        Ok(properties) => Ok(properties.into()),
+        Err(err) => {
+            warn!("Failed to probe media, sending the header without the probed fields: {err}");
+
+            let filesize = tokio::fs::metadata(&path)
+                .await
+                .inspect_err(|err| error!("Failed to get file metadata: {err}"))
+                .map_err(|err| Status::internal(format!("Failed to get file metadata: {err}")))?
+                .len();
+            let extension = path.extension().unwrap_or_default().to_string_lossy();
+            let title = title.unwrap_or_else(|| path.file_stem().unwrap_or_default().to_string_lossy().into_owned());
+            Ok(FileHeader {
+                filesize,
+                filename: suggest_filename(&title, part, &extension),
+                sha256: sha256_file(&path)
+                    .await
+                    .inspect_err(|err| warn!("Failed to hash media: {err}"))
+                    .ok(),
+                ..Default::default()
+            })
+        }
+    }
+}
+
+async fn make_streamable(container: &Container, media: MediaInFS, split: bool) -> Result<MediaInFS, Status> {
+    let interactor = container
+        .get::<streamable::Convert>()
+        .await
+        .inspect_err(|err| error!("Failed to get interactor: {err}"))
+        .map_err(|err| Status::internal(err.to_string()))?;
+
+    interactor
+        .execute(streamable::ConvertInput::new(media, split))
+        .await
+        .inspect_err(|err| error!("Failed to convert media: {err}"))
+        .map_err(|err| match err {
+            streamable::ErrorKind::Truncation(truncation::ErrorKind::FileTooLarge { estimated_filesize }) => {
+                file_too_large_status(estimated_filesize)
+            }
+            err => Status::internal(format!("Failed to convert media: {err}")),
+        })
+}
+
+async fn record_live(
+    container: &Container,
+    video: entities::Video,
+    format: Combined,
+    duration: Option<u32>,
+    live_from_start: bool,
+) -> Result<MediaInFS, Status> {
+    let interactor = container
+        .get::<live::Record>()
+        .await
+        .inspect_err(|err| error!("Failed to get interactor: {err}"))
+        .map_err(|err| Status::internal(err.to_string()))?;
+
+    interactor
+        .execute(live::RecordInput::new(video, format, None, duration, live_from_start))
+        .await
+        .inspect_err(|err| error!("Failed to record live stream: {err}"))
+        .map_err(|err| match err {
+            live::ErrorKind::Upcoming { release_timestamp } => live_not_started_status(release_timestamp),
+            live::ErrorKind::Scratch(err @ scratch::Error::NoSpace { .. }) => no_space_status(&err),
+            err => Status::internal(format!("Failed to record live stream: {err}")),
+        })
+}
+
+async fn make_animation(container: &Container, media: MediaInFS, format: AnimationFormat) -> Result<MediaInFS, Status> {
+    let interactor = container
+        .get::<animation::Convert>()
+        .await
+        .inspect_err(|err| error!("Failed to get interactor: {err}"))
+        .map_err(|err| Status::internal(err.to_string()))?;
+
+    interactor
+        .execute(animation::ConvertInput::new(media, format))
+        .await
+        .inspect_err(|err| error!("Failed to convert media to animation: {err}"))
+        .map_err(|err| match err {
+            animation::ErrorKind::Truncation(truncation::ErrorKind::FileTooLarge { estimated_filesize }) => {
+                file_too_large_status(estimated_filesize)
+            }
+            err => Status::internal(format!("Failed to convert media to animation: {err}")),
+        })
+}
+
+/// Apply the requested output mode to the downloaded video
+async fn convert_video(
+    container: &Container,
+    media: MediaInFS,
+    output_mode: VideoOutputMode,
+    telegram_compatible: bool,
+    split: bool,
+) -> Result<MediaInFS, Status> {
+    if output_mode == VideoOutputMode::VideoNote {
+        transcode(container, media, transcode::Operation::VideoNote).await
+    } else if let Some(format) = output_mode.into() {
+        make_animation(container, media, format).await
+    } else if telegram_compatible {
+        make_streamable(container, media, split).await
+    } else {
+        Ok(media)
+    }
+}
+
+async fn transcode(container: &Container, media: MediaInFS, operation: transcode::Operation) -> Result<MediaInFS, Status> {
+    let interactor = container
+        .get::<transcode::Transcode>()
+        .await
+        .inspect_err(|err| error!("Failed to get interactor: {err}"))
+        .map_err(|err| Status::internal(err.to_string()))?;
+
+    interactor
+        .execute(transcode::TranscodeInput::new(media, operation))
+        .await
+        .inspect_err(|err| error!("Failed to transcode media: {err}"))
+        .map_err(|err| match err {
+            transcode::ErrorKind::Truncation(truncation::ErrorKind::FileTooLarge { estimated_filesize }) => {
+                file_too_large_status(estimated_filesize)
+            }
+            err @ (transcode::ErrorKind::NoAudio
+            | transcode::ErrorKind::NoVideo
+            | transcode::ErrorKind::NoDuration
+            | transcode::ErrorKind::TargetTooSmall { .. }) => Status::invalid_argument(err.to_string()),
+            err => Status::internal(format!("Failed to transcode media: {err}")),
+        })
+}
+
+/// Waveform errors are logged, the voice message is sent without it
+async fn compute_waveform(container: &Container, path: PathBuf) -> Option<Vec<u8>> {
+    let interactor = container
+        .get::<waveform::Compute>()
+        .await
+        .inspect_err(|err| error!("Failed to get interactor: {err}"))
+        .ok()?;
+
+    interactor
+        .execute(waveform::ComputeInput::new(path))
+        .await
+        .inspect_err(|err| error!("Failed to compute waveform: {err}"))
+        .ok()
+}
+
+/// Key of the media processed with the options.
+/// Media of the unsupported sites are keyed by the domain of the URL and the ID from the orchestrator.
+fn cache_key(video: &entities::Video, format_ids: &[&str], options: &str) -> CacheKey {
+    if let Ok(MediaIdentity { extractor, id, .. }) = canonicalize(&video.url) {
+        return CacheKey::new(extractor.as_str(), &id, format_ids, options);
+    }
+    let extractor = url::Url::parse(&video.url)
+        .ok()
+        .and_then(|url| url.host_str().map(ToOwned::to_owned))
+        .unwrap_or_default();
+    CacheKey::new(&extractor, &video.id, format_ids, options)
+}
+
+/// Formats, languages and default flags of the tracks, in their order
+fn audio_tracks_key(tracks: &[entities::format::AudioTrack]) -> String {
+    tracks
+        .iter()
+        .map(|track| {
+            format!(
+                "{}:{}:{}",
+                track.format.id,
+                track.language.as_deref().unwrap_or_default(),
+                track.default
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Cache errors are logged and treated as a miss to not fail the request
+async fn cached_media(container: &Container, key: CacheKey, media_id: String) -> Option<MediaInFS> {
+    let interactor = container
+        .get::<cache::Lookup>()
+        .await
+        .inspect_err(|err| error!("Failed to get interactor: {err}"))
+        .ok()?;
+
+    interactor
+        .execute(cache::LookupInput::new(key, media_id))
+        .await
+        .inspect_err(|err| error!("Failed to get cached media: {err}"))
+        .ok()
+        .flatten()
+}
+
+async fn cache_media(container: &Container, key: CacheKey, media: &MediaInFS) {
+    let Ok(interactor) = container
+        .get::<cache::Store>()
+        .await
+        .inspect_err(|err| error!("Failed to get interactor: {err}"))
+    else {
+        return;
+    };
+
+    let _ = interactor
+        .execute(cache::StoreInput::new(key, media.path.clone()))
+        .await
+        .inspect_err(|err| error!("Failed to cache media: {err}"));
+}
+
+impl From<DownloadThumbnailRequest> for Thumbnail {
+    fn from(
+        DownloadThumbnailRequest {
+            media_id,
+            service_domain,
+            thumbnails,
+            width,
+            height,
+        }: DownloadThumbnailRequest,
+    ) -> Self {
+        Self::new(media_id, service_domain, thumbnails, width, height)
+    }
+}
+
+impl From<value_objects::MediaKind> for MediaKind {
+    fn from(kind: value_objects::MediaKind) -> Self {
+        match kind {
+            value_objects::MediaKind::Video => Self::Video,
+            value_objects::MediaKind::Audio => Self::Audio,
+        }
+    }
+}
+
+impl From<LiveStatus> for Option<value_objects::LiveStatus> {
+    fn from(status: LiveStatus) -> Self {
+        use value_objects::LiveStatus as Status;
+
+        match status {
+            LiveStatus::Unspecified => None,
+            LiveStatus::NotLive => Some(Status::NotLive),
+            LiveStatus::IsLive => Some(Status::IsLive),
+            LiveStatus::IsUpcoming => Some(Status::IsUpcoming),
+            LiveStatus::WasLive => Some(Status::WasLive),
+            LiveStatus::PostLive => Some(Status::PostLive),
+        }
+    }
+}
+
+impl From<SponsorBlockCategory> for Option<value_objects::SponsorBlockCategory> {
+    fn from(category: SponsorBlockCategory) -> Self {
+        use value_objects::SponsorBlockCategory as Category;
+
+        match category {
+            SponsorBlockCategory::Unspecified => None,
+            SponsorBlockCategory::Sponsor => Some(Category::Sponsor),
+            SponsorBlockCategory::Selfpromo => Some(Category::SelfPromo),
+            SponsorBlockCategory::Interaction => Some(Category::Interaction),
+            SponsorBlockCategory::Intro => Some(Category::Intro),
+            SponsorBlockCategory::Outro => Some(Category::Outro),
+            SponsorBlockCategory::Preview => Some(Category::Preview),
+            SponsorBlockCategory::MusicOfftopic => Some(Category::MusicOfftopic),
+            SponsorBlockCategory::Filler => Some(Category::Filler),
+        }
+    }
+}
+
+impl From<SponsorBlock> for entities::SponsorBlock {
+    fn from(sponsorblock: SponsorBlock) -> Self {
+        Self::new(
+            sponsorblock.remove().filter_map(Into::into).collect(),
+            sponsorblock.mark().filter_map(Into::into).collect(),
+        )
+    }
+}
+
+impl From<VideoOutputMode> for Option<AnimationFormat> {
+    fn from(mode: VideoOutputMode) -> Self {
+        match mode {
+            VideoOutputMode::Default => None,
+            VideoOutputMode::Animation => Some(AnimationFormat::Mp4),
+            VideoOutputMode::Gif => Some(AnimationFormat::Gif),
+            VideoOutputMode::WebmAnimation => Some(AnimationFormat::Webm),
+            VideoOutputMode::VideoNote => None,
+        }
+    }
+}
+
+impl From<MediaProperties> for FileHeader {
+    fn from(
+        MediaProperties {
+            filesize,
+            duration,
+            width,
+            height,
+            rotation,
+            video_codec,
+            audio_codec,
+            bitrate,
+            container,
+            mime_type,
+            filename,
+            streamable,
+            sha256,
+        }: MediaProperties,
+    ) -> Self {
+        Self {
+            filesize,
+            part: None,
+            item: None,
+            track: None,
+            shared_file: None,
+            kind: MediaKind::Unspecified.into(),
+            waveform: None,
+            duration,
+            width,
+            height,
+            rotation,
+            video_codec,
+            audio_codec,
+            bitrate,
+            container: container.to_owned(),
+            mime_type: mime_type.to_owned(),
+            filename,
+            streamable,
+            sha256: Some(sha256),
+        }
+    }
+}
+
+/// Another worker may have enough space, so the request can be retried
+fn no_space_status(err: &scratch::Error) -> Status {
+    Status::unavailable(err.to_string())
+}
+
+/// Count the request by its outcome, the duration is the time until the media is ready to be sent
+async fn observe<T>(kind: &'static str, domain: &str, download: impl Future<Output = Result<T, Status>>) -> Result<T, Status> {
+    let in_flight = InFlight::start(kind);
+    let res = download.await;
+
+    let outcome = if res.is_ok() { "success" } else { "error" };
+    metrics::DOWNLOADS.with_label_values(&[kind, domain, outcome]).inc();
+    metrics::DOWNLOAD_DURATION
+        .with_label_values(&[kind])
+        .observe(in_flight.elapsed().as_secs_f64());
+    if let Err(status) = &res {
+        metrics::ERRORS.with_label_values(&[kind, error_class(status)]).inc();
+    }
+    res
+}
+
+/// Low-cardinality class of the error, the detailed codes take precedence over the status code
+fn error_class(status: &Status) -> &'static str {
+    let code = ErrorDetails::decode(status.details()).map(|details| details.code());
+    match (code, status.code()) {
+        (Ok(ErrorCode::FileTooLarge), _) => "file_too_large",
+        (Ok(ErrorCode::LiveNotStarted), _) => "live_not_started",
+        (_, Code::InvalidArgument) => "invalid_argument",
+        (_, Code::NotFound) => "not_found",
+        (_, Code::FailedPrecondition) => "failed_precondition",
+        (_, Code::Unavailable) => "unavailable",
+        (_, Code::ResourceExhausted) => "resource_exhausted",
+        _ => "internal",
+    }
+}
+
+fn file_too_large_status(estimated_filesize: u64) -> Status {
+    let details = ErrorDetails {
+        code: ErrorCode::FileTooLarge.into(),
+        estimated_filesize: Some(estimated_filesize),
+        release_timestamp: None,
+    };
+    Status::with_details(
+        Code::ResourceExhausted,
+        format!("File is too large, estimated size is {estimated_filesize} bytes"),
+        details.encode_to_vec().into(),
+    )
+}
+
+fn live_not_started_status(release_timestamp: Option<i64>) -> Status {
+    let details = ErrorDetails {
+        code: ErrorCode::LiveNotStarted.into(),
+        estimated_filesize: None,
+        release_timestamp,
+    };
+    Status::with_details(
+        Code::FailedPrecondition,
+        "Live stream hasn't started yet",
+        details.encode_to_vec().into(),
+    )
+}
+
 #[derive(Debug, Clone)]
 pub struct Service;
 
-#[async_trait]
-impl DownloadService for Service {
-    type DownloadAudioStream = ReceiverStream<Result<DownloadAudioResponse, Status>>;
-    type DownloadVideoStream = ReceiverStream<Result<DownloadVideoResponse, Status>>;
-    type DownloadThumbnailStream = ReceiverStream<Result<DownloadThumbnailResponse, Status>>;
-
-    async fn download_audio(&self, request: Request<DownloadAudioRequest>) -> Result<Response<Self::DownloadAudioStream>, Status> {
-        let container = di_container::get(&request)?;
+impl Service {
+    async fn audio(
+        &self,
+        request: Request<DownloadAudioRequest>,
+    ) -> Result<Response<ReceiverStream<Result<DownloadAudioResponse, Status>>>, Status> {
+        let container = di_container::get(&request)?.clone();
         let interactor = container
             .get::<audio::Download>()
             .await
@@ -97,20 +746,73 @@ impl DownloadService for Service {
             .map_err(|err| Status::internal(err.to_string()))?;
         let request = request.into_inner();
 
-        let video = required_field(request.video, "Video")?.into();
-        let format = required_field(request.format, "Format")?.into();
+        let output_mode = request.output_mode();
+        if output_mode == AudioOutputMode::Voice && request.split {
+            return Err(Status::invalid_argument("Voice output can't be split"));
+        }
+        if output_mode == AudioOutputMode::Voice && request.split_by_chapters {
+            return Err(Status::invalid_argument("Voice output can't be split by chapters"));
+        }
+        if request.split && request.split_by_chapters {
+            return Err(Status::invalid_argument("Split can't be combined with split by chapters"));
+        }
+        let delivery = get_delivery(&container, request.delivery_mode()).await?;
+        let video: entities::Video = required_field(request.video, "Video")?.into();
+        let title = video.title.clone();
+        let sponsorblock = request.sponsorblock.map(Into::into).unwrap_or_default();
+        let format: entities::format::Audio = required_field(request.format, "Format")?.into();
+        let key = cache_key(
+            &video,
+            &[&format.id],
+            &format!(
+                "audio;split={};output_mode={output_mode:?};sponsorblock={sponsorblock:?}",
+                request.split
+            ),
+        );
+
+        let media = if let Some(media) = cached_media(&container, key.clone(), video.id.clone()).await {
+            media
+        } else {
+            let media = interactor
+                .execute(audio::DownloadInput::new(video, format, None, request.split, sponsorblock))
+                .await
+                .inspect_err(|err| error!("Failed to download audio: {err}"))
+                .map_err(|err| match err {
+                    audio::ErrorKind::Truncation(truncation::ErrorKind::FileTooLarge { estimated_filesize }) => {
+                        file_too_large_status(estimated_filesize)
+                    }
+                    audio::ErrorKind::Scratch(err @ scratch::Error::NoSpace { .. }) => no_space_status(&err),
+                    err => Status::internal(format!("Failed to download audio: {err}")),
+                })?;
+            let media = if output_mode == AudioOutputMode::Voice {
+                transcode(&container, media, transcode::Operation::Voice).await?
+            } else {
+                media
+            };
+            cache_media(&container, key, &media).await;
+            media
+        };
 
-        let media = interactor
-            .execute(audio::DownloadInput::new(video, format, None))
+        if output_mode == AudioOutputMode::Voice {
+            let mut header = probe_header(&container, media.path.clone(), title, None).await?;
+            header.waveform = compute_waveform(&container, media.path.clone()).await;
+            return create_file_stream(media, header, delivery).await.map(Response::new);
+        }
+        if request.split_by_chapters {
+            return stream_tracks(&container, media, title, request.cover.map(Into::into), delivery)
+                .await
+                .map(Response::new);
+        }
+        stream_media(&container, media, title, request.split, delivery)
             .await
-            .inspect_err(|err| error!("Failed to download audio: {err}"))
-            .map_err(|err| Status::internal(format!("Failed to download audio: {err}")))?;
-
-        create_file_stream(media).await.map(Response::new)
+            .map(Response::new)
     }
 
-    async fn download_video(&self, request: Request<DownloadVideoRequest>) -> Result<Response<Self::DownloadVideoStream>, Status> {
-        let container = di_container::get(&request)?;
+    async fn video(
+        &self,
+        request: Request<DownloadVideoRequest>,
+    ) -> Result<Response<ReceiverStream<Result<DownloadVideoResponse, Status>>>, Status> {
+        let container = di_container::get(&request)?.clone();
         let interactor = container
             .get::<video::Download>()
             .await
@@ -118,28 +820,84 @@ impl DownloadService for Service {
             .map_err(|err| Status::internal(err.to_string()))?;
         let request = request.into_inner();
 
-        let video = required_field(request.video, "Video")?.into();
+        let output_mode = request.output_mode();
+        let delivery = get_delivery(&container, request.delivery_mode()).await?;
+        let video: entities::Video = required_field(request.video, "Video")?.into();
+        let title = video.title.clone();
+        let sponsorblock = request.sponsorblock.map(Into::into).unwrap_or_default();
         let format = {
             let format = required_field(request.format, "Format")?;
             let video = required_field(format.video, "Video format")?.into();
             let audio = required_field(format.audio, "Audio format")?.into();
             Combined(video, audio)
         };
+        let audio_tracks = request
+            .audio_tracks
+            .into_iter()
+            .map(|track| {
+                Ok(entities::format::AudioTrack {
+                    format: required_field(track.format, "Audio track format")?.into(),
+                    language: track.language,
+                    default: track.default,
+                })
+            })
+            .collect::<Result<Vec<_>, Status>>()?;
 
-        let media = interactor
-            .execute(video::DownloadInput::new(video, format, None))
-            .await
-            .inspect_err(|err| error!("Failed to download video: {err}"))
-            .map_err(|err| Status::internal(format!("Failed to download video: {err}")))?;
+        // Recordings differ between requests, so they're never cached
+        let media = if matches!(
+            video.live_status,
+            Some(value_objects::LiveStatus::IsLive | value_objects::LiveStatus::IsUpcoming)
+        ) {
+            let media = record_live(&container, video, format, request.live_duration, request.live_from_start).await?;
+            convert_video(&container, media, output_mode, request.telegram_compatible, request.split).await?
+        } else {
+            let key = cache_key(
+                &video,
+                &[&format.0.id, &format.1.id],
+                &format!(
+                    "video;split={};telegram_compatible={};output_mode={output_mode:?};sponsorblock={sponsorblock:?};audio_tracks={}",
+                    request.split,
+                    request.telegram_compatible,
+                    audio_tracks_key(&audio_tracks),
+                ),
+            );
+            if let Some(media) = cached_media(&container, key.clone(), video.id.clone()).await {
+                media
+            } else {
+                let media = interactor
+                    .execute(video::DownloadInput::new(
+                        video,
+                        format,
+                        audio_tracks,
+                        None,
+                        request.split,
+                        sponsorblock,
+                    ))
+                    .await
+                    .inspect_err(|err| error!("Failed to download video: {err}"))
+                    .map_err(|err| match err {
+                        video::ErrorKind::Truncation(truncation::ErrorKind::FileTooLarge { estimated_filesize }) => {
+                            file_too_large_status(estimated_filesize)
+                        }
+                        video::ErrorKind::Scratch(err @ scratch::Error::NoSpace { .. }) => no_space_status(&err),
+                        err => Status::internal(format!("Failed to download video: {err}")),
+                    })?;
+                let media = convert_video(&container, media, output_mode, request.telegram_compatible, request.split).await?;
+                cache_media(&container, key, &media).await;
+                media
+            }
+        };
 
-        create_file_stream(media).await.map(Response::new)
+        stream_media(&container, media, title, request.split, delivery)
+            .await
+            .map(Response::new)
     }
 
-    async fn download_thumbnail(
+    async fn thumbnail(
         &self,
         request: Request<DownloadThumbnailRequest>,
-    ) -> Result<Response<Self::DownloadThumbnailStream>, Status> {
-        let container = di_container::get(&request)?;
+    ) -> Result<Response<ReceiverStream<Result<DownloadThumbnailResponse, Status>>>, Status> {
+        let container = di_container::get(&request)?.clone();
         let interactor = container
             .get::<thumbnail::Download>()
             .await
@@ -148,31 +906,239 @@ impl DownloadService for Service {
         let request = request.into_inner();
 
         let media = interactor
-            .execute(thumbnail::DownloadInput::new(Thumbnail::new(
-                request.media_id,
-                request.service_domain,
-                request.thumbnails,
-                request.width,
-                request.height,
-            )))
+            .execute(thumbnail::DownloadInput::new(request.into()))
             .await
             .inspect_err(|err| error!("Failed to download thumbnail: {err}"))
             .map_err(|err| Status::internal(format!("Failed to download thumbnail: {err}")))?
             .ok_or_else(|| Status::not_found("Available thumbnail is not found"))?;
 
-        create_file_stream(media).await.map(Response::new)
+        let header = probe_header(&container, media.path.clone(), None, None).await?;
+        create_file_stream(media, header, Delivery::Stream).await.map(Response::new)
+    }
+
+    async fn gallery(
+        &self,
+        request: Request<DownloadGalleryRequest>,
+    ) -> Result<Response<ReceiverStream<Result<DownloadGalleryResponse, Status>>>, Status> {
+        let container = di_container::get(&request)?.clone();
+        let interactor = container
+            .get::<gallery::Download>()
+            .await
+            .inspect_err(|err| error!("Failed to get interactor: {err}"))
+            .map_err(|err| Status::internal(err.to_string()))?;
+        let request = request.into_inner();
+
+        let max_items = match request.max_items {
+            0 => MAX_GALLERY_ITEMS,
+            max_items => max_items.min(MAX_GALLERY_ITEMS),
+        };
+        let GalleryInFS {
+            items,
+            count,
+            skipped,
+            temp_dir,
+        } = interactor
+            .execute(gallery::DownloadInput::new(request.url, max_items, None))
+            .await
+            .inspect_err(|err| error!("Failed to download gallery: {err}"))
+            .map_err(|err| match err {
+                gallery::ErrorKind::Empty => Status::not_found("Gallery items are not found"),
+                err => Status::internal(format!("Failed to download gallery: {err}")),
+            })?;
+
+        let mut headers = Vec::with_capacity(items.len());
+        for (index, path) in &items {
+            let mut header = probe_header(&container, path.clone(), None, None).await?;
+            header.item = Some(GalleryItem {
+                index: *index,
+                count,
+                skipped: skipped.clone(),
+            });
+            headers.push(header);
+        }
+        let items = items.into_iter().map(|(_, path)| path).collect();
+
+        create_files_stream(items, temp_dir, headers, Delivery::Stream)
+            .await
+            .map(Response::new)
+    }
+
+    async fn direct(
+        &self,
+        request: Request<DownloadDirectRequest>,
+    ) -> Result<Response<ReceiverStream<Result<DownloadDirectResponse, Status>>>, Status> {
+        let container = di_container::get(&request)?.clone();
+        let interactor = container
+            .get::<direct_download::Download>()
+            .await
+            .inspect_err(|err| error!("Failed to get interactor: {err}"))
+            .map_err(|err| Status::internal(err.to_string()))?;
+        let request = request.into_inner();
+
+        let delivery = get_delivery(&container, request.delivery_mode()).await?;
+        let DirectMediaInFS { media, kind, title } = interactor
+            .execute(direct_download::DownloadInput::new(request.url))
+            .await
+            .inspect_err(|err| error!("Failed to download direct file: {err}"))
+            .map_err(|err| match err {
+                direct_download::ErrorKind::Direct(direct::ErrorKind::TooLarge { filesize }) => file_too_large_status(filesize),
+                err @ (direct_download::ErrorKind::Direct(
+                    direct::ErrorKind::ForbiddenUrl(_) | direct::ErrorKind::ContentType(_) | direct::ErrorKind::UnknownFormat,
+                )
+                | direct_download::ErrorKind::NoStreams) => Status::invalid_argument(err.to_string()),
+                direct_download::ErrorKind::Scratch(err @ scratch::Error::NoSpace { .. }) => no_space_status(&err),
+                err => Status::internal(format!("Failed to download direct file: {err}")),
+            })?;
+
+        let mut header = probe_header(&container, media.path.clone(), title, None).await?;
+        header.set_kind(kind.into());
+        create_file_stream(media, header, delivery).await.map(Response::new)
+    }
+
+    async fn convert(
+        &self,
+        request: Request<Streaming<ConvertRequest>>,
+    ) -> Result<Response<ReceiverStream<Result<ConvertResponse, Status>>>, Status> {
+        let container = di_container::get(&request)?.clone();
+        let interactor = container
+            .get::<upload::Receive>()
+            .await
+            .inspect_err(|err| error!("Failed to get interactor: {err}"))
+            .map_err(|err| Status::internal(err.to_string()))?;
+        let mut stream = request.into_inner();
+
+        let header = match stream.message().await? {
+            Some(ConvertRequest {
+                message: Some(convert_request::Message::Header(header)),
+            }) => header,
+            _ => return Err(Status::invalid_argument("Header is required before the chunks")),
+        };
+        let operation = header.operation();
+        let conversion = Conversion::new(operation, header.target_size)?;
+        let delivery = get_delivery(&container, header.delivery_mode()).await?;
+        let title = Path::new(&header.filename)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned());
+        let chunks = stream.map(|message| match message?.message {
+            Some(convert_request::Message::Chunk(FileChunk { content })) => Ok(content),
+            _ => Err(Status::invalid_argument("Only chunks are expected after the header")),
+        });
+
+        let media = interactor
+            .execute(upload::ReceiveInput::new(header.filename, header.filesize, chunks))
+            .await
+            .inspect_err(|err| error!("Failed to receive file: {err}"))
+            .map_err(|err| match err {
+                upload::ErrorKind::TooLarge { filesize } => file_too_large_status(filesize),
+                err @ (upload::ErrorKind::SizeMismatch { .. } | upload::ErrorKind::Stream(_)) => Status::invalid_argument(err.to_string()),
+                upload::ErrorKind::Scratch(err @ scratch::Error::NoSpace { .. }) => no_space_status(&err),
+                err => Status::internal(format!("Failed to receive file: {err}")),
+            })?;
+
+        let media = match conversion {
+            Conversion::Remux => make_streamable(&container, media, false).await?,
+            Conversion::Animation => make_animation(&container, media, AnimationFormat::Mp4).await?,
+            Conversion::Transcode(operation) => transcode(&container, media, operation).await?,
+        };
+
+        let mut header = probe_header(&container, media.path.clone(), title, None).await?;
+        if operation == ConvertOperation::Voice {
+            header.waveform = compute_waveform(&container, media.path.clone()).await;
+        }
+        create_file_stream(media, header, delivery).await.map(Response::new)
+    }
+}
+
+#[async_trait]
+impl DownloadService for Service {
+    type DownloadAudioStream = ReceiverStream<Result<DownloadAudioResponse, Status>>;
+    type DownloadVideoStream = ReceiverStream<Result<DownloadVideoResponse, Status>>;
+    type DownloadThumbnailStream = ReceiverStream<Result<DownloadThumbnailResponse, Status>>;
+    type DownloadGalleryStream = ReceiverStream<Result<DownloadGalleryResponse, Status>>;
+    type DownloadDirectStream = ReceiverStream<Result<DownloadDirectResponse, Status>>;
+    type ConvertStream = ReceiverStream<Result<ConvertResponse, Status>>;
+
+    #[instrument(skip_all, fields(client = auth::identity(&request)))]
+    async fn download_audio(&self, request: Request<DownloadAudioRequest>) -> Result<Response<Self::DownloadAudioStream>, Status> {
+        let domain = request.get_ref().video.as_ref().map_or("unknown", |video| domain_label(&video.url));
+        observe("audio", domain, self.audio(request)).await
+    }
+
+    #[instrument(skip_all, fields(client = auth::identity(&request)))]
+    async fn download_video(&self, request: Request<DownloadVideoRequest>) -> Result<Response<Self::DownloadVideoStream>, Status> {
+        let domain = request.get_ref().video.as_ref().map_or("unknown", |video| domain_label(&video.url));
+        let kind = if request
+            .get_ref()
+            .video
+            .as_ref()
+            .is_some_and(|video| matches!(video.live_status(), LiveStatus::IsLive | LiveStatus::IsUpcoming))
+        {
+            "live"
+        } else {
+            "video"
+        };
+        observe(kind, domain, self.video(request)).await
+    }
+
+    #[instrument(skip_all, fields(client = auth::identity(&request)))]
+    async fn download_thumbnail(
+        &self,
+        request: Request<DownloadThumbnailRequest>,
+    ) -> Result<Response<Self::DownloadThumbnailStream>, Status> {
+        let domain = domain_label(&format!("https://{}", request.get_ref().service_domain));
+        observe("thumbnail", domain, self.thumbnail(request)).await
+    }
+
+    #[instrument(skip_all, fields(client = auth::identity(&request)))]
+    async fn download_gallery(&self, request: Request<DownloadGalleryRequest>) -> Result<Response<Self::DownloadGalleryStream>, Status> {
+        let domain = domain_label(&request.get_ref().url);
+        observe("gallery", domain, self.gallery(request)).await
+    }
+
+    #[instrument(skip_all, fields(client = auth::identity(&request)))]
+    async fn download_direct(&self, request: Request<DownloadDirectRequest>) -> Result<Response<Self::DownloadDirectStream>, Status> {
+        let domain = domain_label(&request.get_ref().url);
+        observe("direct", domain, self.direct(request)).await
+    }
+
+    #[instrument(skip_all, fields(client = auth::identity(&request)))]
+    async fn convert(&self, request: Request<Streaming<ConvertRequest>>) -> Result<Response<Self::ConvertStream>, Status> {
+        observe("convert", "upload", self.convert(request)).await
+    }
+
+    #[instrument(skip_all, fields(client = auth::identity(&request)))]
+    async fn release(&self, request: Request<ReleaseRequest>) -> Result<Response<ReleaseResponse>, Status> {
+        let container = di_container::get(&request)?.clone();
+        let interactor = container
+            .get::<shared_volume::Release>()
+            .await
+            .inspect_err(|err| error!("Failed to get interactor: {err}"))
+            .map_err(|err| Status::internal(err.to_string()))?;
+        let request = request.into_inner();
+
+        let released = interactor
+            .execute(shared_volume::ReleaseInput::new(request.lease_ids))
+            .await
+            .inspect_err(|err| error!("Failed to release leases: {err}"))
+            .map_err(|err| match err {
+                shared_volume::ErrorKind::Disabled => Status::failed_precondition(err.to_string()),
+                err => Status::internal(format!("Failed to release leases: {err}")),
+            })?;
+
+        Ok(Response::new(ReleaseResponse { released }))
     }
 }
 
 macro_rules! impl_stream_response {
-    ($response_type:ty, $message_module:path) => {
+    ($response_type:ty, $message_module:path, $kind:literal) => {
         impl StreamResponse for $response_type {
             type Message = $message_module;
+            const KIND: &'static str = $kind;
 
-            fn with_header(filesize: u64) -> Self {
+            fn with_header(header: FileHeader) -> Self {
                 use $message_module as Message;
                 Self {
-                    message: Some(Message::Header(FileHeader { filesize })),
+                    message: Some(Message::Header(header)),
                 }
             }
 
@@ -186,18 +1152,33 @@ macro_rules! impl_stream_response {
     };
 }
 
-impl_stream_response!(DownloadAudioResponse, download_audio_response::Message);
-impl_stream_response!(DownloadVideoResponse, download_video_response::Message);
-impl_stream_response!(DownloadThumbnailResponse, download_thumbnail_response::Message);
+impl_stream_response!(DownloadAudioResponse, download_audio_response::Message, "audio");
+impl_stream_response!(DownloadVideoResponse, download_video_response::Message, "video");
+impl_stream_response!(DownloadThumbnailResponse, download_thumbnail_response::Message, "thumbnail");
+impl_stream_response!(DownloadGalleryResponse, download_gallery_response::Message, "gallery");
+impl_stream_response!(DownloadDirectResponse, download_direct_response::Message, "direct");
+impl_stream_response!(ConvertResponse, convert_response::Message, "convert");
 
 impl_from_format!(VideoFormat => entities::format::Video {
-    id, url, filesize, filesize_approx, container
+    id, url, filesize, filesize_approx, container, codec
 });
 
 impl_from_format!(AudioFormat => entities::format::Audio {
     id, url, filesize, filesize_approx, codec
 });
 
-impl_from_format!(Video => entities::Video {
-    id, url, width, height
-});
+impl From<Video> for entities::Video {
+    fn from(video: Video) -> Self {
+        let live_status = video.live_status().into();
+        Self {
+            id: video.id,
+            url: video.url,
+            width: video.width,
+            height: video.height,
+            duration: video.duration,
+            title: video.title,
+            live_status,
+            release_timestamp: video.release_timestamp,
+        }
+    }
+}
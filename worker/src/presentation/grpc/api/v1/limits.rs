@@ -1,12 +1,16 @@
+#[allow(clippy::large_enum_variant)]
 mod generated {
     tonic::include_proto!("worker.api.v1");
 }
 use froodi::async_impl::Container;
 pub use generated::limits_service_server::LimitsServiceServer;
-use generated::{GetCurrentLimitsRequest, GetCurrentLimitsResponse, limits_service_server::LimitsService};
+use generated::{GetCurrentLimitsRequest, GetCurrentLimitsResponse, RateLimitBucket, limits_service_server::LimitsService};
 use tonic::{Request, Response, Status, async_trait};
 
-use crate::config::Limits;
+use crate::{
+    adapters::rate_limiter::{BucketState, RateLimiter},
+    config::{Limits, Reloadable},
+};
 
 #[derive(Debug, Clone)]
 pub struct Service {}
@@ -15,14 +19,37 @@ pub struct Service {}
 impl LimitsService for Service {
     async fn get_current_limits(&self, request: Request<GetCurrentLimitsRequest>) -> Result<Response<GetCurrentLimitsResponse>, Status> {
         let container = request.extensions().get::<Container>().unwrap();
-        let limits = container.get::<Limits>().await.unwrap();
+        let limits = container.get::<Reloadable<Limits>>().await.unwrap().load();
+        let (global, hosts) = container.get::<RateLimiter>().await.unwrap().snapshot();
 
         Ok(Response::new(GetCurrentLimitsResponse {
             max_file_size: limits.max_file_size,
+            global_rate_limit: global.map(Into::into),
+            host_rate_limits: hosts.into_iter().map(Into::into).collect(),
         }))
     }
 }
 
+impl From<BucketState> for RateLimitBucket {
+    fn from(
+        BucketState {
+            host,
+            tokens,
+            rate,
+            burst,
+            slowdown,
+        }: BucketState,
+    ) -> Self {
+        Self {
+            host,
+            tokens,
+            rate,
+            burst,
+            slowdown,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use froodi::{DefaultScope::App, async_registry, instance, registry};
@@ -31,11 +58,15 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_current_limits() {
-        let limits = Limits { max_file_size: 1024 };
+        let limits = Limits {
+            max_file_size: 1024,
+            max_split_file_size: None,
+        };
         let container = Container::new(async_registry! {
             extend(
                 registry! {
-                    provide(App,instance(limits.clone())),
+                    provide(App,instance(Reloadable::new(limits.clone()))),
+                    provide(App,instance(RateLimiter::new(None))),
                 }
             )
         });
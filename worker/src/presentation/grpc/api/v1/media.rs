@@ -0,0 +1,59 @@
+#[allow(clippy::large_enum_variant)]
+mod generated {
+    tonic::include_proto!("worker.api.v1");
+}
+pub use generated::media_service_server::MediaServiceServer;
+use generated::{GetMediaIdentityRequest, GetMediaIdentityResponse, media_service_server::MediaService};
+use tonic::{Request, Response, Status, async_trait};
+
+use crate::utils::url::{ErrorKind, MediaIdentity, canonicalize};
+
+#[derive(Debug, Clone)]
+pub struct Service {}
+
+#[async_trait]
+impl MediaService for Service {
+    async fn get_media_identity(&self, request: Request<GetMediaIdentityRequest>) -> Result<Response<GetMediaIdentityResponse>, Status> {
+        let MediaIdentity { extractor, id, url } = canonicalize(&request.get_ref().url).map_err(|err| match err {
+            ErrorKind::InvalidUrl(_) => Status::invalid_argument(err.to_string()),
+            ErrorKind::ShortLink => Status::failed_precondition(err.to_string()),
+            ErrorKind::Unsupported | ErrorKind::NoMediaId => Status::not_found(err.to_string()),
+        })?;
+
+        Ok(Response::new(GetMediaIdentityResponse {
+            extractor: extractor.to_string(),
+            media_id: id,
+            canonical_url: url,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::Code;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_get_media_identity() {
+        let service = Service {};
+
+        let response = service
+            .get_media_identity(Request::new(GetMediaIdentityRequest {
+                url: "https://youtu.be/dQw4w9WgXcQ?si=abc".to_owned(),
+            }))
+            .await
+            .unwrap();
+        let err = service
+            .get_media_identity(Request::new(GetMediaIdentityRequest {
+                url: "https://vm.tiktok.com/ZMabcdefg/".to_owned(),
+            }))
+            .await
+            .unwrap_err();
+
+        assert_eq!(response.get_ref().extractor, "youtube");
+        assert_eq!(response.get_ref().media_id, "dQw4w9WgXcQ");
+        assert_eq!(response.get_ref().canonical_url, "https://www.youtube.com/watch?v=dQw4w9WgXcQ");
+        assert_eq!(err.code(), Code::FailedPrecondition);
+    }
+}
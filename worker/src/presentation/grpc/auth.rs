@@ -0,0 +1,246 @@
+use sha2::{Digest as _, Sha256};
+use std::{
+    fmt::{self, Display, Formatter},
+    fs, io,
+    sync::Arc,
+};
+use tonic::{
+    Request, Status,
+    service::Interceptor,
+    transport::{Certificate, Identity, ServerTlsConfig},
+};
+use tracing::{debug, warn};
+
+use crate::{config, utils::hash::sha256};
+
+const BEARER_PREFIX: &str = "Bearer ";
+/// Length of the certificate fingerprint prefix used as the identity
+const FINGERPRINT_LENGTH: usize = 16;
+
+/// Name of the authenticated client, it's added to the request extensions
+#[derive(Debug, Clone)]
+pub struct ClientIdentity(pub Box<str>);
+
+impl Display for ClientIdentity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// # Returns
+/// Returns `anonymous` if the request went around the interceptor
+pub fn identity<T>(request: &Request<T>) -> &str {
+    request
+        .extensions()
+        .get::<ClientIdentity>()
+        .map_or("anonymous", |identity| identity.0.as_ref())
+}
+
+/// Check the bearer token of the request if the tokens are configured.
+/// Client is identified by the token, by the certificate fingerprint for mutual TLS or as `anonymous`.
+#[derive(Debug, Clone)]
+pub struct Auth {
+    clients: Arc<[config::AuthClient]>,
+}
+
+impl Auth {
+    #[must_use]
+    pub fn new(cfg: &config::Auth) -> Self {
+        Self {
+            clients: cfg.clients.clone().into(),
+        }
+    }
+
+    fn authenticate<T>(&self, request: &Request<T>) -> Result<ClientIdentity, Status> {
+        if self.clients.is_empty() {
+            return Ok(peer_identity(request));
+        }
+
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix(BEARER_PREFIX))
+            .ok_or_else(|| Status::unauthenticated("Bearer token is required"))?;
+        self.clients
+            .iter()
+            .find(|client| tokens_eq(&client.token, token))
+            .map(|client| ClientIdentity(client.name.clone()))
+            .ok_or_else(|| Status::unauthenticated("Invalid bearer token"))
+    }
+}
+
+impl Interceptor for Auth {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let identity = self
+            .authenticate(&request)
+            .inspect_err(|err| warn!("Request rejected: {}", err.message()))?;
+        debug!(client = %identity, "Request authenticated");
+
+        request.extensions_mut().insert(identity);
+        Ok(request)
+    }
+}
+
+/// # Errors
+/// Returns [`io::Error`] if a PEM file can't be read
+pub fn tls_config(cfg: &config::Tls) -> Result<ServerTlsConfig, io::Error> {
+    let identity = Identity::from_pem(fs::read(cfg.cert.as_ref())?, fs::read(cfg.key.as_ref())?);
+    let mut tls_config = ServerTlsConfig::new().identity(identity);
+    if let Some(client_ca) = &cfg.client_ca {
+        tls_config = tls_config.client_ca_root(Certificate::from_pem(fs::read(client_ca.as_ref())?));
+    }
+    Ok(tls_config)
+}
+
+fn peer_identity<T>(request: &Request<T>) -> ClientIdentity {
+    let fingerprint = request
+        .peer_certs()
+        .and_then(|certs| certs.first().map(sha256))
+        .map(|mut fingerprint| {
+            fingerprint.truncate(FINGERPRINT_LENGTH);
+            format!("cert:{fingerprint}")
+        });
+    ClientIdentity(fingerprint.unwrap_or_else(|| "anonymous".to_owned()).into())
+}
+
+/// Compare the fixed-length digests of the tokens,
+/// so neither the matched prefix nor the token length leaks through the timing
+fn tokens_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (Sha256::digest(a), Sha256::digest(b));
+    a.iter().zip(&b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presentation::grpc::test::{self, EchoServiceClient, EchoServiceServer, TestEchoRequest};
+    use rcgen::{BasicConstraints, CertificateParams, CertifiedIssuer, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+    use std::net::SocketAddr;
+    use tempfile::TempDir;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::{
+        Code,
+        service::InterceptorLayer,
+        transport::{Channel, ClientTlsConfig, Server},
+    };
+
+    /// PEMs of the CA and of the certificates it issued, generated for every test run
+    struct Certs {
+        ca: String,
+        server: (String, String),
+        client: (String, String),
+    }
+
+    impl Certs {
+        fn generate() -> Self {
+            let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+            ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            let ca = CertifiedIssuer::self_signed(ca_params, KeyPair::generate().unwrap()).unwrap();
+
+            let issue = |names: Vec<String>, usage: ExtendedKeyUsagePurpose| {
+                let key = KeyPair::generate().unwrap();
+                let mut params = CertificateParams::new(names).unwrap();
+                params.extended_key_usages = vec![usage];
+                (params.signed_by(&key, &ca).unwrap().pem(), key.serialize_pem())
+            };
+
+            Self {
+                server: issue(vec!["localhost".to_owned()], ExtendedKeyUsagePurpose::ServerAuth),
+                client: issue(vec![], ExtendedKeyUsagePurpose::ClientAuth),
+                ca: ca.pem(),
+            }
+        }
+    }
+
+    fn auth() -> Auth {
+        Auth::new(&config::Auth {
+            clients: vec![config::AuthClient {
+                name: "orchestrator".into(),
+                token: "secret".into(),
+            }],
+        })
+    }
+
+    fn with_token(token: &str) -> Request<()> {
+        let mut request = Request::new(());
+        request
+            .metadata_mut()
+            .insert("authorization", format!("Bearer {token}").parse().unwrap());
+        request
+    }
+
+    async fn connect(addr: SocketAddr, tls: ClientTlsConfig) -> EchoServiceClient<Channel> {
+        let channel = Channel::from_shared(format!("https://{addr}"))
+            .unwrap()
+            .tls_config(tls)
+            .unwrap()
+            .connect_lazy();
+        EchoServiceClient::new(channel)
+    }
+
+    #[test]
+    fn test_token_auth() {
+        let mut auth = auth();
+
+        let request = auth.call(with_token("secret")).unwrap();
+        assert_eq!(identity(&request), "orchestrator");
+        assert_eq!(auth.call(with_token("invalid")).unwrap_err().code(), Code::Unauthenticated);
+        assert_eq!(auth.call(with_token("secret2")).unwrap_err().code(), Code::Unauthenticated);
+        assert_eq!(auth.call(Request::new(())).unwrap_err().code(), Code::Unauthenticated);
+
+        let request = Auth::new(&config::Auth::default()).call(Request::new(())).unwrap();
+        assert_eq!(identity(&request), "anonymous");
+    }
+
+    #[tokio::test]
+    async fn test_mutual_tls_with_token() {
+        let certs = Certs::generate();
+        let dir = TempDir::new().unwrap();
+        let write = |name: &str, content: &str| {
+            let path = dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            path.to_string_lossy().into()
+        };
+
+        let tls = tls_config(&config::Tls {
+            cert: write("server.pem", &certs.server.0),
+            key: write("server.key", &certs.server.1),
+            client_ca: Some(write("ca.pem", &certs.ca)),
+        })
+        .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .tls_config(tls)
+                .unwrap()
+                .layer(InterceptorLayer::new(auth()))
+                .add_service(EchoServiceServer::new(test::Service))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let client_tls = ClientTlsConfig::new()
+            .domain_name("localhost")
+            .ca_certificate(Certificate::from_pem(&certs.ca));
+        let mut client = connect(
+            addr,
+            client_tls.clone().identity(Identity::from_pem(&certs.client.0, &certs.client.1)),
+        )
+        .await;
+
+        let mut request = Request::new(TestEchoRequest { message: "ping".into() });
+        request.metadata_mut().insert("authorization", "Bearer secret".parse().unwrap());
+        assert_eq!(client.echo(request).await.unwrap().into_inner().message, "ping");
+
+        let request = Request::new(TestEchoRequest { message: "ping".into() });
+        assert_eq!(client.echo(request).await.unwrap_err().code(), Code::Unauthenticated);
+
+        // Handshake fails without the client certificate, so the token doesn't matter
+        let mut client = connect(addr, client_tls).await;
+        let mut request = Request::new(TestEchoRequest { message: "ping".into() });
+        request.metadata_mut().insert("authorization", "Bearer secret".parse().unwrap());
+        assert!(client.echo(request).await.is_err());
+    }
+}
@@ -3,6 +3,8 @@ mod generated {
 }
 pub use generated::echo_service_server::EchoServiceServer;
 use generated::{EchoRequest, EchoResponse, echo_service_server::EchoService};
+#[cfg(test)]
+pub use generated::{EchoRequest as TestEchoRequest, echo_service_client::EchoServiceClient};
 use tonic::{Request, Response, Status, async_trait};
 
 #[derive(Debug, Clone)]
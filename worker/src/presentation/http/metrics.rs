@@ -0,0 +1,9 @@
+use axum::{http::header::CONTENT_TYPE, response::IntoResponse};
+use prometheus::TEXT_FORMAT;
+
+use crate::adapters::metrics;
+
+/// Metrics in the Prometheus text format, the endpoint isn't covered by the gRPC authentication
+pub async fn handler() -> impl IntoResponse {
+    ([(CONTENT_TYPE, TEXT_FORMAT)], metrics::encode())
+}
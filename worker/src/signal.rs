@@ -1,4 +1,7 @@
 use tokio::signal;
+use tracing::{error, info};
+
+use crate::config::{Config, ReloadableSections};
 
 /// # Panics
 /// Failed to install signal handler
@@ -23,3 +26,28 @@ pub async fn shutdown_signal() {
         () = terminate => {},
     }
 }
+
+/// Re-read the config on SIGHUP and apply the reloadable sections.
+/// The current config is kept if the new one can't be read or is invalid.
+/// # Panics
+/// Failed to install signal handler
+#[cfg(unix)]
+pub async fn reload_on_hangup(config_path: Box<str>, sections: ReloadableSections) {
+    let mut hangup = signal::unix::signal(signal::unix::SignalKind::hangup()).expect("failed to install signal handler");
+
+    while hangup.recv().await.is_some() {
+        match Config::from_fs(config_path.as_ref()) {
+            Ok(config) => {
+                let max_file_size = config.limits.max_file_size;
+                sections.store(config);
+                info!(max_file_size, "Config reloaded");
+            }
+            Err(err) => error!("Failed to reload config, the current one is kept: {err:#}"),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn reload_on_hangup(_config_path: Box<str>, _sections: ReloadableSections) {
+    std::future::pending::<()>().await;
+}
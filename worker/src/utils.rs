@@ -1,9 +1,14 @@
 mod aspect;
 mod errors;
+mod filename;
 mod macros;
 
+pub mod hash;
+pub mod mp4;
 pub mod thumbnail;
 pub mod url;
+pub mod waveform;
 
 pub use aspect::calculate_aspect_ratio;
 pub use errors::format_error_report;
+pub use filename::suggest_filename;
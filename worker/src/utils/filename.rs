@@ -0,0 +1,44 @@
+const MAX_FILENAME_LENGTH: usize = 128;
+
+/// Make a filename from the title, replacing characters that aren't allowed in filenames
+#[must_use]
+pub fn suggest_filename(title: &str, part: Option<(u32, u32)>, extension: &str) -> String {
+    let mut name: String = title
+        .chars()
+        .map(|char| match char {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            char if char.is_control() => '_',
+            char => char,
+        })
+        .take(MAX_FILENAME_LENGTH)
+        .collect::<String>()
+        .trim()
+        .trim_matches('.')
+        .to_owned();
+    if name.is_empty() {
+        name.push_str("media");
+    }
+    if let Some((index, count)) = part {
+        name = format!("{name} (part {} of {count})", index + 1);
+    }
+    format!("{name}.{extension}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggest_filename() {
+        assert_eq!(suggest_filename("Artist - Song: Live", None, "m4a"), "Artist - Song_ Live.m4a");
+        assert_eq!(suggest_filename("  a/b\n ", None, "mp4"), "a_b_.mp4");
+        assert_eq!(suggest_filename("...", None, "mp4"), "media.mp4");
+        assert_eq!(suggest_filename("Lecture", Some((0, 3)), "mp4"), "Lecture (part 1 of 3).mp4");
+    }
+
+    #[test]
+    fn test_suggest_filename_truncates_long_title() {
+        let filename = suggest_filename(&"a".repeat(500), None, "mp4");
+        assert_eq!(filename.len(), MAX_FILENAME_LENGTH + ".mp4".len());
+    }
+}
@@ -0,0 +1,39 @@
+use sha2::{Digest as _, Sha256};
+use std::{fmt::Write as _, io, path::Path};
+use tokio::io::AsyncReadExt as _;
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Calculate `SHA-256` of the file content
+/// # Returns
+/// Returns the lowercase hex digest
+pub async fn sha256_file(path: impl AsRef<Path>) -> Result<String, io::Error> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// Calculate `SHA-256` of the data
+/// # Returns
+/// Returns the lowercase hex digest
+#[must_use]
+pub fn sha256(data: impl AsRef<[u8]>) -> String {
+    to_hex(&Sha256::digest(data))
+}
+
+fn to_hex(digest: &[u8]) -> String {
+    let mut output = String::with_capacity(digest.len() * 2);
+    for byte in digest {
+        write!(&mut output, "{byte:02x}").unwrap();
+    }
+    output
+}
@@ -0,0 +1,71 @@
+use std::io::{self, SeekFrom};
+use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncSeek, AsyncSeekExt as _};
+
+/// Check that the `moov` atom is placed before `mdat`, so the file can be played before it's fully downloaded.
+/// Only top-level boxes are read, their content is skipped.
+pub async fn is_faststart<R>(reader: &mut R) -> Result<bool, io::Error>
+where
+    R: AsyncRead + AsyncSeek + Unpin,
+{
+    let mut header = [0u8; 8];
+    loop {
+        match reader.read_exact(&mut header).await {
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+            Err(err) => return Err(err),
+        }
+        match &header[4..] {
+            b"moov" => return Ok(true),
+            b"mdat" => return Ok(false),
+            _ => {}
+        }
+
+        let size = u64::from(u32::from_be_bytes([header[0], header[1], header[2], header[3]]));
+        let skip = match size {
+            // The box extends to the end of the file
+            0 => return Ok(false),
+            // The real size is stored in the next 8 bytes
+            1 => {
+                let mut largesize = [0u8; 8];
+                reader.read_exact(&mut largesize).await?;
+                u64::from_be_bytes(largesize).checked_sub(16)
+            }
+            size => size.checked_sub(8),
+        };
+        let Some(skip) = skip.and_then(|skip| i64::try_from(skip).ok()) else {
+            return Ok(false);
+        };
+        reader.seek(SeekFrom::Current(skip)).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn atom(kind: &[u8; 4], content_len: u32) -> Vec<u8> {
+        let mut atom = (content_len + 8).to_be_bytes().to_vec();
+        atom.extend_from_slice(kind);
+        atom.extend(vec![0u8; content_len as usize]);
+        atom
+    }
+
+    #[tokio::test]
+    async fn test_moov_before_mdat() {
+        let data = [atom(b"ftyp", 16), atom(b"moov", 32), atom(b"mdat", 64)].concat();
+        assert!(is_faststart(&mut Cursor::new(data)).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_mdat_before_moov() {
+        let data = [atom(b"ftyp", 16), atom(b"mdat", 64), atom(b"moov", 32)].concat();
+        assert!(!is_faststart(&mut Cursor::new(data)).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_not_mp4() {
+        assert!(!is_faststart(&mut Cursor::new(b"\x1aE\xdf\xa3".to_vec())).await.unwrap());
+    }
+}
@@ -1,120 +1,905 @@
-use thiserror::Error;
-use url::Url;
+use std::{
+    borrow::Cow,
+    fmt::{self, Display, Formatter},
+};
+use url::{Host, Url};
 
-#[derive(Debug, Error)]
-pub enum GetVideoIdErrorKind {
-    #[error("Invalid URL format")]
-    InvalidUrl,
-    #[error("No video ID found")]
-    NoVideoIdFound,
-}
+const YOUTUBE_ID_LENGTH: usize = 11;
+const YOUTUBE_CLIP_ID_LENGTH: usize = 36;
 
-const VIDEO_ID_LENGTH: usize = 11;
+/// Paths of the sites that aren't users or channels
+const SOUNDCLOUD_RESERVED: [&str; 12] = [
+    "discover",
+    "stream",
+    "search",
+    "upload",
+    "charts",
+    "you",
+    "pages",
+    "settings",
+    "messages",
+    "notifications",
+    "people",
+    "tags",
+];
+const SOUNDCLOUD_USER_PAGES: [&str; 10] = [
+    "likes",
+    "tracks",
+    "albums",
+    "reposts",
+    "followers",
+    "following",
+    "popular-tracks",
+    "comments",
+    "spotlight",
+    "playlists",
+];
+const TWITCH_RESERVED: [&str; 14] = [
+    "directory",
+    "videos",
+    "settings",
+    "search",
+    "downloads",
+    "p",
+    "login",
+    "signup",
+    "subscriptions",
+    "inventory",
+    "wallet",
+    "drops",
+    "turbo",
+    "jobs",
+];
 
-pub fn get_video_id(input: &str) -> Result<String, GetVideoIdErrorKind> {
-    let url = Url::parse(input).map_err(|_| GetVideoIdErrorKind::InvalidUrl)?;
-    let host = url.host_str().ok_or(GetVideoIdErrorKind::InvalidUrl)?;
+#[derive(thiserror::Error, Debug)]
+pub enum ErrorKind {
+    #[error("Invalid URL: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+    #[error("Unsupported site")]
+    Unsupported,
+    #[error("No media ID found")]
+    NoMediaId,
+    #[error("Short link must be resolved first")]
+    ShortLink,
+}
 
-    if host.ends_with("youtube.com") {
-        if let Some(query_pairs) = url.query_pairs().find(|(key, _)| key == "v") {
-            let id = query_pairs.1.to_string();
-            if id.len() == VIDEO_ID_LENGTH {
-                return Ok(id);
-            }
-        }
+/// Site of the media, named after the `yt-dlp` extractor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extractor {
+    Youtube,
+    YoutubeClip,
+    Vimeo,
+    TikTok,
+    Instagram,
+    Twitter,
+    Reddit,
+    SoundCloud,
+    SoundCloudSet,
+    TwitchVod,
+    TwitchClip,
+    TwitchStream,
+}
 
-        let path_segments: Vec<&str> = url.path_segments().map(Iterator::collect).unwrap_or_default();
-        if path_segments.len() > 1 {
-            match path_segments[0] {
-                "embed" | "v" | "shorts" if path_segments[1].len() == VIDEO_ID_LENGTH => return Ok(path_segments[1].to_string()),
-                "clip" => return Ok(path_segments[1].to_string()),
-                _ => {}
-            }
-        }
-    } else if host == "youtu.be" {
-        let path_segments: Vec<&str> = url.path_segments().map(Iterator::collect).unwrap_or_default();
-        if let Some(id) = path_segments.first() {
-            if id.len() == VIDEO_ID_LENGTH {
-                return Ok((*id).to_owned());
-            }
+impl Extractor {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Youtube => "youtube",
+            Self::YoutubeClip => "youtube:clip",
+            Self::Vimeo => "vimeo",
+            Self::TikTok => "tiktok",
+            Self::Instagram => "instagram",
+            Self::Twitter => "twitter",
+            Self::Reddit => "reddit",
+            Self::SoundCloud => "soundcloud",
+            Self::SoundCloudSet => "soundcloud:set",
+            Self::TwitchVod => "twitch:vod",
+            Self::TwitchClip => "twitch:clips",
+            Self::TwitchStream => "twitch:stream",
         }
     }
+}
 
-    Err(GetVideoIdErrorKind::NoVideoIdFound)
+impl Display for Extractor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Media identity, the same for all URL shapes of the media
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaIdentity {
+    pub extractor: Extractor,
+    pub id: String,
+    /// URL without the tracking params
+    pub url: String,
+}
 
-    #[test]
-    fn test_valid_url_standard() {
-        assert_eq!(get_video_id("https://www.youtube.com/watch?v=abcdefghijk").unwrap(), "abcdefghijk");
+impl MediaIdentity {
+    fn new(extractor: Extractor, id: impl Into<String>, url: String) -> Self {
+        Self {
+            extractor,
+            id: id.into(),
+            url,
+        }
     }
+}
 
-    #[test]
-    fn test_valid_url_with_params() {
-        assert_eq!(
-            get_video_id("https://www.youtube.com/watch?v=abcdefghijk&si=someparam").unwrap(),
-            "abcdefghijk"
-        );
-    }
+type Canonicalizer = fn(&str, &Url, &[&str]) -> Result<MediaIdentity, ErrorKind>;
 
-    #[test]
-    fn test_valid_url_youtu_be_with_params() {
-        assert_eq!(get_video_id("https://youtu.be/abcdefghijk?si=someparam").unwrap(), "abcdefghijk");
+/// Sites by their domains, subdomains are matched too
+const SITES: [(&[&str], Canonicalizer); 8] = [
+    (&["youtube.com", "youtube-nocookie.com", "youtu.be"], youtube),
+    (&["vimeo.com"], vimeo),
+    (&["tiktok.com"], tiktok),
+    (&["instagram.com", "instagr.am"], instagram),
+    (
+        &[
+            "twitter.com",
+            "x.com",
+            "fxtwitter.com",
+            "vxtwitter.com",
+            "fixupx.com",
+            "fixvx.com",
+            "t.co",
+        ],
+        twitter,
+    ),
+    (&["reddit.com", "redd.it"], reddit),
+    (&["soundcloud.com"], soundcloud),
+    (&["twitch.tv"], twitch),
+];
+
+/// Map the URL to the media identity, so the services agree on the cache keys
+/// # Errors
+/// Returns [`ErrorKind::ShortLink`] if the URL has to be followed to get the media,
+/// other errors if the URL isn't a media of the supported sites
+pub fn canonicalize(input: &str) -> Result<MediaIdentity, ErrorKind> {
+    let url = Url::parse(input.trim())?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(ErrorKind::Unsupported);
     }
+    let Some(Host::Domain(host)) = url.host() else {
+        return Err(ErrorKind::Unsupported);
+    };
+    let host = host.trim_end_matches('.');
+    let segments = url
+        .path_segments()
+        .map(|segments| segments.filter(|segment| !segment.is_empty()).collect::<Vec<_>>())
+        .unwrap_or_default();
 
-    #[test]
-    fn test_valid_url_mobile() {
-        assert_eq!(get_video_id("https://m.youtube.com/watch?v=abcdefghijk").unwrap(), "abcdefghijk");
+    let (_, canonicalizer) = SITES
+        .iter()
+        .find(|(domains, _)| domains.iter().any(|domain| is_subdomain(host, domain)))
+        .ok_or(ErrorKind::Unsupported)?;
+    canonicalizer(host, &url, &segments)
+}
+
+fn is_subdomain(host: &str, domain: &str) -> bool {
+    host.strip_suffix(domain)
+        .is_some_and(|prefix| prefix.is_empty() || prefix.ends_with('.'))
+}
+
+fn query<'a>(url: &'a Url, key: &str) -> Option<Cow<'a, str>> {
+    url.query_pairs().find(|(name, _)| name == key).map(|(_, value)| value)
+}
+
+fn is_id(id: &str, len: usize) -> bool {
+    id.len() == len && is_slug(id)
+}
+
+fn is_slug(id: &str) -> bool {
+    !id.is_empty() && id.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+fn is_numeric(id: &str) -> bool {
+    !id.is_empty() && id.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+fn youtube(host: &str, url: &Url, segments: &[&str]) -> Result<MediaIdentity, ErrorKind> {
+    let id = if host == "youtu.be" {
+        segments.first().map(|id| Cow::Borrowed(*id))
+    } else {
+        match segments {
+            ["watch"] => query(url, "v"),
+            // Embedded playlist, its name has the length of an ID
+            ["embed", "videoseries", ..] => None,
+            ["embed" | "v" | "e" | "shorts" | "live", id, ..] => Some(Cow::Borrowed(*id)),
+            ["clip", id, ..] if is_id(id, YOUTUBE_CLIP_ID_LENGTH) => {
+                return Ok(MediaIdentity::new(
+                    Extractor::YoutubeClip,
+                    *id,
+                    format!("https://www.youtube.com/clip/{id}"),
+                ));
+            }
+            // Shared links of the old apps: `/attribution_link?u=/watch?v=<id>`
+            ["attribution_link"] => query(url, "u")
+                .and_then(|path| Url::parse("https://www.youtube.com").ok()?.join(&path).ok())
+                .and_then(|url| query(&url, "v").map(Cow::into_owned))
+                .map(Cow::Owned),
+            _ => None,
+        }
+    };
+    let id = id.filter(|id| is_id(id, YOUTUBE_ID_LENGTH)).ok_or(ErrorKind::NoMediaId)?;
+
+    // Music player is kept, its formats are selected for the audio
+    let url = if host == "music.youtube.com" {
+        format!("https://music.youtube.com/watch?v={id}")
+    } else {
+        format!("https://www.youtube.com/watch?v={id}")
+    };
+    Ok(MediaIdentity::new(Extractor::Youtube, id, url))
+}
+
+fn vimeo(host: &str, url: &Url, segments: &[&str]) -> Result<MediaIdentity, ErrorKind> {
+    let (id, hash) = match segments {
+        ["video", id, ..] if host == "player.vimeo.com" => (*id, query(url, "h")),
+        [id, hash, ..] if is_numeric(id) => (*id, Some(Cow::Borrowed(*hash))),
+        [id] => (*id, None),
+        ["channels", _, id, ..] | ["groups", _, "videos", id, ..] | ["album" | "showcase", _, "video", id, ..] => (*id, None),
+        _ => return Err(ErrorKind::NoMediaId),
+    };
+    if !is_numeric(id) {
+        return Err(ErrorKind::NoMediaId);
     }
 
-    #[test]
-    fn test_valid_url_embed() {
-        assert_eq!(get_video_id("https://www.youtube.com/embed/abcdefghijk").unwrap(), "abcdefghijk");
+    // Hash of the unlisted video is required to access it
+    let url = match hash.filter(|hash| hash.bytes().all(|byte| byte.is_ascii_alphanumeric())) {
+        Some(hash) => format!("https://vimeo.com/{id}/{hash}"),
+        None => format!("https://vimeo.com/{id}"),
+    };
+    Ok(MediaIdentity::new(Extractor::Vimeo, id, url))
+}
+
+fn tiktok(host: &str, _url: &Url, segments: &[&str]) -> Result<MediaIdentity, ErrorKind> {
+    if matches!(host, "vm.tiktok.com" | "vt.tiktok.com") || matches!(segments, ["t", ..]) {
+        return Err(ErrorKind::ShortLink);
+    }
+    let id = match segments {
+        [user, "video" | "photo", id, ..] if user.starts_with('@') => *id,
+        ["embed", "v2", id, ..] | ["embed", id, ..] | ["share", "video", id, ..] => *id,
+        ["v", id, ..] => id.strip_suffix(".html").unwrap_or(id),
+        _ => return Err(ErrorKind::NoMediaId),
+    };
+    if !is_numeric(id) {
+        return Err(ErrorKind::NoMediaId);
     }
 
-    #[test]
-    fn test_valid_url_shorts() {
-        assert_eq!(get_video_id("https://www.youtube.com/shorts/abcdefghijk").unwrap(), "abcdefghijk");
+    // Any user name is redirected to the author of the video
+    Ok(MediaIdentity::new(
+        Extractor::TikTok,
+        id,
+        format!("https://www.tiktok.com/@_/video/{id}"),
+    ))
+}
+
+fn instagram(_host: &str, _url: &Url, segments: &[&str]) -> Result<MediaIdentity, ErrorKind> {
+    let code = match segments {
+        ["share", ..] => return Err(ErrorKind::ShortLink),
+        ["p" | "reel" | "reels" | "tv", code, ..] | [_, "p" | "reel", code, ..] => *code,
+        _ => return Err(ErrorKind::NoMediaId),
+    };
+    if !is_slug(code) {
+        return Err(ErrorKind::NoMediaId);
     }
 
-    #[test]
-    fn test_valid_url_clips() {
-        assert_eq!(
-            get_video_id("https://youtube.com/clip/UgkxAunuGm-TAKLTuefe7EpYiyFPLfTWR28D?si=pC4WroQ7YCmPm3wO").unwrap(),
-            "UgkxAunuGm-TAKLTuefe7EpYiyFPLfTWR28D"
-        );
+    Ok(MediaIdentity::new(
+        Extractor::Instagram,
+        code,
+        format!("https://www.instagram.com/p/{code}/"),
+    ))
+}
+
+fn twitter(host: &str, _url: &Url, segments: &[&str]) -> Result<MediaIdentity, ErrorKind> {
+    if host == "t.co" {
+        return Err(ErrorKind::ShortLink);
+    }
+    let id = match segments {
+        ["i", "web", "status", id, ..] | [_, "status" | "statuses", id, ..] | ["statuses", id, ..] => *id,
+        _ => return Err(ErrorKind::NoMediaId),
+    };
+    if !is_numeric(id) {
+        return Err(ErrorKind::NoMediaId);
     }
 
-    #[test]
-    fn test_valid_url_youtu_be() {
-        assert_eq!(get_video_id("https://youtu.be/abcdefghijk").unwrap(), "abcdefghijk");
+    Ok(MediaIdentity::new(Extractor::Twitter, id, format!("https://x.com/i/status/{id}")))
+}
+
+fn reddit(host: &str, _url: &Url, segments: &[&str]) -> Result<MediaIdentity, ErrorKind> {
+    // Video host has its own ids, the post is known after the redirect only
+    if host == "v.redd.it" || matches!(segments, ["r", _, "s", ..]) {
+        return Err(ErrorKind::ShortLink);
+    }
+    let id = match segments {
+        [id] if host == "redd.it" => *id,
+        ["r" | "u" | "user", _, "comments", id, ..] | ["comments", id, ..] => *id,
+        _ => return Err(ErrorKind::NoMediaId),
+    };
+    if !id.bytes().all(|byte| byte.is_ascii_alphanumeric()) {
+        return Err(ErrorKind::NoMediaId);
     }
+    // Ids are base36
+    let id = id.to_ascii_lowercase();
 
-    #[test]
-    fn test_invalid_url_example() {
-        assert!(get_video_id("https://www.example.com/watch?v=abcdefghijk").is_err());
+    let url = format!("https://www.reddit.com/comments/{id}/");
+    Ok(MediaIdentity::new(Extractor::Reddit, id, url))
+}
+
+fn soundcloud(host: &str, _url: &Url, segments: &[&str]) -> Result<MediaIdentity, ErrorKind> {
+    if host == "on.soundcloud.com" {
+        return Err(ErrorKind::ShortLink);
     }
+    let Some(user) = segments.first().filter(|user| !SOUNDCLOUD_RESERVED.contains(*user)) else {
+        return Err(ErrorKind::NoMediaId);
+    };
+    // Permalinks are case insensitive
+    let user = user.to_ascii_lowercase();
 
-    #[test]
-    fn test_invalid_url_non_video_path() {
-        assert!(get_video_id("https://youtube.com/someotherpath").is_err());
+    match segments[1..] {
+        ["sets", set, ..] => {
+            let id = format!("{user}/sets/{}", set.to_ascii_lowercase());
+            let url = format!("https://soundcloud.com/{id}");
+            Ok(MediaIdentity::new(Extractor::SoundCloudSet, id, url))
+        }
+        [track, ref rest @ ..] if !SOUNDCLOUD_USER_PAGES.contains(&track) => {
+            let id = format!("{user}/{}", track.to_ascii_lowercase());
+            // Secret token of the private track is required to access it
+            let url = match rest.first().filter(|token| token.starts_with("s-")) {
+                Some(token) => format!("https://soundcloud.com/{id}/{token}"),
+                None => format!("https://soundcloud.com/{id}"),
+            };
+            Ok(MediaIdentity::new(Extractor::SoundCloud, id, url))
+        }
+        _ => Err(ErrorKind::NoMediaId),
     }
+}
+
+fn twitch(host: &str, url: &Url, segments: &[&str]) -> Result<MediaIdentity, ErrorKind> {
+    let vod = |id: &str| {
+        let id = id.strip_prefix('v').unwrap_or(id);
+        is_numeric(id).then(|| MediaIdentity::new(Extractor::TwitchVod, format!("v{id}"), format!("https://www.twitch.tv/videos/{id}")))
+    };
+    let clip =
+        |slug: &str| is_slug(slug).then(|| MediaIdentity::new(Extractor::TwitchClip, slug, format!("https://clips.twitch.tv/{slug}")));
+    let stream = |channel: &str| {
+        let channel = channel.to_ascii_lowercase();
+        let url = format!("https://www.twitch.tv/{channel}");
+        (is_slug(&channel) && !TWITCH_RESERVED.contains(&channel.as_str()))
+            .then(|| MediaIdentity::new(Extractor::TwitchStream, channel, url))
+    };
+
+    let identity = match (host, segments) {
+        ("clips.twitch.tv", ["embed"]) => query(url, "clip").and_then(|slug| clip(&slug)),
+        ("clips.twitch.tv", [slug]) => clip(slug),
+        ("player.twitch.tv", _) => query(url, "video")
+            .and_then(|id| vod(&id))
+            .or_else(|| query(url, "clip").and_then(|slug| clip(&slug)))
+            .or_else(|| query(url, "channel").and_then(|channel| stream(&channel))),
+        (_, ["videos", id, ..] | [_, "v" | "video", id, ..]) => vod(id),
+        (_, [_, "clip", slug, ..]) => clip(slug),
+        (_, [channel]) => stream(channel),
+        _ => None,
+    };
+    identity.ok_or(ErrorKind::NoMediaId)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Input, extractor, media id and canonical URL
+    const FIXTURES: &[(&str, &str, &str, &str)] = &[
+        // YouTube
+        (
+            "https://www.youtube.com/watch?v=abcdefghijk",
+            "youtube",
+            "abcdefghijk",
+            "https://www.youtube.com/watch?v=abcdefghijk",
+        ),
+        (
+            "https://www.youtube.com/watch?v=abcdefghijk&si=someparam",
+            "youtube",
+            "abcdefghijk",
+            "https://www.youtube.com/watch?v=abcdefghijk",
+        ),
+        (
+            "https://www.youtube.com/watch?feature=share&v=dQw4w9WgXcQ&t=42s",
+            "youtube",
+            "dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        ),
+        (
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PL1234567890&index=2",
+            "youtube",
+            "dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        ),
+        (
+            "http://youtube.com/watch?v=dQw4w9WgXcQ",
+            "youtube",
+            "dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        ),
+        (
+            "https://m.youtube.com/watch?v=abcdefghijk",
+            "youtube",
+            "abcdefghijk",
+            "https://www.youtube.com/watch?v=abcdefghijk",
+        ),
+        (
+            "https://WWW.YouTube.com/watch?v=dQw4w9WgXcQ",
+            "youtube",
+            "dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        ),
+        (
+            "https://www.youtube.com/embed/abcdefghijk",
+            "youtube",
+            "abcdefghijk",
+            "https://www.youtube.com/watch?v=abcdefghijk",
+        ),
+        (
+            "https://www.youtube.com/embed/dQw4w9WgXcQ?autoplay=1",
+            "youtube",
+            "dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        ),
+        (
+            "https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ?rel=0",
+            "youtube",
+            "dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        ),
+        (
+            "https://www.youtube.com/v/dQw4w9WgXcQ",
+            "youtube",
+            "dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        ),
+        (
+            "https://www.youtube.com/e/dQw4w9WgXcQ",
+            "youtube",
+            "dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        ),
+        (
+            "https://www.youtube.com/shorts/abcdefghijk",
+            "youtube",
+            "abcdefghijk",
+            "https://www.youtube.com/watch?v=abcdefghijk",
+        ),
+        (
+            "https://youtube.com/shorts/dQw4w9WgXcQ?si=abcdef&feature=share",
+            "youtube",
+            "dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        ),
+        (
+            "https://www.youtube.com/live/dQw4w9WgXcQ?si=abc",
+            "youtube",
+            "dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        ),
+        (
+            "https://www.youtube.com/attribution_link?a=xyz&u=%2Fwatch%3Fv%3DdQw4w9WgXcQ%26feature%3Dshare",
+            "youtube",
+            "dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        ),
+        (
+            "https://youtu.be/abcdefghijk",
+            "youtube",
+            "abcdefghijk",
+            "https://www.youtube.com/watch?v=abcdefghijk",
+        ),
+        (
+            "https://youtu.be/abcdefghijk?si=someparam",
+            "youtube",
+            "abcdefghijk",
+            "https://www.youtube.com/watch?v=abcdefghijk",
+        ),
+        (
+            "https://youtu.be/dQw4w9WgXcQ?t=10&utm_source=twitter",
+            "youtube",
+            "dQw4w9WgXcQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+        ),
+        (
+            "https://youtube.com/clip/UgkxAunuGm-TAKLTuefe7EpYiyFPLfTWR28D?si=pC4WroQ7YCmPm3wO",
+            "youtube:clip",
+            "UgkxAunuGm-TAKLTuefe7EpYiyFPLfTWR28D",
+            "https://www.youtube.com/clip/UgkxAunuGm-TAKLTuefe7EpYiyFPLfTWR28D",
+        ),
+        // YouTube Music
+        (
+            "https://music.youtube.com/watch?v=dQw4w9WgXcQ&feature=share",
+            "youtube",
+            "dQw4w9WgXcQ",
+            "https://music.youtube.com/watch?v=dQw4w9WgXcQ",
+        ),
+        (
+            "https://music.youtube.com/watch?v=dQw4w9WgXcQ&list=RDAMVMdQw4w9WgXcQ",
+            "youtube",
+            "dQw4w9WgXcQ",
+            "https://music.youtube.com/watch?v=dQw4w9WgXcQ",
+        ),
+        // Vimeo
+        ("https://vimeo.com/76979871", "vimeo", "76979871", "https://vimeo.com/76979871"),
+        (
+            "https://www.vimeo.com/76979871?share=copy",
+            "vimeo",
+            "76979871",
+            "https://vimeo.com/76979871",
+        ),
+        (
+            "https://vimeo.com/76979871/9c8b5a6e1f",
+            "vimeo",
+            "76979871",
+            "https://vimeo.com/76979871/9c8b5a6e1f",
+        ),
+        (
+            "https://player.vimeo.com/video/76979871?h=9c8b5a6e1f&badge=0",
+            "vimeo",
+            "76979871",
+            "https://vimeo.com/76979871/9c8b5a6e1f",
+        ),
+        (
+            "https://player.vimeo.com/video/76979871",
+            "vimeo",
+            "76979871",
+            "https://vimeo.com/76979871",
+        ),
+        (
+            "https://vimeo.com/channels/staffpicks/76979871",
+            "vimeo",
+            "76979871",
+            "https://vimeo.com/76979871",
+        ),
+        (
+            "https://vimeo.com/groups/shortfilms/videos/76979871",
+            "vimeo",
+            "76979871",
+            "https://vimeo.com/76979871",
+        ),
+        (
+            "https://vimeo.com/showcase/1234567/video/76979871",
+            "vimeo",
+            "76979871",
+            "https://vimeo.com/76979871",
+        ),
+        // TikTok
+        (
+            "https://www.tiktok.com/@scout2015/video/6718335390845095173",
+            "tiktok",
+            "6718335390845095173",
+            "https://www.tiktok.com/@_/video/6718335390845095173",
+        ),
+        (
+            "https://www.tiktok.com/@scout2015/video/6718335390845095173?is_from_webapp=1&sender_device=pc&web_id=123",
+            "tiktok",
+            "6718335390845095173",
+            "https://www.tiktok.com/@_/video/6718335390845095173",
+        ),
+        (
+            "https://www.tiktok.com/@user.name/photo/7216121176445873450",
+            "tiktok",
+            "7216121176445873450",
+            "https://www.tiktok.com/@_/video/7216121176445873450",
+        ),
+        (
+            "https://m.tiktok.com/v/6718335390845095173.html",
+            "tiktok",
+            "6718335390845095173",
+            "https://www.tiktok.com/@_/video/6718335390845095173",
+        ),
+        (
+            "https://www.tiktok.com/embed/v2/6718335390845095173",
+            "tiktok",
+            "6718335390845095173",
+            "https://www.tiktok.com/@_/video/6718335390845095173",
+        ),
+        (
+            "https://www.tiktok.com/embed/6718335390845095173",
+            "tiktok",
+            "6718335390845095173",
+            "https://www.tiktok.com/@_/video/6718335390845095173",
+        ),
+        // Instagram
+        (
+            "https://www.instagram.com/p/CrnWbZ6yZ0J/",
+            "instagram",
+            "CrnWbZ6yZ0J",
+            "https://www.instagram.com/p/CrnWbZ6yZ0J/",
+        ),
+        (
+            "https://www.instagram.com/p/CrnWbZ6yZ0J/?igsh=MWQ1ZGUxMzBkMA==",
+            "instagram",
+            "CrnWbZ6yZ0J",
+            "https://www.instagram.com/p/CrnWbZ6yZ0J/",
+        ),
+        (
+            "https://www.instagram.com/reel/Cq_Zw-dL9aB/?utm_source=ig_web_copy_link",
+            "instagram",
+            "Cq_Zw-dL9aB",
+            "https://www.instagram.com/p/Cq_Zw-dL9aB/",
+        ),
+        (
+            "https://instagram.com/reels/Cq_Zw-dL9aB",
+            "instagram",
+            "Cq_Zw-dL9aB",
+            "https://www.instagram.com/p/Cq_Zw-dL9aB/",
+        ),
+        (
+            "https://www.instagram.com/tv/B5Yp1XKJbQm/",
+            "instagram",
+            "B5Yp1XKJbQm",
+            "https://www.instagram.com/p/B5Yp1XKJbQm/",
+        ),
+        (
+            "https://www.instagram.com/natgeo/p/CrnWbZ6yZ0J/",
+            "instagram",
+            "CrnWbZ6yZ0J",
+            "https://www.instagram.com/p/CrnWbZ6yZ0J/",
+        ),
+        (
+            "https://instagr.am/p/CrnWbZ6yZ0J",
+            "instagram",
+            "CrnWbZ6yZ0J",
+            "https://www.instagram.com/p/CrnWbZ6yZ0J/",
+        ),
+        // Twitter/X
+        (
+            "https://twitter.com/NASA/status/1650123456789012345",
+            "twitter",
+            "1650123456789012345",
+            "https://x.com/i/status/1650123456789012345",
+        ),
+        (
+            "https://x.com/NASA/status/1650123456789012345?s=20&t=abcdef",
+            "twitter",
+            "1650123456789012345",
+            "https://x.com/i/status/1650123456789012345",
+        ),
+        (
+            "https://mobile.twitter.com/NASA/status/1650123456789012345",
+            "twitter",
+            "1650123456789012345",
+            "https://x.com/i/status/1650123456789012345",
+        ),
+        (
+            "https://x.com/NASA/status/1650123456789012345/video/1",
+            "twitter",
+            "1650123456789012345",
+            "https://x.com/i/status/1650123456789012345",
+        ),
+        (
+            "https://twitter.com/i/web/status/1650123456789012345",
+            "twitter",
+            "1650123456789012345",
+            "https://x.com/i/status/1650123456789012345",
+        ),
+        (
+            "https://x.com/i/status/1650123456789012345",
+            "twitter",
+            "1650123456789012345",
+            "https://x.com/i/status/1650123456789012345",
+        ),
+        (
+            "https://fxtwitter.com/NASA/status/1650123456789012345",
+            "twitter",
+            "1650123456789012345",
+            "https://x.com/i/status/1650123456789012345",
+        ),
+        (
+            "https://vxtwitter.com/NASA/status/1650123456789012345",
+            "twitter",
+            "1650123456789012345",
+            "https://x.com/i/status/1650123456789012345",
+        ),
+        (
+            "https://fixupx.com/NASA/status/1650123456789012345",
+            "twitter",
+            "1650123456789012345",
+            "https://x.com/i/status/1650123456789012345",
+        ),
+        // Reddit
+        (
+            "https://www.reddit.com/r/videos/comments/12abcde/some_title/",
+            "reddit",
+            "12abcde",
+            "https://www.reddit.com/comments/12abcde/",
+        ),
+        (
+            "https://old.reddit.com/r/videos/comments/12abcde/some_title/?utm_source=share&utm_medium=web2x",
+            "reddit",
+            "12abcde",
+            "https://www.reddit.com/comments/12abcde/",
+        ),
+        (
+            "https://new.reddit.com/r/videos/comments/12ABCDE/",
+            "reddit",
+            "12abcde",
+            "https://www.reddit.com/comments/12abcde/",
+        ),
+        (
+            "https://www.reddit.com/user/someone/comments/12abcde/title/",
+            "reddit",
+            "12abcde",
+            "https://www.reddit.com/comments/12abcde/",
+        ),
+        (
+            "https://www.reddit.com/comments/12abcde",
+            "reddit",
+            "12abcde",
+            "https://www.reddit.com/comments/12abcde/",
+        ),
+        (
+            "https://redd.it/12abcde",
+            "reddit",
+            "12abcde",
+            "https://www.reddit.com/comments/12abcde/",
+        ),
+        // SoundCloud
+        (
+            "https://soundcloud.com/forss/flickermood",
+            "soundcloud",
+            "forss/flickermood",
+            "https://soundcloud.com/forss/flickermood",
+        ),
+        (
+            "https://soundcloud.com/forss/flickermood?utm_source=clipboard&in=forss/sets/soulhack",
+            "soundcloud",
+            "forss/flickermood",
+            "https://soundcloud.com/forss/flickermood",
+        ),
+        (
+            "https://m.soundcloud.com/Forss/FlickerMood",
+            "soundcloud",
+            "forss/flickermood",
+            "https://soundcloud.com/forss/flickermood",
+        ),
+        (
+            "https://soundcloud.com/forss/flickermood/s-abcDEF123",
+            "soundcloud",
+            "forss/flickermood",
+            "https://soundcloud.com/forss/flickermood/s-abcDEF123",
+        ),
+        (
+            "https://soundcloud.com/forss/sets/soulhack",
+            "soundcloud:set",
+            "forss/sets/soulhack",
+            "https://soundcloud.com/forss/sets/soulhack",
+        ),
+        // Twitch
+        (
+            "https://www.twitch.tv/videos/1234567890",
+            "twitch:vod",
+            "v1234567890",
+            "https://www.twitch.tv/videos/1234567890",
+        ),
+        (
+            "https://m.twitch.tv/videos/1234567890?t=1h2m3s",
+            "twitch:vod",
+            "v1234567890",
+            "https://www.twitch.tv/videos/1234567890",
+        ),
+        (
+            "https://www.twitch.tv/somechannel/v/1234567890",
+            "twitch:vod",
+            "v1234567890",
+            "https://www.twitch.tv/videos/1234567890",
+        ),
+        (
+            "https://player.twitch.tv/?video=v1234567890&parent=example.com",
+            "twitch:vod",
+            "v1234567890",
+            "https://www.twitch.tv/videos/1234567890",
+        ),
+        (
+            "https://clips.twitch.tv/FunnyClipSlug-AbC123_xyz",
+            "twitch:clips",
+            "FunnyClipSlug-AbC123_xyz",
+            "https://clips.twitch.tv/FunnyClipSlug-AbC123_xyz",
+        ),
+        (
+            "https://clips.twitch.tv/embed?clip=FunnyClipSlug&parent=example.com",
+            "twitch:clips",
+            "FunnyClipSlug",
+            "https://clips.twitch.tv/FunnyClipSlug",
+        ),
+        (
+            "https://www.twitch.tv/somechannel/clip/FunnyClipSlug?filter=clips",
+            "twitch:clips",
+            "FunnyClipSlug",
+            "https://clips.twitch.tv/FunnyClipSlug",
+        ),
+        (
+            "https://www.twitch.tv/SomeChannel",
+            "twitch:stream",
+            "somechannel",
+            "https://www.twitch.tv/somechannel",
+        ),
+        (
+            "https://player.twitch.tv/?channel=somechannel&parent=example.com",
+            "twitch:stream",
+            "somechannel",
+            "https://www.twitch.tv/somechannel",
+        ),
+    ];
+
+    const NO_MEDIA_ID: &[&str] = &[
+        "https://youtube.com/someotherpath",
+        "https://www.youtube.com/",
+        "https://youtu.be/shortvideo",
+        "https://www.youtube.com/watch?v=short",
+        "https://www.youtube.com/clip/abc",
+        "https://www.youtube.com/@channel/live",
+        "https://www.youtube.com/playlist?list=PL1234567890",
+        "https://www.youtube.com/embed/videoseries?list=PL1234567890",
+        "https://vimeo.com/staffpicks",
+        "https://www.tiktok.com/@scout2015",
+        "https://www.instagram.com/natgeo/",
+        "https://www.instagram.com/stories/natgeo/3123456789012345678/",
+        "https://x.com/NASA",
+        "https://twitter.com/NASA/status/notanumber",
+        "https://www.reddit.com/r/videos/",
+        "https://soundcloud.com/forss",
+        "https://soundcloud.com/forss/likes",
+        "https://soundcloud.com/discover/sets/charts-top",
+        "https://www.twitch.tv/directory",
+        "https://www.twitch.tv/",
+    ];
+
+    const SHORT_LINKS: &[&str] = &[
+        "https://vm.tiktok.com/ZMabcdefg/",
+        "https://vt.tiktok.com/ZSabcdefg/",
+        "https://www.tiktok.com/t/ZTabcdefg/",
+        "https://t.co/AbCdEfGhIj",
+        "https://www.reddit.com/r/videos/s/AbCdEfGhIj",
+        "https://v.redd.it/abcdefghijk1",
+        "https://on.soundcloud.com/AbCdEfGh",
+        "https://www.instagram.com/share/AbCdEfGh",
+    ];
+
+    const UNSUPPORTED: &[&str] = &[
+        "https://www.example.com/watch?v=abcdefghijk",
+        "https://notyoutube.com/watch?v=abcdefghijk",
+        "https://youtube.com.example.com/watch?v=abcdefghijk",
+        "ftp://youtube.com/watch?v=abcdefghijk",
+        "https://127.0.0.1/watch?v=abcdefghijk",
+    ];
 
     #[test]
-    fn test_invalid_url_short_id() {
-        assert!(get_video_id("https://youtu.be/shortvideo").is_err());
+    fn test_canonicalize() {
+        for (input, extractor, id, url) in FIXTURES {
+            let identity = canonicalize(input).unwrap_or_else(|err| panic!("{input}: {err}"));
+            assert_eq!(
+                (identity.extractor.as_str(), identity.id.as_str(), identity.url.as_str()),
+                (*extractor, *id, *url),
+                "{input}"
+            );
+        }
     }
 
     #[test]
-    fn test_invalid_url_not_a_url() {
-        assert!(get_video_id("not a url").is_err());
+    fn test_canonical_url_is_stable() {
+        for (input, ..) in FIXTURES {
+            let identity = canonicalize(input).unwrap();
+            assert_eq!(canonicalize(&identity.url).unwrap(), identity, "{input}");
+        }
     }
 
     #[test]
-    fn test_invalid_url_homepage() {
-        assert!(get_video_id("https://www.youtube.com/").is_err());
+    fn test_errors() {
+        for input in NO_MEDIA_ID {
+            assert!(matches!(canonicalize(input), Err(ErrorKind::NoMediaId)), "{input}");
+        }
+        for input in SHORT_LINKS {
+            assert!(matches!(canonicalize(input), Err(ErrorKind::ShortLink)), "{input}");
+        }
+        for input in UNSUPPORTED {
+            assert!(matches!(canonicalize(input), Err(ErrorKind::Unsupported)), "{input}");
+        }
+        assert!(matches!(canonicalize("not a url"), Err(ErrorKind::InvalidUrl(_))));
     }
 }
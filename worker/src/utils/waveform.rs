@@ -0,0 +1,134 @@
+/// Telegram shows at most 100 bars of 5 bits
+pub const MAX_BARS: usize = 100;
+const MAX_BAR_VALUE: u32 = 31;
+const BITS_PER_BAR: usize = 5;
+
+/// Peaks of the fixed-size blocks of samples, so the waveform is computed without keeping all the samples
+#[derive(Debug)]
+pub struct Peaks {
+    block_size: usize,
+    in_block: usize,
+    current: u16,
+    peaks: Vec<u16>,
+    /// Low byte of the sample split between the chunks
+    odd: Option<u8>,
+}
+
+impl Peaks {
+    #[must_use]
+    pub fn new(block_size: usize) -> Self {
+        Self {
+            block_size: block_size.max(1),
+            in_block: 0,
+            current: 0,
+            peaks: vec![],
+            odd: None,
+        }
+    }
+
+    /// Add signed 16-bit little-endian samples
+    pub fn push_pcm(&mut self, mut bytes: &[u8]) {
+        if let Some(low) = self.odd.take() {
+            let Some((&high, rest)) = bytes.split_first() else {
+                self.odd = Some(low);
+                return;
+            };
+            self.push_sample(i16::from_le_bytes([low, high]));
+            bytes = rest;
+        }
+
+        let mut samples = bytes.chunks_exact(2);
+        for sample in samples.by_ref() {
+            self.push_sample(i16::from_le_bytes([sample[0], sample[1]]));
+        }
+        self.odd = samples.remainder().first().copied();
+    }
+
+    fn push_sample(&mut self, sample: i16) {
+        self.current = self.current.max(sample.unsigned_abs());
+        self.in_block += 1;
+        if self.in_block == self.block_size {
+            self.peaks.push(self.current);
+            self.current = 0;
+            self.in_block = 0;
+        }
+    }
+
+    /// Packed waveform of the peaks, empty if there are no samples
+    #[must_use]
+    pub fn waveform(mut self) -> Vec<u8> {
+        if self.in_block > 0 {
+            self.peaks.push(self.current);
+        }
+        pack(&bars(&self.peaks, MAX_BARS))
+    }
+}
+
+/// Max peak of each of the `count` even ranges, scaled to `0..=31` relative to the loudest one
+fn bars(peaks: &[u16], count: usize) -> Vec<u8> {
+    let count = count.min(peaks.len());
+    let max = u32::from(peaks.iter().copied().max().unwrap_or_default());
+
+    (0..count)
+        .map(|index| {
+            let range = &peaks[index * peaks.len() / count..(index + 1) * peaks.len() / count];
+            let peak = u32::from(range.iter().copied().max().unwrap_or_default());
+            #[allow(clippy::cast_possible_truncation)]
+            (peak * MAX_BAR_VALUE).checked_div(max).map_or(0, |value| value as u8)
+        })
+        .collect()
+}
+
+/// Pack the 5-bit values from the lowest bit, as Telegram stores the waveform
+fn pack(bars: &[u8]) -> Vec<u8> {
+    let mut packed = vec![0u8; (bars.len() * BITS_PER_BAR).div_ceil(8)];
+    for (index, &bar) in bars.iter().enumerate() {
+        let offset = index * BITS_PER_BAR;
+        let value = u16::from(bar & 0b1_1111) << (offset % 8);
+        let [low, high] = value.to_le_bytes();
+        packed[offset / 8] |= low;
+        if high != 0 {
+            packed[offset / 8 + 1] |= high;
+        }
+    }
+    packed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack() {
+        assert_eq!(pack(&[]), Vec::<u8>::new());
+        assert_eq!(pack(&[31]), [0b1_1111]);
+        assert_eq!(pack(&[1, 1]), [0b0010_0001, 0]);
+        assert_eq!(pack(&[0, 0, 0, 0, 0, 0, 0, 31]), [0, 0, 0, 0, 0b1111_1000]);
+
+        let packed = pack(&[31; MAX_BARS]);
+        assert_eq!(packed.len(), 63);
+        assert!(packed[..62].iter().all(|byte| *byte == 0xFF));
+        assert_eq!(packed[62], 0x0F);
+    }
+
+    #[test]
+    fn test_bars() {
+        assert_eq!(bars(&[0, 100, 50, 200], 2), [15, 31]);
+        assert_eq!(bars(&[10, 20], 100), [15, 31]);
+        assert_eq!(bars(&[0, 0], 2), [0, 0]);
+        assert_eq!(bars(&[], 100), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_peaks_split_samples() {
+        let samples = [100i16, -300, 50, i16::MIN];
+        let bytes = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect::<Vec<_>>();
+
+        let mut peaks = Peaks::new(2);
+        peaks.push_pcm(&bytes[..3]);
+        peaks.push_pcm(&bytes[3..5]);
+        peaks.push_pcm(&bytes[5..]);
+        assert_eq!(peaks.peaks, [300, 32768]);
+        assert_eq!(peaks.waveform(), pack(&[0, 31]));
+    }
+}
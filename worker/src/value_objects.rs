@@ -1,3 +1,15 @@
+mod animation;
 mod aspect;
+mod codec;
+mod container;
+mod live;
+mod media_kind;
+mod sponsorblock;
 
+pub use animation::AnimationFormat;
 pub use aspect::AspectKind;
+pub use codec::{AudioCodec, VideoCodec};
+pub use container::Container;
+pub use live::LiveStatus;
+pub use media_kind::MediaKind;
+pub use sponsorblock::SponsorBlockCategory;
@@ -0,0 +1,19 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    /// Silent H.264, which Telegram shows as an animation
+    Mp4,
+    Gif,
+    Webm,
+}
+
+impl AnimationFormat {
+    #[inline]
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Mp4 => "mp4",
+            Self::Gif => "gif",
+            Self::Webm => "webm",
+        }
+    }
+}
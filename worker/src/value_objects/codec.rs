@@ -0,0 +1,180 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoCodec {
+    H264,
+    H265,
+    Vp8,
+    Vp9,
+    Av1,
+    Other,
+}
+
+impl VideoCodec {
+    /// Parse the codec by the `ffprobe` codec name
+    #[must_use]
+    pub fn from_ffprobe(name: &str) -> Self {
+        match name {
+            "h264" => Self::H264,
+            "hevc" => Self::H265,
+            "vp8" => Self::Vp8,
+            "vp9" => Self::Vp9,
+            "av1" => Self::Av1,
+            _ => Self::Other,
+        }
+    }
+
+    /// Parse the codec by the `yt-dlp` codec string, e.g. `avc1.64001F` or `vp09.00.40.08`.
+    /// Returns `None` for `none`, which `yt-dlp` uses for the missing stream.
+    #[must_use]
+    pub fn from_ytdlp(codec: &str) -> Option<Self> {
+        let codec = codec.to_ascii_lowercase();
+        let codec = match codec.split('.').next().unwrap_or_default() {
+            "" | "none" => return None,
+            "avc1" | "avc3" | "h264" => Self::H264,
+            "hvc1" | "hev1" | "h265" | "hevc" => Self::H265,
+            "vp8" | "vp08" => Self::Vp8,
+            "vp9" | "vp09" => Self::Vp9,
+            "av01" | "av1" => Self::Av1,
+            _ => Self::Other,
+        };
+        Some(codec)
+    }
+
+    /// Telegram clients play only H.264 inline on every platform
+    #[inline]
+    #[must_use]
+    pub const fn is_telegram_compatible(self) -> bool {
+        matches!(self, Self::H264)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioCodec {
+    Aac,
+    Mp3,
+    Opus,
+    Vorbis,
+    Flac,
+    Other,
+}
+
+impl AudioCodec {
+    /// Parse the codec by the `ffprobe` codec name
+    #[must_use]
+    pub fn from_ffprobe(name: &str) -> Self {
+        match name {
+            "aac" => Self::Aac,
+            "mp3" => Self::Mp3,
+            "opus" => Self::Opus,
+            "vorbis" => Self::Vorbis,
+            "flac" => Self::Flac,
+            _ => Self::Other,
+        }
+    }
+
+    /// Parse the codec by the `yt-dlp` codec string, e.g. `mp4a.40.2` or `opus`.
+    /// Returns `None` for `none`, which `yt-dlp` uses for the missing stream.
+    #[must_use]
+    pub fn from_ytdlp(codec: &str) -> Option<Self> {
+        let codec = codec.to_ascii_lowercase();
+        let mut parts = codec.split('.');
+        let codec = match (parts.next().unwrap_or_default(), parts.next()) {
+            ("" | "none", _) => return None,
+            // MPEG-1/2 audio object types of the `mp4a` codec strings
+            ("mp4a", Some("69" | "6b")) | ("mp3", _) => Self::Mp3,
+            ("mp4a" | "aac" | "m4a", _) => Self::Aac,
+            ("opus", _) => Self::Opus,
+            ("vorbis", _) => Self::Vorbis,
+            ("flac", _) => Self::Flac,
+            _ => Self::Other,
+        };
+        Some(codec)
+    }
+
+    /// Audio of the `mp4` videos that Telegram clients play inline
+    #[inline]
+    #[must_use]
+    pub const fn is_telegram_compatible(self) -> bool {
+        matches!(self, Self::Aac | Self::Mp3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_video_telegram_compatibility() {
+        let cases = [
+            ("h264", true),
+            ("hevc", false),
+            ("vp8", false),
+            ("vp9", false),
+            ("av1", false),
+            ("mpeg4", false),
+        ];
+        for (name, compatible) in cases {
+            assert_eq!(VideoCodec::from_ffprobe(name).is_telegram_compatible(), compatible, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_video_from_ytdlp() {
+        let cases = [
+            ("avc1.64001F", Some(VideoCodec::H264)),
+            ("avc1.4d401e", Some(VideoCodec::H264)),
+            ("h264", Some(VideoCodec::H264)),
+            ("hvc1.1.6.L93.B0", Some(VideoCodec::H265)),
+            ("hev1.1.6.L93.B0", Some(VideoCodec::H265)),
+            ("vp8", Some(VideoCodec::Vp8)),
+            ("vp8.0", Some(VideoCodec::Vp8)),
+            ("vp9", Some(VideoCodec::Vp9)),
+            ("vp09.00.40.08", Some(VideoCodec::Vp9)),
+            ("av01.0.08M.08", Some(VideoCodec::Av1)),
+            ("theora", Some(VideoCodec::Other)),
+            ("none", None),
+            ("", None),
+        ];
+        for (codec, expected) in cases {
+            assert_eq!(VideoCodec::from_ytdlp(codec), expected, "{codec}");
+        }
+    }
+
+    #[test]
+    fn test_audio_from_ytdlp() {
+        let cases = [
+            ("mp4a.40.2", Some(AudioCodec::Aac)),
+            ("mp4a.40.5", Some(AudioCodec::Aac)),
+            ("aac", Some(AudioCodec::Aac)),
+            ("m4a", Some(AudioCodec::Aac)),
+            ("mp4a.6B", Some(AudioCodec::Mp3)),
+            ("mp4a.69", Some(AudioCodec::Mp3)),
+            ("mp3", Some(AudioCodec::Mp3)),
+            ("opus", Some(AudioCodec::Opus)),
+            ("vorbis", Some(AudioCodec::Vorbis)),
+            ("flac", Some(AudioCodec::Flac)),
+            ("fLaC", Some(AudioCodec::Flac)),
+            ("ac-3", Some(AudioCodec::Other)),
+            ("ec-3", Some(AudioCodec::Other)),
+            ("none", None),
+            ("", None),
+        ];
+        for (codec, expected) in cases {
+            assert_eq!(AudioCodec::from_ytdlp(codec), expected, "{codec}");
+        }
+    }
+
+    #[test]
+    fn test_audio_telegram_compatibility() {
+        let cases = [
+            ("aac", true),
+            ("mp3", true),
+            ("opus", false),
+            ("vorbis", false),
+            ("flac", false),
+            ("ac3", false),
+        ];
+        for (name, compatible) in cases {
+            assert_eq!(AudioCodec::from_ffprobe(name).is_telegram_compatible(), compatible, "{name}");
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use super::{AudioCodec, VideoCodec};
+
+/// Containers tried in order when the preferred one can't keep the streams
+const VIDEO_FALLBACKS: [Container; 3] = [Container::Mp4, Container::Webm, Container::Mkv];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Mp4,
+    M4a,
+    Mov,
+    Webm,
+    Mkv,
+    Mp3,
+    Ogg,
+    Opus,
+    Flac,
+    Wav,
+    Jpeg,
+    Png,
+    Webp,
+    Gif,
+    Other,
+}
+
+impl Container {
+    #[must_use]
+    pub fn from_extension(extension: &str) -> Self {
+        match extension.to_ascii_lowercase().as_str() {
+            "mp4" => Self::Mp4,
+            "m4a" => Self::M4a,
+            "mov" => Self::Mov,
+            "webm" => Self::Webm,
+            "mkv" => Self::Mkv,
+            "mp3" => Self::Mp3,
+            "ogg" | "oga" => Self::Ogg,
+            "opus" => Self::Opus,
+            "flac" => Self::Flac,
+            "wav" => Self::Wav,
+            "jpg" | "jpeg" => Self::Jpeg,
+            "png" => Self::Png,
+            "webp" => Self::Webp,
+            "gif" => Self::Gif,
+            _ => Self::Other,
+        }
+    }
+
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Mp4 => "mp4",
+            Self::M4a => "m4a",
+            Self::Mov => "mov",
+            Self::Webm => "webm",
+            Self::Mkv => "mkv",
+            Self::Mp3 => "mp3",
+            Self::Ogg => "ogg",
+            Self::Opus => "opus",
+            Self::Flac => "flac",
+            Self::Wav => "wav",
+            Self::Jpeg => "jpg",
+            Self::Png => "png",
+            Self::Webp => "webp",
+            Self::Gif => "gif",
+            Self::Other => "bin",
+        }
+    }
+
+    /// Containers that can be both video and audio-only are distinguished by `has_video`
+    #[must_use]
+    pub const fn mime_type(self, has_video: bool) -> &'static str {
+        match (self, has_video) {
+            (Self::Mp4, true) => "video/mp4",
+            (Self::Mp4 | Self::M4a, _) => "audio/mp4",
+            (Self::Mov, _) => "video/quicktime",
+            (Self::Webm, true) => "video/webm",
+            (Self::Webm, false) => "audio/webm",
+            (Self::Mkv, true) => "video/x-matroska",
+            (Self::Mkv, false) => "audio/x-matroska",
+            (Self::Mp3, _) => "audio/mpeg",
+            (Self::Ogg | Self::Opus, _) => "audio/ogg",
+            (Self::Flac, _) => "audio/flac",
+            (Self::Wav, _) => "audio/wav",
+            (Self::Jpeg, _) => "image/jpeg",
+            (Self::Png, _) => "image/png",
+            (Self::Webp, _) => "image/webp",
+            (Self::Gif, _) => "image/gif",
+            (Self::Other, _) => "application/octet-stream",
+        }
+    }
+
+    /// Name of the `ffmpeg` muxer, Matroska is used for the unknown containers as it keeps any streams
+    #[must_use]
+    pub const fn muxer(self) -> &'static str {
+        match self {
+            Self::Mp4 => "mp4",
+            Self::M4a => "ipod",
+            Self::Mov => "mov",
+            Self::Webm => "webm",
+            Self::Mkv | Self::Other => "matroska",
+            Self::Mp3 => "mp3",
+            Self::Ogg => "ogg",
+            Self::Opus => "opus",
+            Self::Flac => "flac",
+            Self::Wav => "wav",
+            Self::Jpeg | Self::Png | Self::Webp => "image2",
+            Self::Gif => "gif",
+        }
+    }
+
+    /// Value of `yt-dlp --audio-format` that produces the file with this extension
+    #[must_use]
+    pub const fn ytdlp_audio_format(self) -> Option<&'static str> {
+        match self {
+            Self::M4a => Some("m4a"),
+            Self::Mp3 => Some("mp3"),
+            Self::Ogg => Some("vorbis"),
+            Self::Opus => Some("opus"),
+            Self::Flac => Some("flac"),
+            Self::Wav => Some("wav"),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn supports_video(self, codec: VideoCodec) -> bool {
+        match self {
+            Self::Mp4 => matches!(codec, VideoCodec::H264 | VideoCodec::H265 | VideoCodec::Vp9 | VideoCodec::Av1),
+            Self::Mov => matches!(codec, VideoCodec::H264 | VideoCodec::H265),
+            Self::Webm => matches!(codec, VideoCodec::Vp8 | VideoCodec::Vp9 | VideoCodec::Av1),
+            Self::Mkv => true,
+            _ => false,
+        }
+    }
+
+    #[must_use]
+    pub const fn supports_audio(self, codec: AudioCodec) -> bool {
+        match self {
+            Self::Mp4 => matches!(codec, AudioCodec::Aac | AudioCodec::Mp3 | AudioCodec::Opus | AudioCodec::Flac),
+            Self::Mov => matches!(codec, AudioCodec::Aac | AudioCodec::Mp3),
+            Self::M4a => matches!(codec, AudioCodec::Aac),
+            Self::Webm => matches!(codec, AudioCodec::Opus | AudioCodec::Vorbis),
+            Self::Ogg => matches!(codec, AudioCodec::Opus | AudioCodec::Vorbis | AudioCodec::Flac),
+            Self::Opus => matches!(codec, AudioCodec::Opus),
+            Self::Mp3 => matches!(codec, AudioCodec::Mp3),
+            Self::Flac => matches!(codec, AudioCodec::Flac),
+            Self::Mkv => true,
+            _ => false,
+        }
+    }
+
+    /// Container of the audio extracted without transcoding, the unknown codecs are transcoded to `m4a`
+    #[must_use]
+    pub const fn for_audio(codec: AudioCodec) -> Self {
+        match codec {
+            AudioCodec::Aac | AudioCodec::Other => Self::M4a,
+            AudioCodec::Mp3 => Self::Mp3,
+            AudioCodec::Opus => Self::Opus,
+            AudioCodec::Vorbis => Self::Ogg,
+            AudioCodec::Flac => Self::Flac,
+        }
+    }
+
+    /// The preferred container if it keeps the video and the audio tracks, otherwise `mp4`, `webm` or `mkv`, whichever keeps them first.
+    /// Unknown codecs aren't checked, so they're passed as `None` or skipped.
+    #[must_use]
+    pub fn for_video(preferred: Self, video: Option<VideoCodec>, audio: &[AudioCodec]) -> Self {
+        let keeps = |container: Self| {
+            video.is_none_or(|codec| container.supports_video(codec)) && audio.iter().all(|codec| container.supports_audio(*codec))
+        };
+        if (VIDEO_FALLBACKS.contains(&preferred) || preferred == Self::Mov) && keeps(preferred) {
+            return preferred;
+        }
+        VIDEO_FALLBACKS.into_iter().find(|container| keeps(*container)).unwrap_or(Self::Mkv)
+    }
+
+    /// Containers based on the ISO BMFF, where the `moov` atom can be moved to the beginning of the file
+    #[inline]
+    #[must_use]
+    pub const fn supports_faststart(self) -> bool {
+        matches!(self, Self::Mp4 | Self::M4a | Self::Mov)
+    }
+
+    /// Containers that can keep an image stream as the attached cover art
+    #[inline]
+    #[must_use]
+    pub const fn supports_cover_art(self) -> bool {
+        matches!(self, Self::Mp4 | Self::M4a | Self::Mp3 | Self::Flac)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIDEO_CODECS: [VideoCodec; 6] = [
+        VideoCodec::H264,
+        VideoCodec::H265,
+        VideoCodec::Vp8,
+        VideoCodec::Vp9,
+        VideoCodec::Av1,
+        VideoCodec::Other,
+    ];
+    const AUDIO_CODECS: [AudioCodec; 6] = [
+        AudioCodec::Aac,
+        AudioCodec::Mp3,
+        AudioCodec::Opus,
+        AudioCodec::Vorbis,
+        AudioCodec::Flac,
+        AudioCodec::Other,
+    ];
+    const CONTAINERS: [Container; 15] = [
+        Container::Mp4,
+        Container::M4a,
+        Container::Mov,
+        Container::Webm,
+        Container::Mkv,
+        Container::Mp3,
+        Container::Ogg,
+        Container::Opus,
+        Container::Flac,
+        Container::Wav,
+        Container::Jpeg,
+        Container::Png,
+        Container::Webp,
+        Container::Gif,
+        Container::Other,
+    ];
+
+    #[test]
+    fn test_extension_roundtrip() {
+        for container in CONTAINERS {
+            assert_eq!(Container::from_extension(container.as_str()), container, "{container:?}");
+        }
+        assert_eq!(Container::from_extension("MP4"), Container::Mp4);
+        assert_eq!(Container::from_extension("oga"), Container::Ogg);
+        assert_eq!(Container::from_extension("jpeg"), Container::Jpeg);
+    }
+
+    #[test]
+    fn test_video_matrix() {
+        // H264, H265, VP8, VP9, AV1, other
+        let cases = [
+            (Container::Mp4, [true, true, false, true, true, false]),
+            (Container::Mov, [true, true, false, false, false, false]),
+            (Container::Webm, [false, false, true, true, true, false]),
+            (Container::Mkv, [true, true, true, true, true, true]),
+        ];
+        for container in CONTAINERS {
+            let expected = cases
+                .iter()
+                .find(|(case, _)| *case == container)
+                .map_or([false; 6], |(_, expected)| *expected);
+            for (codec, expected) in VIDEO_CODECS.into_iter().zip(expected) {
+                assert_eq!(container.supports_video(codec), expected, "{container:?} {codec:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_audio_matrix() {
+        // AAC, MP3, Opus, Vorbis, FLAC, other
+        let cases = [
+            (Container::Mp4, [true, true, true, false, true, false]),
+            (Container::Mov, [true, true, false, false, false, false]),
+            (Container::M4a, [true, false, false, false, false, false]),
+            (Container::Webm, [false, false, true, true, false, false]),
+            (Container::Ogg, [false, false, true, true, true, false]),
+            (Container::Opus, [false, false, true, false, false, false]),
+            (Container::Mp3, [false, true, false, false, false, false]),
+            (Container::Flac, [false, false, false, false, true, false]),
+            (Container::Mkv, [true, true, true, true, true, true]),
+        ];
+        for container in CONTAINERS {
+            let expected = cases
+                .iter()
+                .find(|(case, _)| *case == container)
+                .map_or([false; 6], |(_, expected)| *expected);
+            for (codec, expected) in AUDIO_CODECS.into_iter().zip(expected) {
+                assert_eq!(container.supports_audio(codec), expected, "{container:?} {codec:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_for_audio() {
+        for codec in AUDIO_CODECS {
+            let container = Container::for_audio(codec);
+            let expected = match codec {
+                AudioCodec::Aac | AudioCodec::Other => Container::M4a,
+                AudioCodec::Mp3 => Container::Mp3,
+                AudioCodec::Opus => Container::Opus,
+                AudioCodec::Vorbis => Container::Ogg,
+                AudioCodec::Flac => Container::Flac,
+            };
+            assert_eq!(container, expected, "{codec:?}");
+            // `yt-dlp` writes the file with the extension of the container
+            assert!(container.ytdlp_audio_format().is_some(), "{codec:?}");
+            if codec != AudioCodec::Other {
+                assert!(container.supports_audio(codec), "{codec:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_for_video() {
+        let cases = [
+            (Container::Mp4, VideoCodec::H264, AudioCodec::Aac, Container::Mp4),
+            (Container::Mp4, VideoCodec::H264, AudioCodec::Opus, Container::Mp4),
+            (Container::Mp4, VideoCodec::Vp8, AudioCodec::Aac, Container::Mkv),
+            (Container::Mp4, VideoCodec::H264, AudioCodec::Vorbis, Container::Mkv),
+            (Container::Webm, VideoCodec::Vp9, AudioCodec::Opus, Container::Webm),
+            (Container::Webm, VideoCodec::Vp9, AudioCodec::Aac, Container::Mp4),
+            (Container::Webm, VideoCodec::Vp8, AudioCodec::Vorbis, Container::Webm),
+            (Container::Webm, VideoCodec::H264, AudioCodec::Opus, Container::Mp4),
+            (Container::Mkv, VideoCodec::H264, AudioCodec::Aac, Container::Mkv),
+            (Container::Mov, VideoCodec::H264, AudioCodec::Aac, Container::Mov),
+            (Container::Mov, VideoCodec::Vp9, AudioCodec::Aac, Container::Mp4),
+            (Container::M4a, VideoCodec::H264, AudioCodec::Aac, Container::Mp4),
+            (Container::Other, VideoCodec::Vp9, AudioCodec::Vorbis, Container::Webm),
+            (Container::Mp4, VideoCodec::Other, AudioCodec::Aac, Container::Mkv),
+        ];
+        for (preferred, video, audio, expected) in cases {
+            assert_eq!(
+                Container::for_video(preferred, Some(video), &[audio]),
+                expected,
+                "{preferred:?} {video:?} {audio:?}"
+            );
+        }
+
+        // Every pair gets a container that keeps both streams
+        for preferred in CONTAINERS {
+            for video in VIDEO_CODECS {
+                for audio in AUDIO_CODECS {
+                    let container = Container::for_video(preferred, Some(video), &[audio]);
+                    assert!(container.supports_video(video) && container.supports_audio(audio));
+                }
+            }
+        }
+
+        assert_eq!(Container::for_video(Container::Webm, None, &[AudioCodec::Opus]), Container::Webm);
+        assert_eq!(Container::for_video(Container::Webm, None, &[AudioCodec::Aac]), Container::Mp4);
+        assert_eq!(Container::for_video(Container::Mp4, None, &[]), Container::Mp4);
+        assert_eq!(
+            Container::for_video(Container::Webm, Some(VideoCodec::Vp9), &[AudioCodec::Opus, AudioCodec::Aac]),
+            Container::Mp4
+        );
+        assert_eq!(
+            Container::for_video(Container::Mp4, Some(VideoCodec::H264), &[AudioCodec::Aac, AudioCodec::Vorbis]),
+            Container::Mkv
+        );
+    }
+
+    #[test]
+    fn test_muxer() {
+        let cases = [
+            (Container::Mp4, "mp4"),
+            (Container::M4a, "ipod"),
+            (Container::Mov, "mov"),
+            (Container::Webm, "webm"),
+            (Container::Mkv, "matroska"),
+            (Container::Mp3, "mp3"),
+            (Container::Ogg, "ogg"),
+            (Container::Opus, "opus"),
+            (Container::Flac, "flac"),
+            (Container::Wav, "wav"),
+            (Container::Jpeg, "image2"),
+            (Container::Png, "image2"),
+            (Container::Webp, "image2"),
+            (Container::Gif, "gif"),
+            (Container::Other, "matroska"),
+        ];
+        assert_eq!(cases.len(), CONTAINERS.len());
+        for (container, muxer) in cases {
+            assert_eq!(container.muxer(), muxer, "{container:?}");
+        }
+    }
+
+    #[test]
+    fn test_mime_type() {
+        let cases = [
+            (Container::Mp4, "video/mp4", "audio/mp4"),
+            (Container::M4a, "audio/mp4", "audio/mp4"),
+            (Container::Mov, "video/quicktime", "video/quicktime"),
+            (Container::Webm, "video/webm", "audio/webm"),
+            (Container::Mkv, "video/x-matroska", "audio/x-matroska"),
+            (Container::Mp3, "audio/mpeg", "audio/mpeg"),
+            (Container::Ogg, "audio/ogg", "audio/ogg"),
+            (Container::Opus, "audio/ogg", "audio/ogg"),
+            (Container::Flac, "audio/flac", "audio/flac"),
+            (Container::Wav, "audio/wav", "audio/wav"),
+            (Container::Jpeg, "image/jpeg", "image/jpeg"),
+            (Container::Png, "image/png", "image/png"),
+            (Container::Webp, "image/webp", "image/webp"),
+            (Container::Gif, "image/gif", "image/gif"),
+            (Container::Other, "application/octet-stream", "application/octet-stream"),
+        ];
+        assert_eq!(cases.len(), CONTAINERS.len());
+        for (container, video, audio) in cases {
+            assert_eq!(container.mime_type(true), video, "{container:?}");
+            assert_eq!(container.mime_type(false), audio, "{container:?}");
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use serde::Deserialize;
+
+/// Live status of the media as reported by `yt-dlp`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LiveStatus {
+    NotLive,
+    IsLive,
+    IsUpcoming,
+    WasLive,
+    PostLive,
+}
@@ -0,0 +1,6 @@
+/// Kind of the media file detected by its streams
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    Video,
+    Audio,
+}
@@ -0,0 +1,28 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SponsorBlockCategory {
+    Sponsor,
+    SelfPromo,
+    Interaction,
+    Intro,
+    Outro,
+    Preview,
+    MusicOfftopic,
+    Filler,
+}
+
+impl SponsorBlockCategory {
+    /// Name used by the API and `yt-dlp`
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Sponsor => "sponsor",
+            Self::SelfPromo => "selfpromo",
+            Self::Interaction => "interaction",
+            Self::Intro => "intro",
+            Self::Outro => "outro",
+            Self::Preview => "preview",
+            Self::MusicOfftopic => "music_offtopic",
+            Self::Filler => "filler",
+        }
+    }
+}